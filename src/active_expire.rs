@@ -0,0 +1,65 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::global_config_get;
+use crate::store::{with_databases, Database};
+
+/// Keys sampled per iteration at the lowest effort, like Redis' ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP.
+const KEYS_PER_LOOP: usize = 20;
+/// Percentage of expired keys among the sampled ones above which the cycle keeps going.
+const ACCEPTABLE_STALE: usize = 10;
+/// Percentage of the time between two cycles that a cycle may spend expiring keys.
+const SLOW_TIME_PERC: u64 = 25;
+
+/// Start the background thread that reclaims expired keys nobody reads.
+/// It runs `hz` times per second and samples more keys per run as `active-expire-effort` grows.
+/// Both settings are re-read before every cycle.
+pub fn start_active_expire() {
+    thread::Builder::new()
+        .name("active-expire".to_owned())
+        .spawn(|| loop {
+            let hz = config_integer("hz", 10, 1, 500);
+            let effort = config_integer("active-expire-effort", 1, 1, 10);
+            active_expire_cycle(hz, effort);
+            thread::sleep(Duration::from_millis(1000 / hz));
+        })
+        .expect("Could not start the active expire thread");
+}
+
+/// Read an integer setting, clamped to the range Redis accepts for it.
+fn config_integer(name: &str, default: u64, min: u64, max: u64) -> u64 {
+    global_config_get(name)
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(default)
+        .clamp(min, max)
+}
+
+/// In each database in turn, repeatedly sample keys with a time to live and delete the expired ones, for as
+/// long as a large share of the sample turns out to be expired and the cycle stays within its time budget.
+/// The store lock is released between iterations so clients are not starved.
+fn active_expire_cycle(hz: u64, effort: u64) {
+    let effort = (effort - 1) as usize;
+    let keys_per_loop = KEYS_PER_LOOP + KEYS_PER_LOOP / 4 * effort;
+    let acceptable_stale = ACCEPTABLE_STALE - effort;
+    let time_limit = Duration::from_micros(1_000_000 * (SLOW_TIME_PERC + 2 * effort as u64) / hz / 100);
+
+    let start = Instant::now();
+    let mut total_expired = 0;
+    let database_count = with_databases(|databases, _| databases.len());
+    for index in 0..database_count {
+        loop {
+            let (sampled, expired) = with_databases(|databases, _| databases[index].expire_random_keys(keys_per_loop));
+            total_expired += expired;
+            if sampled == 0 || expired * 100 / sampled <= acceptable_stale || start.elapsed() > time_limit {
+                break;
+            }
+        }
+        if start.elapsed() > time_limit {
+            break;
+        }
+    }
+    if total_expired > 0 {
+        let remaining = with_databases(|databases, _| databases.iter().map(Database::volatile_count).sum::<usize>());
+        log::debug!("Active expire cycle deleted {} keys, {} keys with a TTL left", total_expired, remaining);
+    }
+}
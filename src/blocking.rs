@@ -0,0 +1,170 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::RedisError;
+use crate::serialization::RespValue;
+use crate::server::client_disconnected;
+use crate::stats;
+use crate::store::{with_store, Database};
+
+/// How often a blocked client checks whether its connection was closed.
+const DISCONNECT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Attempt to serve a blocked command once the given key is ready, returning its reply on success or
+/// None to keep waiting, for example because another client got to the new elements first.
+pub type Serve = Box<dyn FnMut(&mut Database, &[u8]) -> Option<Result<RespValue, RedisError>> + Send>;
+
+/// A client blocked on one or more keys.
+pub struct Waiter {
+    keys: Vec<Vec<u8>>,
+    serve: Mutex<Serve>,
+    reply: Mutex<Option<Result<RespValue, RedisError>>>,
+    served: Condvar,
+}
+
+/// Clients blocked on keys, in the order they blocked, and the keys that may now let some of them through.
+#[derive(Default)]
+pub struct BlockedClients {
+    waiters: HashMap<Vec<u8>, VecDeque<Arc<Waiter>>>,
+    ready: VecDeque<Vec<u8>>,
+}
+
+impl BlockedClients {
+    fn register(&mut self, waiter: &Arc<Waiter>) {
+        for key in &waiter.keys {
+            self.waiters.entry(key.clone()).or_default().push_back(waiter.clone());
+        }
+        stats::increment(&stats::BLOCKED_CLIENTS, 1);
+    }
+
+    /// Stop waiting on the keys. Does nothing if the client was already unregistered.
+    fn unregister(&mut self, waiter: &Arc<Waiter>) {
+        let mut registered = false;
+        for key in &waiter.keys {
+            let Some(queue) = self.waiters.get_mut(key) else {
+                continue;
+            };
+            let length = queue.len();
+            queue.retain(|other| !Arc::ptr_eq(other, waiter));
+            registered |= queue.len() != length;
+            if queue.is_empty() {
+                self.waiters.remove(key);
+            }
+        }
+        if registered {
+            stats::decrement(&stats::BLOCKED_CLIENTS);
+        }
+    }
+
+    /// Every key some client waits for.
+    pub fn waited_keys(&self) -> Vec<Vec<u8>> {
+        self.waiters.keys().cloned().collect()
+    }
+
+    /// Note that the key was written to, so that clients blocked on it get a chance to be served once the
+    /// current command is done. Does nothing if nobody waits for the key.
+    pub fn signal_key_ready(&mut self, key: &[u8]) {
+        if self.waiters.contains_key(key) && !self.ready.iter().any(|ready| ready == key) {
+            self.ready.push_back(key.to_vec());
+        }
+    }
+}
+
+/// Serve the clients blocked on keys that became ready, first come first served.
+/// Called with the keyspace locked after every command, so clients are served before anyone else
+/// can take the new elements. Serving a client may make more keys ready, as BLMOVE pushes to its destination.
+pub fn serve_ready_keys(database: &mut Database) {
+    while let Some(key) = database.blocked_clients().ready.pop_front() {
+        let waiters = database.blocked_clients().waiters.get(&key).cloned().unwrap_or_default();
+        for waiter in waiters {
+            let served = (waiter.serve.lock().unwrap())(database, &key);
+            // Like in Redis, a key of the wrong type keeps the client blocked rather than failing it.
+            if let Some(reply) = served.filter(|reply| *reply != Err(RedisError::WrongType)) {
+                database.blocked_clients().unregister(&waiter);
+                *waiter.reply.lock().unwrap() = Some(reply);
+                waiter.served.notify_one();
+            }
+        }
+    }
+}
+
+/// Outcome of the first attempt at a blocking command.
+enum Attempt {
+    Served(Result<RespValue, RedisError>),
+    Blocked(Arc<Waiter>),
+}
+
+/// Parse the timeout of a blocking command, in seconds with decimals allowed. 0 means wait forever.
+pub fn parse_timeout(arg: &[u8]) -> Result<Option<Duration>, RedisError> {
+    let seconds = std::str::from_utf8(arg)
+        .ok()
+        .and_then(|text| text.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite())
+        .ok_or_else(|| RedisError::Other("timeout is not a float or out of range".to_owned()))?;
+    if seconds < 0.0 {
+        return Err(RedisError::Other("timeout is negative".to_owned()));
+    }
+    Ok(Some(Duration::from_secs_f64(seconds)).filter(|timeout| !timeout.is_zero()))
+}
+
+/// Run a blocking command: try `serve` on each key in order and reply right away if one of them succeeds,
+/// otherwise park the connection until another client makes one of the keys ready and `serve` succeeds,
+/// the timeout expires, or the client disconnects. Timing out replies with the null array.
+pub fn block_on_keys(keys: &[Vec<u8>], timeout: Option<Duration>, mut serve: Serve) -> Result<RespValue, RedisError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let attempt = with_store(|database| {
+        for key in keys {
+            if let Some(reply) = serve(database, key) {
+                return Attempt::Served(reply);
+            }
+        }
+        let waiter = Arc::new(Waiter {
+            keys: keys.to_vec(),
+            serve: Mutex::new(serve),
+            reply: Mutex::new(None),
+            served: Condvar::new(),
+        });
+        database.blocked_clients().register(&waiter);
+        Attempt::Blocked(waiter)
+    });
+    match attempt {
+        Attempt::Served(reply) => reply,
+        Attempt::Blocked(waiter) => wait_until_served(&waiter, deadline),
+    }
+}
+
+fn wait_until_served(waiter: &Arc<Waiter>, deadline: Option<Instant>) -> Result<RespValue, RedisError> {
+    let mut reply = waiter.reply.lock().unwrap();
+    loop {
+        if let Some(reply) = reply.take() {
+            return reply;
+        }
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| now >= deadline) || client_disconnected() {
+            drop(reply);
+            // The client may have been served right before it was unregistered.
+            return with_store(|database| {
+                database.blocked_clients().unregister(waiter);
+                waiter.reply.lock().unwrap().take().unwrap_or(Ok(RespValue::NullArray))
+            });
+        }
+        let wait = deadline.map_or(DISCONNECT_POLL_INTERVAL, |deadline| (deadline - now).min(DISCONNECT_POLL_INTERVAL));
+        reply = waiter.served.wait_timeout(reply, wait).unwrap().0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("0", Ok(None))]
+    #[case("1.5", Ok(Some(Duration::from_millis(1500))))]
+    #[case("-1", Err(RedisError::Other("timeout is negative".to_owned())))]
+    #[case("soon", Err(RedisError::Other("timeout is not a float or out of range".to_owned())))]
+    fn test_parse_timeout(#[case] input: &str, #[case] expected: Result<Option<Duration>, RedisError>) {
+        assert_eq!(parse_timeout(input.as_bytes()), expected);
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+
+use super::{check_arity, CommandResult};
+use crate::config::{global_config_get, global_config_get_keys, global_config_set};
+use crate::config_file::rewrite_config_file;
+use crate::error::RedisError;
+use crate::serialization::RespValue;
+use crate::stats::reset_stats;
+
+pub fn config_execute(args: &[Vec<u8>]) -> CommandResult {
+    match String::from_utf8_lossy(&args[0]).to_uppercase().as_str() {
+        "GET" => {
+            check_arity("config|get", -3, args)?;
+            config_get(&args[1..])
+        }
+        "SET" => {
+            check_arity("config|set", -4, args)?;
+            if args.len().is_multiple_of(2) {
+                return Err(RedisError::WrongArity("config|set".to_owned()));
+            }
+            config_set(&args[1..])
+        }
+        "REWRITE" => {
+            check_arity("config|rewrite", 2, args)?;
+            rewrite_config_file().map_err(RedisError::Other)?;
+            Ok(RespValue::ok())
+        }
+        "RESETSTAT" => {
+            check_arity("config|resetstat", 2, args)?;
+            reset_stats();
+            Ok(RespValue::ok())
+        }
+        _ => Err(RedisError::UnknownSubcommand { command: "CONFIG".to_owned(), subcommand: String::from_utf8_lossy(&args[0]).into_owned() }),
+    }
+}
+
+fn config_get(args: &[Vec<u8>]) -> CommandResult {
+    let mut response: Vec<RespValue> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for arg in args {
+        let keys = global_config_get_keys(&String::from_utf8_lossy(arg));
+        // Several patterns may match the same parameter, it is only listed once.
+        for key in keys.into_iter().filter(|key| seen.insert(key.clone())) {
+            let value = global_config_get(&key).unwrap_or_default();
+            response.push(RespValue::bulk_string(key));
+            response.push(RespValue::bulk_string(value));
+        }
+    }
+    Ok(RespValue::Array(response))
+}
+
+/// Set every parameter/value pair, or none of them if any is rejected.
+fn config_set(args: &[Vec<u8>]) -> CommandResult {
+    let pairs = args
+        .chunks(2)
+        .map(|pair| (String::from_utf8_lossy(&pair[0]).into_owned(), String::from_utf8_lossy(&pair[1]).into_owned()))
+        .collect::<Vec<_>>();
+    global_config_set(&pairs)?;
+    Ok(RespValue::ok())
+}
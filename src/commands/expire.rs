@@ -0,0 +1,203 @@
+use super::{is_keyword, parse_integer, CommandResult};
+use crate::error::RedisError;
+use crate::serialization::RespValue;
+use crate::store::{now_ms, with_store};
+
+/// Milliseconds per unit of an expire time argument.
+pub const SECONDS: u64 = 1000;
+pub const MILLISECONDS: u64 = 1;
+
+/// Convert an expire time argument into an absolute Unix time in milliseconds.
+/// The result may lie in the past, or even be negative, which callers treat as already expired.
+fn expire_time_ms(value: i64, unit: u64, absolute: bool, command: &str) -> Result<i64, RedisError> {
+    let invalid = || RedisError::InvalidExpireTime(command.to_owned());
+    let value = value.checked_mul(unit as i64).ok_or_else(invalid)?;
+    if absolute {
+        return Ok(value);
+    }
+    value.checked_add(now_ms() as i64).ok_or_else(invalid)
+}
+
+/// Unit and absoluteness of the EX / PX / EXAT / PXAT options taken by SET and GETEX.
+pub fn expire_option(option: &[u8]) -> Option<(u64, bool)> {
+    if is_keyword(option, "EX") {
+        Some((SECONDS, false))
+    } else if is_keyword(option, "PX") {
+        Some((MILLISECONDS, false))
+    } else if is_keyword(option, "EXAT") {
+        Some((SECONDS, true))
+    } else if is_keyword(option, "PXAT") {
+        Some((MILLISECONDS, true))
+    } else {
+        None
+    }
+}
+
+/// Parse the expire time option of SET or GETEX, which unlike EXPIRE has to be strictly positive.
+pub fn parse_option_expire_time(arg: &[u8], unit: u64, absolute: bool, command: &str) -> Result<u64, RedisError> {
+    let value = parse_integer(arg)?;
+    if value <= 0 {
+        return Err(RedisError::InvalidExpireTime(command.to_owned()));
+    }
+    Ok(expire_time_ms(value, unit, absolute, command)? as u64)
+}
+
+/// The NX / XX / GT / LT flags of the EXPIRE family.
+#[derive(Default)]
+struct ExpireCondition {
+    nx: bool,
+    xx: bool,
+    gt: bool,
+    lt: bool,
+}
+
+impl ExpireCondition {
+    fn parse(options: &[Vec<u8>]) -> Result<ExpireCondition, RedisError> {
+        let mut condition = ExpireCondition::default();
+        for option in options {
+            if is_keyword(option, "NX") {
+                condition.nx = true;
+            } else if is_keyword(option, "XX") {
+                condition.xx = true;
+            } else if is_keyword(option, "GT") {
+                condition.gt = true;
+            } else if is_keyword(option, "LT") {
+                condition.lt = true;
+            } else {
+                return Err(RedisError::Other(format!("Unsupported option {}", String::from_utf8_lossy(option))));
+            }
+        }
+        if condition.nx && (condition.xx || condition.gt || condition.lt) {
+            return Err(RedisError::Other("NX and XX, GT or LT options at the same time are not compatible".to_owned()));
+        }
+        if condition.gt && condition.lt {
+            return Err(RedisError::Other("GT and LT options at the same time are not compatible".to_owned()));
+        }
+        Ok(condition)
+    }
+
+    /// Whether a key currently expiring at `current` may be given the new expiry time.
+    /// A key without a time to live counts as expiring infinitely far in the future.
+    fn allows(&self, current: Option<u64>, new: i64) -> bool {
+        if self.nx && current.is_some() {
+            return false;
+        }
+        if self.xx && current.is_none() {
+            return false;
+        }
+        if self.gt && current.is_none_or(|current| new <= current as i64) {
+            return false;
+        }
+        if self.lt && current.is_some_and(|current| new >= current as i64) {
+            return false;
+        }
+        true
+    }
+}
+
+/// Shared implementation of EXPIRE, PEXPIRE, EXPIREAT and PEXPIREAT.
+fn expire_generic(args: &[Vec<u8>], unit: u64, absolute: bool, command: &str) -> CommandResult {
+    let expires_at = expire_time_ms(parse_integer(&args[1])?, unit, absolute, command)?;
+    let condition = ExpireCondition::parse(&args[2..])?;
+    with_store(|database| {
+        let Some(entry) = database.get(&args[0]) else {
+            return Ok(RespValue::Integer(0));
+        };
+        if !condition.allows(entry.expires_at(), expires_at) {
+            return Ok(RespValue::Integer(0));
+        }
+        database.set_expiry(&args[0], Some(expires_at.max(0) as u64));
+        Ok(RespValue::Integer(1))
+    })
+}
+
+/// EXPIRE key seconds [NX | XX | GT | LT]
+pub fn expire_execute(args: &[Vec<u8>]) -> CommandResult {
+    expire_generic(args, SECONDS, false, "expire")
+}
+
+/// PEXPIRE key milliseconds [NX | XX | GT | LT]
+pub fn pexpire_execute(args: &[Vec<u8>]) -> CommandResult {
+    expire_generic(args, MILLISECONDS, false, "pexpire")
+}
+
+/// EXPIREAT key unix-time-seconds [NX | XX | GT | LT]
+pub fn expireat_execute(args: &[Vec<u8>]) -> CommandResult {
+    expire_generic(args, SECONDS, true, "expireat")
+}
+
+/// PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]
+pub fn pexpireat_execute(args: &[Vec<u8>]) -> CommandResult {
+    expire_generic(args, MILLISECONDS, true, "pexpireat")
+}
+
+/// Shared implementation of TTL, PTTL, EXPIRETIME and PEXPIRETIME.
+/// Replies -2 if the key does not exist and -1 if it has no time to live.
+fn ttl_generic(key: &[u8], unit: u64, absolute: bool) -> CommandResult {
+    with_store(|database| {
+        let reply = match database.get(key) {
+            None => -2,
+            Some(entry) => match entry.expires_at() {
+                None => -1,
+                Some(expires_at) if absolute => (expires_at / unit) as i64,
+                // Round to the nearest unit like Redis does.
+                Some(expires_at) => ((expires_at.saturating_sub(now_ms()) + unit / 2) / unit) as i64,
+            },
+        };
+        Ok(RespValue::Integer(reply))
+    })
+}
+
+/// TTL key
+pub fn ttl_execute(args: &[Vec<u8>]) -> CommandResult {
+    ttl_generic(&args[0], SECONDS, false)
+}
+
+/// PTTL key
+pub fn pttl_execute(args: &[Vec<u8>]) -> CommandResult {
+    ttl_generic(&args[0], MILLISECONDS, false)
+}
+
+/// EXPIRETIME key
+pub fn expiretime_execute(args: &[Vec<u8>]) -> CommandResult {
+    ttl_generic(&args[0], SECONDS, true)
+}
+
+/// PEXPIRETIME key
+pub fn pexpiretime_execute(args: &[Vec<u8>]) -> CommandResult {
+    ttl_generic(&args[0], MILLISECONDS, true)
+}
+
+/// PERSIST key
+pub fn persist_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let has_ttl = database.get(&args[0]).is_some_and(|entry| entry.expires_at().is_some());
+        if has_ttl {
+            database.set_expiry(&args[0], None);
+        }
+        Ok(RespValue::Integer(has_ttl as i64))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(&[], None, 10, true)]
+    #[case(&["NX"], None, 10, true)]
+    #[case(&["NX"], Some(5), 10, false)]
+    #[case(&["XX"], None, 10, false)]
+    #[case(&["XX"], Some(5), 10, true)]
+    #[case(&["GT"], None, 10, false)]
+    #[case(&["GT"], Some(5), 10, true)]
+    #[case(&["GT"], Some(15), 10, false)]
+    #[case(&["LT"], None, 10, true)]
+    #[case(&["LT"], Some(5), 10, false)]
+    #[case(&["xx", "lt"], Some(15), 10, true)]
+    fn test_expire_condition(#[case] options: &[&str], #[case] current: Option<u64>, #[case] new: i64, #[case] expected: bool) {
+        let options: Vec<Vec<u8>> = options.iter().map(|option| option.as_bytes().to_vec()).collect();
+        assert_eq!(ExpireCondition::parse(&options).unwrap().allows(current, new), expected);
+    }
+}
@@ -0,0 +1,353 @@
+use super::keyspace::{parse_cursor, scan_reply, ScanOptions};
+use super::{format_float, is_keyword, parse_float, parse_integer, parse_pick_count, CommandResult};
+use crate::error::RedisError;
+use crate::random::random_picks;
+use crate::serialization::RespValue;
+use crate::store::{scan_collection, with_store};
+
+/// Turn an optional field value into a bulk string reply, or the null bulk string if missing.
+fn bulk_or_null(value: Option<&Vec<u8>>) -> RespValue {
+    value.map_or(RespValue::NullBulkString, RespValue::bulk_string)
+}
+
+/// Split the field/value arguments of HSET and HMSET into pairs.
+fn field_value_pairs<'a>(args: &'a [Vec<u8>], command: &str) -> Result<std::slice::ChunksExact<'a, Vec<u8>>, RedisError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(RedisError::WrongArity(command.to_owned()));
+    }
+    Ok(args.chunks_exact(2))
+}
+
+/// Set every field/value pair, returning how many fields did not exist before.
+fn hash_set(args: &[Vec<u8>], command: &str) -> Result<usize, RedisError> {
+    let pairs = field_value_pairs(&args[1..], command)?;
+    with_store(|database| {
+        let hash = database.get_or_create_hash(&args[0])?;
+        Ok(pairs.filter(|pair| hash.insert(pair[0].clone(), pair[1].clone()).is_none()).count())
+    })
+}
+
+/// HSET key field value [field value ...]
+pub fn hset_execute(args: &[Vec<u8>]) -> CommandResult {
+    Ok(RespValue::Integer(hash_set(args, "hset")? as i64))
+}
+
+/// HMSET key field value [field value ...]
+/// Same as HSET but replies OK, kept for older clients.
+pub fn hmset_execute(args: &[Vec<u8>]) -> CommandResult {
+    hash_set(args, "hmset")?;
+    Ok(RespValue::ok())
+}
+
+/// HSETNX key field value
+pub fn hsetnx_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let hash = database.get_or_create_hash(&args[0])?;
+        if hash.contains_key(&args[1]) {
+            return Ok(RespValue::Integer(0));
+        }
+        hash.insert(args[1].clone(), args[2].clone());
+        Ok(RespValue::Integer(1))
+    })
+}
+
+/// HGET key field
+pub fn hget_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let hash = database.get_hash(&args[0])?;
+        Ok(bulk_or_null(hash.and_then(|hash| hash.get(&args[1]))))
+    })
+}
+
+/// HMGET key field [field ...]
+pub fn hmget_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let hash = database.get_hash(&args[0])?;
+        let values = args[1..]
+            .iter()
+            .map(|field| bulk_or_null(hash.and_then(|hash| hash.get(field))))
+            .collect();
+        Ok(RespValue::Array(values))
+    })
+}
+
+/// HDEL key field [field ...]
+/// The key is deleted once its last field is.
+pub fn hdel_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let Some(hash) = database.get_hash_mut(&args[0])? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let deleted = args[1..].iter().filter(|field| hash.remove(*field).is_some()).count();
+        database.remove_if_empty(&args[0]);
+        Ok(RespValue::Integer(deleted as i64))
+    })
+}
+
+/// HLEN key
+pub fn hlen_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let length = database.get_hash(&args[0])?.map_or(0, |hash| hash.len());
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// HSTRLEN key field
+pub fn hstrlen_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let length = database.get_hash(&args[0])?.and_then(|hash| hash.get(&args[1])).map_or(0, |value| value.len());
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// HEXISTS key field
+pub fn hexists_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let exists = database.get_hash(&args[0])?.is_some_and(|hash| hash.contains_key(&args[1]));
+        Ok(RespValue::Integer(exists as i64))
+    })
+}
+
+/// Reply with something derived from every field/value pair of a hash, or nothing if the key is missing.
+fn hash_elements(key: &[u8], element: impl Fn(&Vec<u8>, &Vec<u8>) -> Vec<RespValue>) -> CommandResult {
+    with_store(|database| {
+        let elements = database
+            .get_hash(key)?
+            .map(|hash| hash.iter().flat_map(|(field, value)| element(field, value)).collect())
+            .unwrap_or_default();
+        Ok(RespValue::Array(elements))
+    })
+}
+
+/// HKEYS key
+pub fn hkeys_execute(args: &[Vec<u8>]) -> CommandResult {
+    hash_elements(&args[0], |field, _| vec![RespValue::bulk_string(field)])
+}
+
+/// HVALS key
+pub fn hvals_execute(args: &[Vec<u8>]) -> CommandResult {
+    hash_elements(&args[0], |_, value| vec![RespValue::bulk_string(value)])
+}
+
+/// HGETALL key
+pub fn hgetall_execute(args: &[Vec<u8>]) -> CommandResult {
+    hash_elements(&args[0], |field, value| vec![RespValue::bulk_string(field), RespValue::bulk_string(value)])
+}
+
+/// HINCRBY key field increment
+pub fn hincrby_execute(args: &[Vec<u8>]) -> CommandResult {
+    let increment = parse_integer(&args[2])?;
+    with_store(|database| {
+        let hash = database.get_or_create_hash(&args[0])?;
+        let current = match hash.get(&args[1]) {
+            Some(value) => parse_integer(value)
+                .map_err(|_| RedisError::Other("hash value is not an integer".to_owned()))?,
+            None => 0,
+        };
+        let result = current.checked_add(increment).ok_or(RedisError::Overflow)?;
+        hash.insert(args[1].clone(), result.to_string().into_bytes());
+        Ok(RespValue::Integer(result))
+    })
+}
+
+/// HINCRBYFLOAT key field increment
+pub fn hincrbyfloat_execute(args: &[Vec<u8>]) -> CommandResult {
+    let increment = parse_float(&args[2])?;
+    // Checked before the hash is looked up, so that a new key is not left behind as an empty hash.
+    if !increment.is_finite() {
+        return Err(RedisError::Other("value is NaN or Infinity".to_owned()));
+    }
+    with_store(|database| {
+        let hash = database.get_or_create_hash(&args[0])?;
+        let current = match hash.get(&args[1]) {
+            Some(value) => parse_float(value).map_err(|_| RedisError::Other("hash value is not a float".to_owned()))?,
+            None => 0.0,
+        };
+        let result = current + increment;
+        if !result.is_finite() {
+            return Err(RedisError::Other("increment would produce NaN or Infinity".to_owned()));
+        }
+        let formatted = format_float(result).into_bytes();
+        hash.insert(args[1].clone(), formatted.clone());
+        Ok(RespValue::BulkString(formatted))
+    })
+}
+
+/// HRANDFIELD key [count [WITHVALUES]]
+/// Without a count a single field is returned, see `random_picks` for how the count is interpreted.
+pub fn hrandfield_execute(args: &[Vec<u8>]) -> CommandResult {
+    let count = args.get(1).map(|count| parse_pick_count(count)).transpose()?;
+    let with_values = match args.get(2) {
+        None => false,
+        Some(option) if args.len() == 3 && is_keyword(option, "WITHVALUES") => true,
+        Some(_) => return Err(RedisError::Syntax),
+    };
+    with_store(|database| {
+        let hash = database.get_hash(&args[0])?;
+        let Some(count) = count else {
+            let fields = hash.map(|hash| hash.keys().collect::<Vec<_>>()).unwrap_or_default();
+            return Ok(bulk_or_null(random_picks(&fields, 1).into_iter().next().copied()));
+        };
+        let pairs = hash.map(|hash| hash.iter().collect::<Vec<_>>()).unwrap_or_default();
+        let picked = random_picks(&pairs, count)
+            .into_iter()
+            .flat_map(|(field, value)| {
+                let mut elements = vec![RespValue::bulk_string(field)];
+                if with_values {
+                    elements.push(RespValue::bulk_string(value));
+                }
+                elements
+            })
+            .collect();
+        Ok(RespValue::Array(picked))
+    })
+}
+
+/// HSCAN key cursor [MATCH pattern] [COUNT count]
+/// Fields present during the whole iteration are returned at least once, along with their values.
+pub fn hscan_execute(args: &[Vec<u8>]) -> CommandResult {
+    let cursor = parse_cursor(&args[1])?;
+    let options = ScanOptions::parse(&args[2..], false)?;
+    with_store(|database| {
+        let Some(hash) = database.get_hash(&args[0])? else {
+            return Ok(scan_reply(0, Vec::new()));
+        };
+        let (cursor, fields) = scan_collection(hash.keys(), cursor, options.count);
+        let elements = fields
+            .into_iter()
+            .filter(|field| options.matches(field))
+            .flat_map(|field| [RespValue::bulk_string(field), RespValue::bulk_string(&hash[field])])
+            .collect();
+        Ok(scan_reply(cursor, elements))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_helpers::args;
+
+    #[test]
+    fn test_hdel_removes_empty_hash() {
+        hset_execute(&args(&["test_hdel", "a", "1", "b", "2"])).unwrap();
+        assert_eq!(hdel_execute(&args(&["test_hdel", "a", "b", "c"])).unwrap(), RespValue::Integer(2));
+        assert!(with_store(|database| database.get(b"test_hdel").is_none()));
+    }
+
+    #[test]
+    fn test_hscan_visits_every_field() {
+        let mut hset_args = args(&["test_hscan"]);
+        for index in 0..50 {
+            hset_args.extend(args(&[&format!("field{}", index), "v"]));
+        }
+        hset_execute(&hset_args).unwrap();
+
+        let mut fields = Vec::new();
+        let mut cursor = b"0".to_vec();
+        loop {
+            let reply = hscan_execute(&[b"test_hscan".to_vec(), cursor, b"COUNT".to_vec(), b"7".to_vec()]).unwrap();
+            let RespValue::Array(reply) = reply else { panic!("HSCAN replies with an array") };
+            let [RespValue::BulkString(next), RespValue::Array(elements)] = reply.as_slice() else {
+                panic!("HSCAN replies with a cursor and elements")
+            };
+            fields.extend(elements.iter().step_by(2).cloned());
+            cursor = next.clone();
+            if cursor == b"0" {
+                break;
+            }
+        }
+        assert_eq!(fields.len(), 50);
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        with_store(|database| database.set(b"test_hash_wrong_type".to_vec(), b"v".to_vec(), crate::store::Ttl::Persist));
+        assert_eq!(hget_execute(&args(&["test_hash_wrong_type", "f"])), Err(RedisError::WrongType));
+        assert_eq!(hset_execute(&args(&["test_hash_wrong_type", "f", "v"])), Err(RedisError::WrongType));
+    }
+
+    #[test]
+    fn test_hrandfield_rejects_huge_negative_count() {
+        hset_execute(&args(&["test_hrandfield_huge", "f", "v"])).unwrap();
+        let reply = hrandfield_execute(&args(&["test_hrandfield_huge", "-100000000000000"]));
+        assert_eq!(reply, Err(RedisError::Other("value is out of range".to_owned())));
+        assert_eq!(hrandfield_execute(&args(&["test_hrandfield_huge", &i64::MIN.to_string()])), reply);
+    }
+
+    #[test]
+    fn test_hincrbyfloat_infinite_increment_creates_no_key() {
+        let reply = hincrbyfloat_execute(&args(&["test_hincrbyfloat_inf", "f", "inf"]));
+        assert_eq!(reply, Err(RedisError::Other("value is NaN or Infinity".to_owned())));
+        assert!(with_store(|database| database.get(b"test_hincrbyfloat_inf").is_none()));
+    }
+
+    #[test]
+    fn test_hincrby_errors() {
+        hset_execute(&args(&["test_hincrby_errors", "max", &i64::MAX.to_string(), "text", "abc"])).unwrap();
+        assert_eq!(hincrby_execute(&args(&["test_hincrby_errors", "max", "1"])), Err(RedisError::Overflow));
+        let reply = hincrby_execute(&args(&["test_hincrby_errors", "max", "-1"])).unwrap();
+        assert_eq!(reply, RespValue::Integer(i64::MAX - 1));
+        assert_eq!(
+            hincrby_execute(&args(&["test_hincrby_errors", "text", "1"])),
+            Err(RedisError::Other("hash value is not an integer".to_owned()))
+        );
+        assert_eq!(hincrby_execute(&args(&["test_hincrby_errors", "new", "1.5"])), Err(RedisError::NotInteger));
+        assert_eq!(hget_execute(&args(&["test_hincrby_errors", "new"])).unwrap(), RespValue::NullBulkString);
+    }
+
+    #[test]
+    fn test_hincrbyfloat_errors() {
+        hset_execute(&args(&["test_hincrbyfloat_errors", "big", "1.7e308", "text", "abc"])).unwrap();
+        assert_eq!(
+            hincrbyfloat_execute(&args(&["test_hincrbyfloat_errors", "big", "1.7e308"])),
+            Err(RedisError::Other("increment would produce NaN or Infinity".to_owned()))
+        );
+        assert_eq!(
+            hincrbyfloat_execute(&args(&["test_hincrbyfloat_errors", "text", "1"])),
+            Err(RedisError::Other("hash value is not a float".to_owned()))
+        );
+        let reply = hincrbyfloat_execute(&args(&["test_hincrbyfloat_errors", "new", "abc"]));
+        assert_eq!(reply, Err(RedisError::NotFloat));
+        let reply = hincrbyfloat_execute(&args(&["test_hincrbyfloat_errors", "new", "0.5"])).unwrap();
+        assert_eq!(reply, RespValue::bulk_string("0.5"));
+    }
+
+    #[test]
+    fn test_hrandfield_counts() {
+        hset_execute(&args(&["test_hrandfield_counts", "a", "1", "b", "2", "c", "3"])).unwrap();
+        let RespValue::Array(fields) = hrandfield_execute(&args(&["test_hrandfield_counts", "10"])).unwrap() else {
+            panic!("HRANDFIELD with a count replies with an array")
+        };
+        // A positive count returns distinct fields, at most all of them.
+        let distinct = fields
+            .iter()
+            .filter_map(|field| if let RespValue::BulkString(field) = field { Some(field) } else { None })
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!((fields.len(), distinct.len()), (3, 3));
+
+        // A negative count may repeat fields, and returns exactly as many as asked.
+        let RespValue::Array(fields) = hrandfield_execute(&args(&["test_hrandfield_counts", "-10"])).unwrap() else {
+            panic!("HRANDFIELD with a count replies with an array")
+        };
+        assert_eq!(fields.len(), 10);
+        assert_eq!(hrandfield_execute(&args(&["test_hrandfield_counts", "0"])).unwrap(), RespValue::Array(Vec::new()));
+        let reply = hrandfield_execute(&args(&["test_hrandfield_missing", "-3"])).unwrap();
+        assert_eq!(reply, RespValue::Array(Vec::new()));
+    }
+
+    #[test]
+    fn test_hrandfield_with_values() {
+        hset_execute(&args(&["test_hrandfield_with_values", "a", "1", "b", "2"])).unwrap();
+        let reply = hrandfield_execute(&args(&["test_hrandfield_with_values", "-5", "WITHVALUES"])).unwrap();
+        let RespValue::Array(elements) = reply else { panic!("HRANDFIELD with a count replies with an array") };
+        assert_eq!(elements.len(), 10);
+        for pair in elements.chunks(2) {
+            let [RespValue::BulkString(field), RespValue::BulkString(value)] = pair else {
+                panic!("WITHVALUES replies with fields and values")
+            };
+            assert_eq!(*value, if field == b"a" { b"1".to_vec() } else { b"2".to_vec() });
+        }
+        let reply = hrandfield_execute(&args(&["test_hrandfield_with_values", "1", "WITHSCORES"]));
+        assert_eq!(reply, Err(RedisError::Syntax));
+    }
+}
@@ -0,0 +1,61 @@
+use super::CommandResult;
+use crate::config::global_config_get;
+use crate::maxmemory::{maxmemory, used_memory};
+use crate::serialization::RespValue;
+use crate::stats::{
+    self, BLOCKED_CLIENTS, CONNECTED_CLIENTS, EVICTED_KEYS, EXPIRED_KEYS, REJECTED_CONNECTIONS, TOTAL_COMMANDS_PROCESSED,
+    TOTAL_CONNECTIONS_RECEIVED,
+};
+use crate::store::with_databases;
+
+/// Sections listed when INFO is called without arguments, or with `default`, `all` or `everything`.
+const SECTIONS: [&str; 4] = ["clients", "memory", "stats", "keyspace"];
+
+pub fn info_execute(args: &[Vec<u8>]) -> CommandResult {
+    let requested = args.iter().map(|arg| String::from_utf8_lossy(arg).to_lowercase()).collect::<Vec<_>>();
+    let everything = requested.is_empty()
+        || requested.iter().any(|section| ["default", "all", "everything"].contains(&section.as_str()));
+
+    let sections = SECTIONS
+        .iter()
+        .filter(|section| everything || requested.iter().any(|requested| requested == *section))
+        .map(|section| info_section(section))
+        .collect::<Vec<_>>();
+    Ok(RespValue::bulk_string(sections.join("\r\n")))
+}
+
+fn info_section(section: &str) -> String {
+    let mut text = String::new();
+    match section {
+        "clients" => {
+            text.push_str("# Clients\r\n");
+            text.push_str(&format!("connected_clients:{}\r\n", stats::read(&CONNECTED_CLIENTS)));
+            text.push_str(&format!("blocked_clients:{}\r\n", stats::read(&BLOCKED_CLIENTS)));
+        }
+        "memory" => {
+            text.push_str("# Memory\r\n");
+            text.push_str(&format!("used_memory:{}\r\n", used_memory()));
+            text.push_str(&format!("maxmemory:{}\r\n", maxmemory()));
+            text.push_str(&format!("maxmemory_policy:{}\r\n", global_config_get("maxmemory-policy").unwrap_or_default()));
+        }
+        "stats" => {
+            text.push_str("# Stats\r\n");
+            text.push_str(&format!("total_connections_received:{}\r\n", stats::read(&TOTAL_CONNECTIONS_RECEIVED)));
+            text.push_str(&format!("total_commands_processed:{}\r\n", stats::read(&TOTAL_COMMANDS_PROCESSED)));
+            text.push_str(&format!("rejected_connections:{}\r\n", stats::read(&REJECTED_CONNECTIONS)));
+            text.push_str(&format!("expired_keys:{}\r\n", stats::read(&EXPIRED_KEYS)));
+            text.push_str(&format!("evicted_keys:{}\r\n", stats::read(&EVICTED_KEYS)));
+        }
+        "keyspace" => {
+            text.push_str("# Keyspace\r\n");
+            let counts = with_databases(|databases, _| {
+                databases.iter().map(|database| (database.key_count(), database.volatile_count())).collect::<Vec<_>>()
+            });
+            for (index, (keys, expires)) in counts.into_iter().enumerate().filter(|(_, (keys, _))| *keys > 0) {
+                text.push_str(&format!("db{}:keys={},expires={},avg_ttl=0\r\n", index, keys, expires));
+            }
+        }
+        _ => {}
+    }
+    text
+}
@@ -0,0 +1,354 @@
+use super::{is_keyword, parse_integer, CommandResult};
+use crate::blocking::serve_ready_keys;
+use crate::config::global_config_get;
+use crate::error::RedisError;
+use crate::glob::glob_match;
+use crate::serialization::RespValue;
+use crate::store::{free_in_background, select_database, with_databases, with_store};
+
+/// Number of elements SCAN style commands look at per call when no COUNT is given.
+pub const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// DEL key [key ...]
+pub fn del_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let deleted = args.iter().filter(|key| database.remove(key).is_some()).count();
+        Ok(RespValue::Integer(deleted as i64))
+    })
+}
+
+/// UNLINK key [key ...]
+/// Like DEL, but the memory of the removed values is reclaimed in the background.
+pub fn unlink_execute(args: &[Vec<u8>]) -> CommandResult {
+    let removed: Vec<_> = with_store(|database| args.iter().filter_map(|key| database.remove(key)).collect());
+    let count = removed.len();
+    if count > 0 {
+        free_in_background(removed);
+    }
+    Ok(RespValue::Integer(count as i64))
+}
+
+/// EXISTS key [key ...]
+/// A key given several times is counted several times.
+pub fn exists_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let existing = args.iter().filter(|key| database.get(key).is_some()).count();
+        Ok(RespValue::Integer(existing as i64))
+    })
+}
+
+/// TOUCH key [key ...]
+pub fn touch_execute(args: &[Vec<u8>]) -> CommandResult {
+    exists_execute(args)
+}
+
+/// TYPE key
+pub fn type_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let type_name = database.get(&args[0]).map_or("none", |entry| entry.type_name());
+        Ok(RespValue::SimpleString(type_name.to_owned()))
+    })
+}
+
+/// Shared implementation of RENAME and RENAMENX. The key keeps its time to live.
+/// Returns whether the key was renamed, which only fails when `replace` is false and the destination exists.
+fn rename_generic(source: &[u8], destination: &[u8], replace: bool) -> Result<bool, RedisError> {
+    with_store(|database| {
+        if database.get(source).is_none() {
+            return Err(RedisError::NoSuchKey);
+        }
+        if source == destination {
+            return Ok(replace);
+        }
+        if !replace && database.get(destination).is_some() {
+            return Ok(false);
+        }
+        let entry = database.remove(source).expect("Source key checked above");
+        database.insert(destination.to_vec(), entry);
+        Ok(true)
+    })
+}
+
+/// RENAME key newkey
+pub fn rename_execute(args: &[Vec<u8>]) -> CommandResult {
+    rename_generic(&args[0], &args[1], true)?;
+    Ok(RespValue::ok())
+}
+
+/// RENAMENX key newkey
+pub fn renamenx_execute(args: &[Vec<u8>]) -> CommandResult {
+    let renamed = rename_generic(&args[0], &args[1], false)?;
+    Ok(RespValue::Integer(renamed as i64))
+}
+
+/// COPY source destination [DB destination-db] [REPLACE]
+pub fn copy_execute(args: &[Vec<u8>]) -> CommandResult {
+    let mut replace = false;
+    let mut target = None;
+    let mut index = 2;
+    while index < args.len() {
+        if is_keyword(&args[index], "REPLACE") {
+            replace = true;
+        } else if is_keyword(&args[index], "DB") && index + 1 < args.len() {
+            index += 1;
+            target = Some(parse_integer(&args[index])?);
+        } else {
+            return Err(RedisError::Syntax);
+        }
+        index += 1;
+    }
+
+    let (source, destination) = (&args[0], &args[1]);
+    with_databases(|databases, selected| {
+        let target = match target {
+            Some(target) => database_index(target, databases.len())?,
+            None => selected,
+        };
+        if source == destination && target == selected {
+            return Err(RedisError::Other("source and destination objects are the same".to_owned()));
+        }
+        let Some(entry) = databases[selected].get(source).cloned() else {
+            return Ok(RespValue::Integer(0));
+        };
+        if !replace && databases[target].get(destination).is_some() {
+            return Ok(RespValue::Integer(0));
+        }
+        databases[target].insert(destination.to_vec(), entry);
+        serve_ready_keys(&mut databases[target]);
+        Ok(RespValue::Integer(1))
+    })
+}
+
+/// Check a database index given by the client against the number of databases.
+fn database_index(index: i64, count: usize) -> Result<usize, RedisError> {
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < count)
+        .ok_or_else(|| RedisError::Other("DB index is out of range".to_owned()))
+}
+
+/// SELECT index
+pub fn select_execute(args: &[Vec<u8>]) -> CommandResult {
+    let index = parse_integer(&args[0])?;
+    let index = with_databases(|databases, _| database_index(index, databases.len()))?;
+    select_database(index);
+    Ok(RespValue::ok())
+}
+
+/// MOVE key db
+/// Moves the key with its time to live to another database, unless the key exists there already.
+pub fn move_execute(args: &[Vec<u8>]) -> CommandResult {
+    let (key, target) = (&args[0], parse_integer(&args[1])?);
+    with_databases(|databases, selected| {
+        let target = database_index(target, databases.len())?;
+        if target == selected {
+            return Err(RedisError::Other("source and destination objects are the same".to_owned()));
+        }
+        if databases[selected].get(key).is_none() || databases[target].get(key).is_some() {
+            return Ok(RespValue::Integer(0));
+        }
+        let entry = databases[selected].remove(key).expect("Key checked above");
+        databases[target].insert(key.to_vec(), entry);
+        serve_ready_keys(&mut databases[target]);
+        Ok(RespValue::Integer(1))
+    })
+}
+
+/// SWAPDB index1 index2
+/// Clients connected to either database immediately see the keys of the other one.
+pub fn swapdb_execute(args: &[Vec<u8>]) -> CommandResult {
+    let first = parse_integer(&args[0]).map_err(|_| RedisError::Other("invalid first DB index".to_owned()))?;
+    let second = parse_integer(&args[1]).map_err(|_| RedisError::Other("invalid second DB index".to_owned()))?;
+    with_databases(|databases, _| {
+        let (first, second) = (database_index(first, databases.len())?, database_index(second, databases.len())?);
+        if first != second {
+            let (low, high) = databases.split_at_mut(first.max(second));
+            low[first.min(second)].swap_keys(&mut high[0]);
+            serve_ready_keys(&mut databases[first]);
+            serve_ready_keys(&mut databases[second]);
+        }
+        Ok(RespValue::ok())
+    })
+}
+
+/// DBSIZE
+pub fn dbsize_execute(_args: &[Vec<u8>]) -> CommandResult {
+    let keys = with_store(|database| database.key_count());
+    Ok(RespValue::Integer(keys as i64))
+}
+
+/// Parse the ASYNC and SYNC options of FLUSHDB and FLUSHALL, returning whether to free the keys in the
+/// background. Without either, `lazyfree-lazy-user-flush` decides.
+fn parse_flush_mode(args: &[Vec<u8>]) -> Result<bool, RedisError> {
+    match args {
+        [] => Ok(global_config_get("lazyfree-lazy-user-flush").is_some_and(|value| value == "yes")),
+        [mode] if is_keyword(mode, "ASYNC") => Ok(true),
+        [mode] if is_keyword(mode, "SYNC") => Ok(false),
+        _ => Err(RedisError::Syntax),
+    }
+}
+
+/// FLUSHDB [ASYNC | SYNC]
+pub fn flushdb_execute(args: &[Vec<u8>]) -> CommandResult {
+    let asynchronous = parse_flush_mode(args)?;
+    let removed = with_store(|database| database.flush());
+    if asynchronous {
+        free_in_background(removed);
+    }
+    Ok(RespValue::ok())
+}
+
+/// FLUSHALL [ASYNC | SYNC]
+pub fn flushall_execute(args: &[Vec<u8>]) -> CommandResult {
+    let asynchronous = parse_flush_mode(args)?;
+    let removed = with_databases(|databases, _| {
+        let removed = databases.iter_mut().map(|database| database.flush()).collect::<Vec<_>>();
+        databases.iter_mut().for_each(serve_ready_keys);
+        removed
+    });
+    if asynchronous {
+        free_in_background(removed);
+    }
+    Ok(RespValue::ok())
+}
+
+/// KEYS pattern
+pub fn keys_execute(args: &[Vec<u8>]) -> CommandResult {
+    let pattern = &args[0];
+    let keys = with_store(|database| database.keys());
+    Ok(RespValue::Array(
+        keys.into_iter()
+            .filter(|key| glob_match(pattern, key, false))
+            .map(RespValue::BulkString)
+            .collect(),
+    ))
+}
+
+/// Parse a SCAN style cursor, which is an unsigned 64 bit integer.
+pub fn parse_cursor(arg: &[u8]) -> Result<u64, RedisError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|text| text.parse::<u64>().ok())
+        .filter(|value| value.to_string().as_bytes() == arg)
+        .ok_or_else(|| RedisError::Other("invalid cursor".to_owned()))
+}
+
+/// The MATCH and COUNT options shared by SCAN, HSCAN, SSCAN and ZSCAN.
+pub struct ScanOptions {
+    pub pattern: Option<Vec<u8>>,
+    pub count: usize,
+    /// Only used by SCAN.
+    pub type_name: Option<String>,
+}
+
+impl ScanOptions {
+    pub fn parse(options: &[Vec<u8>], allow_type: bool) -> Result<ScanOptions, RedisError> {
+        let mut parsed = ScanOptions { pattern: None, count: DEFAULT_SCAN_COUNT, type_name: None };
+        let mut index = 0;
+        while index < options.len() {
+            let Some(value) = options.get(index + 1) else {
+                return Err(RedisError::Syntax);
+            };
+            if is_keyword(&options[index], "MATCH") {
+                // A lone star matches everything, skip the matching altogether.
+                parsed.pattern = Some(value.clone()).filter(|pattern| pattern != b"*");
+            } else if is_keyword(&options[index], "COUNT") {
+                let count = parse_integer(value)?;
+                if count < 1 {
+                    return Err(RedisError::Syntax);
+                }
+                parsed.count = count as usize;
+            } else if allow_type && is_keyword(&options[index], "TYPE") {
+                parsed.type_name = Some(String::from_utf8_lossy(value).to_lowercase());
+            } else {
+                return Err(RedisError::Syntax);
+            }
+            index += 2;
+        }
+        Ok(parsed)
+    }
+
+    pub fn matches(&self, element: &[u8]) -> bool {
+        self.pattern.as_ref().is_none_or(|pattern| glob_match(pattern, element, false))
+    }
+}
+
+/// Build the two element reply of SCAN style commands.
+pub fn scan_reply(cursor: u64, elements: Vec<RespValue>) -> RespValue {
+    RespValue::Array(vec![RespValue::bulk_string(cursor.to_string()), RespValue::Array(elements)])
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+/// Keys present during the whole iteration are returned at least once. Filters are applied after
+/// the keys are picked, so a call may return fewer keys than COUNT, or none, before the cursor is 0.
+pub fn scan_execute(args: &[Vec<u8>]) -> CommandResult {
+    let cursor = parse_cursor(&args[0])?;
+    let options = ScanOptions::parse(&args[1..], true)?;
+    with_store(|database| {
+        let (cursor, keys) = database.scan(cursor, options.count);
+        let keys = keys.into_iter()
+            .filter(|key| options.matches(key))
+            .filter(|key| {
+                options.type_name.as_ref().is_none_or(|type_name| {
+                    database.get(key).is_some_and(|entry| entry.type_name() == type_name)
+                })
+            })
+            .map(RespValue::BulkString)
+            .collect();
+        Ok(scan_reply(cursor, keys))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::list::{blpop_execute, rpush_execute};
+    use crate::commands::test_helpers::args;
+    use crate::store::{global_store_get, Ttl};
+
+    #[test]
+    fn test_rename_keeps_ttl() {
+        with_store(|database| database.set(b"test_rename_source".to_vec(), b"v".to_vec(), Ttl::ExpireAt(u64::MAX)));
+        assert_eq!(rename_generic(b"test_rename_source", b"test_rename_destination", true), Ok(true));
+        assert_eq!(global_store_get(b"test_rename_source"), Ok(None));
+        let expires_at = with_store(|database| database.get(b"test_rename_destination").and_then(|entry| entry.expires_at()));
+        assert_eq!(expires_at, Some(u64::MAX));
+        assert_eq!(rename_generic(b"test_rename_source", b"test_rename_destination", true), Err(RedisError::NoSuchKey));
+    }
+
+    #[test]
+    fn test_move_between_databases() {
+        // The selected database belongs to the test thread, like it does to a connection.
+        select_execute(&args(&["12"])).unwrap();
+        with_store(|database| database.set(b"test_move".to_vec(), b"v".to_vec(), Ttl::ExpireAt(u64::MAX)));
+        assert_eq!(move_execute(&args(&["test_move", "13"])), Ok(RespValue::Integer(1)));
+        assert_eq!(move_execute(&args(&["test_move", "13"])), Ok(RespValue::Integer(0)));
+        assert_eq!(dbsize_execute(&[]), Ok(RespValue::Integer(0)));
+        assert_eq!(select_execute(&args(&["-1"])), Err(RedisError::Other("DB index is out of range".to_owned())));
+
+        select_execute(&args(&["13"])).unwrap();
+        let expires_at = with_store(|database| database.get(b"test_move").and_then(|entry| entry.expires_at()));
+        assert_eq!(expires_at, Some(u64::MAX));
+        assert_eq!(swapdb_execute(&args(&["12", "13"])), Ok(RespValue::ok()));
+        assert_eq!(dbsize_execute(&[]), Ok(RespValue::Integer(0)));
+        assert_eq!(flushall_execute(&args(&["LAZY"])), Err(RedisError::Syntax));
+        select_execute(&args(&["12"])).unwrap();
+        assert_eq!(dbsize_execute(&[]), Ok(RespValue::Integer(1)));
+        assert_eq!(flushdb_execute(&args(&["ASYNC"])), Ok(RespValue::ok()));
+        assert_eq!(dbsize_execute(&[]), Ok(RespValue::Integer(0)));
+    }
+
+    #[test]
+    fn test_move_serves_clients_blocked_in_target_database() {
+        let blocked = std::thread::spawn(|| {
+            select_execute(&args(&["14"])).unwrap();
+            blpop_execute(&args(&["test_move_blocked", "0"]))
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        rpush_execute(&args(&["test_move_blocked", "a"])).unwrap();
+        assert_eq!(move_execute(&args(&["test_move_blocked", "14"])), Ok(RespValue::Integer(1)));
+        let reply = RespValue::Array(vec![RespValue::bulk_string("test_move_blocked"), RespValue::bulk_string("a")]);
+        assert_eq!(blocked.join().unwrap(), Ok(reply));
+    }
+}
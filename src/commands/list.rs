@@ -0,0 +1,541 @@
+use super::{is_keyword, normalize_range, parse_integer, CommandResult};
+use crate::blocking::{block_on_keys, parse_timeout};
+use crate::error::RedisError;
+use crate::serialization::RespValue;
+use crate::store::{with_store, Database, List};
+
+/// Which end of a list to push to or pop from.
+#[derive(Clone, Copy)]
+pub enum End {
+    Left,
+    Right,
+}
+
+impl End {
+    /// Parse the LEFT or RIGHT argument of LMOVE and friends.
+    pub fn parse(arg: &[u8]) -> Result<End, RedisError> {
+        if is_keyword(arg, "LEFT") {
+            Ok(End::Left)
+        } else if is_keyword(arg, "RIGHT") {
+            Ok(End::Right)
+        } else {
+            Err(RedisError::Syntax)
+        }
+    }
+
+    fn push(self, list: &mut List, element: Vec<u8>) {
+        match self {
+            End::Left => list.push_front(element),
+            End::Right => list.push_back(element),
+        }
+    }
+
+    fn pop(self, list: &mut List) -> Option<Vec<u8>> {
+        match self {
+            End::Left => list.pop_front(),
+            End::Right => list.pop_back(),
+        }
+    }
+}
+
+/// Resolve a list index, negative ones counting from the tail, into a position within the list.
+fn list_position(index: i64, length: usize) -> Option<usize> {
+    let position = if index < 0 { length as i64 + index } else { index };
+    (0..length as i64).contains(&position).then_some(position as usize)
+}
+
+/// Parse the COUNT of a pop, which must not be negative.
+fn parse_pop_count(arg: &[u8]) -> Result<usize, RedisError> {
+    let count = parse_integer(arg).map_err(|_| RedisError::Other("value is out of range, must be positive".to_owned()))?;
+    if count < 0 {
+        return Err(RedisError::Other("value is out of range, must be positive".to_owned()));
+    }
+    Ok(count as usize)
+}
+
+/// Pop up to `count` elements from one end of the list under the key, deleting the key once empty.
+/// Returns None if the key does not exist.
+pub fn pop_elements(database: &mut Database, key: &[u8], end: End, count: usize) -> Result<Option<Vec<Vec<u8>>>, RedisError> {
+    let Some(list) = database.get_list_mut(key)? else {
+        return Ok(None);
+    };
+    let popped = (0..count).map_while(|_| end.pop(list)).collect();
+    database.remove_if_empty(key);
+    Ok(Some(popped))
+}
+
+/// Pop an element from one end of the source list and push it to one end of the destination list,
+/// which may be the same list. Returns the element moved, or None if the source does not exist.
+/// Nothing is popped if the destination holds another type.
+pub fn move_element(
+    database: &mut Database,
+    source: &[u8],
+    destination: &[u8],
+    from: End,
+    to: End,
+) -> Result<Option<Vec<u8>>, RedisError> {
+    if database.get_list(source)?.is_none() {
+        return Ok(None);
+    }
+    database.get_list(destination)?;
+    let element = pop_elements(database, source, from, 1)?.and_then(|mut popped| popped.pop());
+    if let Some(element) = &element {
+        to.push(database.get_or_create_list(destination)?, element.clone());
+    }
+    Ok(element)
+}
+
+/// Shared implementation of the push commands. With `only_existing` nothing is created for a missing key.
+fn push(args: &[Vec<u8>], end: End, only_existing: bool) -> CommandResult {
+    with_store(|database| {
+        if only_existing && database.get_list(&args[0])?.is_none() {
+            return Ok(RespValue::Integer(0));
+        }
+        let list = database.get_or_create_list(&args[0])?;
+        for element in &args[1..] {
+            end.push(list, element.clone());
+        }
+        Ok(RespValue::Integer(list.len() as i64))
+    })
+}
+
+/// LPUSH key element [element ...]
+pub fn lpush_execute(args: &[Vec<u8>]) -> CommandResult {
+    push(args, End::Left, false)
+}
+
+/// RPUSH key element [element ...]
+pub fn rpush_execute(args: &[Vec<u8>]) -> CommandResult {
+    push(args, End::Right, false)
+}
+
+/// LPUSHX key element [element ...]
+pub fn lpushx_execute(args: &[Vec<u8>]) -> CommandResult {
+    push(args, End::Left, true)
+}
+
+/// RPUSHX key element [element ...]
+pub fn rpushx_execute(args: &[Vec<u8>]) -> CommandResult {
+    push(args, End::Right, true)
+}
+
+/// Shared implementation of LPOP and RPOP.
+/// Without a count a single element is returned, with one an array of up to that many elements.
+fn pop(args: &[Vec<u8>], end: End, command: &str) -> CommandResult {
+    let count = match args {
+        [_] => None,
+        [_, count] => Some(parse_pop_count(count)?),
+        _ => return Err(RedisError::WrongArity(command.to_owned())),
+    };
+    with_store(|database| {
+        let popped = pop_elements(database, &args[0], end, count.unwrap_or(1))?;
+        Ok(match (popped, count) {
+            (None, None) => RespValue::NullBulkString,
+            (None, Some(_)) => RespValue::NullArray,
+            (Some(mut popped), None) => popped.pop().map_or(RespValue::NullBulkString, RespValue::BulkString),
+            (Some(popped), Some(_)) => RespValue::Array(popped.into_iter().map(RespValue::BulkString).collect()),
+        })
+    })
+}
+
+/// LPOP key [count]
+pub fn lpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    pop(args, End::Left, "lpop")
+}
+
+/// RPOP key [count]
+pub fn rpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    pop(args, End::Right, "rpop")
+}
+
+/// LLEN key
+pub fn llen_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let length = database.get_list(&args[0])?.map_or(0, |list| list.len());
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// LRANGE key start stop
+pub fn lrange_execute(args: &[Vec<u8>]) -> CommandResult {
+    let start = parse_integer(&args[1])?;
+    let stop = parse_integer(&args[2])?;
+    with_store(|database| {
+        let Some(list) = database.get_list(&args[0])? else {
+            return Ok(RespValue::Array(Vec::new()));
+        };
+        let elements = match normalize_range(start, stop, list.len()) {
+            Some((start, stop)) => list.range(start..=stop).map(RespValue::bulk_string).collect(),
+            None => Vec::new(),
+        };
+        Ok(RespValue::Array(elements))
+    })
+}
+
+/// LINDEX key index
+pub fn lindex_execute(args: &[Vec<u8>]) -> CommandResult {
+    let index = parse_integer(&args[1])?;
+    with_store(|database| {
+        let element = database
+            .get_list(&args[0])?
+            .and_then(|list| list_position(index, list.len()).map(|position| &list[position]));
+        Ok(element.map_or(RespValue::NullBulkString, RespValue::bulk_string))
+    })
+}
+
+/// LSET key index element
+pub fn lset_execute(args: &[Vec<u8>]) -> CommandResult {
+    let index = parse_integer(&args[1])?;
+    with_store(|database| {
+        let list = database.get_list_mut(&args[0])?.ok_or(RedisError::NoSuchKey)?;
+        let position = list_position(index, list.len()).ok_or_else(|| RedisError::Other("index out of range".to_owned()))?;
+        list[position] = args[2].clone();
+        Ok(RespValue::ok())
+    })
+}
+
+/// LINSERT key BEFORE | AFTER pivot element
+/// Replies with the new length, -1 if the pivot was not found, or 0 if the key does not exist.
+pub fn linsert_execute(args: &[Vec<u8>]) -> CommandResult {
+    let after = if is_keyword(&args[1], "BEFORE") {
+        false
+    } else if is_keyword(&args[1], "AFTER") {
+        true
+    } else {
+        return Err(RedisError::Syntax);
+    };
+    with_store(|database| {
+        let Some(list) = database.get_list_mut(&args[0])? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let Some(pivot) = list.iter().position(|element| *element == args[2]) else {
+            return Ok(RespValue::Integer(-1));
+        };
+        list.insert(pivot + after as usize, args[3].clone());
+        Ok(RespValue::Integer(list.len() as i64))
+    })
+}
+
+/// LREM key count element
+/// Removes the first `count` occurrences from the head, from the tail if negative, or all of them if 0.
+pub fn lrem_execute(args: &[Vec<u8>]) -> CommandResult {
+    let count = parse_integer(&args[1])?;
+    let limit = if count == 0 { usize::MAX } else { count.unsigned_abs() as usize };
+    with_store(|database| {
+        let Some(list) = database.get_list_mut(&args[0])? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let mut positions = list
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| **element == args[2])
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>();
+        if count < 0 {
+            positions.reverse();
+        }
+        positions.truncate(limit);
+        positions.sort_unstable();
+        // Remove from the back so the remaining positions stay valid.
+        for position in positions.iter().rev() {
+            list.remove(*position);
+        }
+        database.remove_if_empty(&args[0]);
+        Ok(RespValue::Integer(positions.len() as i64))
+    })
+}
+
+/// LTRIM key start stop
+/// Keeps only the elements within the range, deleting the key if none are left.
+pub fn ltrim_execute(args: &[Vec<u8>]) -> CommandResult {
+    let start = parse_integer(&args[1])?;
+    let stop = parse_integer(&args[2])?;
+    with_store(|database| {
+        let Some(list) = database.get_list_mut(&args[0])? else {
+            return Ok(RespValue::ok());
+        };
+        match normalize_range(start, stop, list.len()) {
+            Some((start, stop)) => {
+                list.truncate(stop + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+        database.remove_if_empty(&args[0]);
+        Ok(RespValue::ok())
+    })
+}
+
+/// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+/// RANK picks which match to start from, negative ranks searching from the tail. With COUNT an array of
+/// up to that many positions is returned, 0 meaning all of them. MAXLEN caps how many elements are compared.
+pub fn lpos_execute(args: &[Vec<u8>]) -> CommandResult {
+    let mut rank: i64 = 1;
+    let mut count: Option<usize> = None;
+    let mut max_length: usize = 0;
+    for option in args[2..].chunks(2) {
+        let [name, value] = option else {
+            return Err(RedisError::Syntax);
+        };
+        if is_keyword(name, "RANK") {
+            rank = parse_integer(value)?;
+            if rank == 0 || rank == i64::MIN {
+                return Err(RedisError::Other(
+                    "RANK can't be zero: use 1 to start from the first match, 2 from the second ... \
+                     or use negative to start from the end of the list"
+                        .to_owned(),
+                ));
+            }
+        } else if is_keyword(name, "COUNT") {
+            let value = parse_integer(value)?;
+            if value < 0 {
+                return Err(RedisError::Other("COUNT can't be negative".to_owned()));
+            }
+            count = Some(value as usize);
+        } else if is_keyword(name, "MAXLEN") {
+            let value = parse_integer(value)?;
+            if value < 0 {
+                return Err(RedisError::Other("MAXLEN can't be negative".to_owned()));
+            }
+            max_length = value as usize;
+        } else {
+            return Err(RedisError::Syntax);
+        }
+    }
+
+    with_store(|database| {
+        let list = database.get_list(&args[0])?;
+        let compared = if max_length == 0 { usize::MAX } else { max_length };
+        let wanted = match count {
+            Some(0) => usize::MAX,
+            Some(count) => count,
+            None => 1,
+        };
+        let matches = |positions: &mut dyn Iterator<Item = usize>| -> Vec<usize> {
+            let list = list.expect("Only called for existing lists");
+            positions
+                .take(compared)
+                .filter(|position| list[*position] == args[1])
+                .skip(rank.unsigned_abs() as usize - 1)
+                .take(wanted)
+                .collect()
+        };
+        let positions = match list {
+            None => Vec::new(),
+            Some(list) if rank > 0 => matches(&mut (0..list.len())),
+            Some(list) => matches(&mut (0..list.len()).rev()),
+        };
+        Ok(match count {
+            Some(_) => RespValue::Array(positions.into_iter().map(|position| RespValue::Integer(position as i64)).collect()),
+            None => positions.first().map_or(RespValue::NullBulkString, |position| RespValue::Integer(*position as i64)),
+        })
+    })
+}
+
+/// LMOVE source destination LEFT | RIGHT LEFT | RIGHT
+pub fn lmove_execute(args: &[Vec<u8>]) -> CommandResult {
+    let from = End::parse(&args[2])?;
+    let to = End::parse(&args[3])?;
+    with_store(|database| {
+        let element = move_element(database, &args[0], &args[1], from, to)?;
+        Ok(element.map_or(RespValue::NullBulkString, RespValue::BulkString))
+    })
+}
+
+/// RPOPLPUSH source destination
+/// Same as `LMOVE source destination RIGHT LEFT`.
+pub fn rpoplpush_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let element = move_element(database, &args[0], &args[1], End::Right, End::Left)?;
+        Ok(element.map_or(RespValue::NullBulkString, RespValue::BulkString))
+    })
+}
+
+/// The arguments of LMPOP, also used by BLMPOP: the keys to try in order, the end to pop from and the count.
+pub struct MultiPopArgs {
+    pub keys: Vec<Vec<u8>>,
+    pub end: End,
+    pub count: usize,
+}
+
+impl MultiPopArgs {
+    /// Parse `numkeys key [key ...] LEFT | RIGHT [COUNT count]`.
+    pub fn parse(args: &[Vec<u8>]) -> Result<MultiPopArgs, RedisError> {
+        let key_count = parse_integer(&args[0])?;
+        if key_count <= 0 {
+            return Err(RedisError::Other("numkeys should be greater than 0".to_owned()));
+        }
+        let key_count = key_count as usize;
+        let (Some(keys), Some(end)) = (args.get(1..=key_count), args.get(key_count + 1)) else {
+            return Err(RedisError::Syntax);
+        };
+        let end = End::parse(end)?;
+        let count = match &args[key_count + 2..] {
+            [] => 1,
+            [option, count] if is_keyword(option, "COUNT") => {
+                let count = parse_integer(count)?;
+                if count <= 0 {
+                    return Err(RedisError::Other("count should be greater than 0".to_owned()));
+                }
+                count as usize
+            }
+            _ => return Err(RedisError::Syntax),
+        };
+        Ok(MultiPopArgs { keys: keys.to_vec(), end, count })
+    }
+
+    /// Pop from the first of the keys holding a list, replying with the key and the elements popped,
+    /// or None if none of the keys exist.
+    pub fn pop(&self, database: &mut Database) -> Result<Option<RespValue>, RedisError> {
+        for key in &self.keys {
+            if let Some(popped) = pop_elements(database, key, self.end, self.count)? {
+                let elements = popped.into_iter().map(RespValue::BulkString).collect();
+                return Ok(Some(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::Array(elements)])));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// LMPOP numkeys key [key ...] LEFT | RIGHT [COUNT count]
+pub fn lmpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    let multi_pop = MultiPopArgs::parse(args)?;
+    with_store(|database| Ok(multi_pop.pop(database)?.unwrap_or(RespValue::NullArray)))
+}
+
+/// Shared implementation of BLPOP and BRPOP.
+fn blocking_pop(args: &[Vec<u8>], end: End) -> CommandResult {
+    let (timeout, keys) = args.split_last().expect("Arity checked");
+    let timeout = parse_timeout(timeout)?;
+    block_on_keys(
+        keys,
+        timeout,
+        Box::new(move |database, key| match pop_elements(database, key, end, 1) {
+            Ok(popped) => popped?.pop().map(|element| {
+                Ok(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::BulkString(element)]))
+            }),
+            Err(error) => Some(Err(error)),
+        }),
+    )
+}
+
+/// BLPOP key [key ...] timeout
+/// Pops from the first non-empty list, or blocks until another client pushes to one of them.
+pub fn blpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    blocking_pop(args, End::Left)
+}
+
+/// BRPOP key [key ...] timeout
+pub fn brpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    blocking_pop(args, End::Right)
+}
+
+/// Shared implementation of BLMOVE and BRPOPLPUSH.
+fn blocking_move(source: &[u8], destination: &[u8], from: End, to: End, timeout: &[u8]) -> CommandResult {
+    let timeout = parse_timeout(timeout)?;
+    let destination = destination.to_vec();
+    block_on_keys(
+        &[source.to_vec()],
+        timeout,
+        Box::new(move |database, source| match move_element(database, source, &destination, from, to) {
+            Ok(element) => element.map(|element| Ok(RespValue::BulkString(element))),
+            Err(error) => Some(Err(error)),
+        }),
+    )
+}
+
+/// BLMOVE source destination LEFT | RIGHT LEFT | RIGHT timeout
+pub fn blmove_execute(args: &[Vec<u8>]) -> CommandResult {
+    let from = End::parse(&args[2])?;
+    let to = End::parse(&args[3])?;
+    blocking_move(&args[0], &args[1], from, to, &args[4])
+}
+
+/// BRPOPLPUSH source destination timeout
+pub fn brpoplpush_execute(args: &[Vec<u8>]) -> CommandResult {
+    blocking_move(&args[0], &args[1], End::Right, End::Left, &args[2])
+}
+
+/// BLMPOP timeout numkeys key [key ...] LEFT | RIGHT [COUNT count]
+pub fn blmpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    let timeout = parse_timeout(&args[0])?;
+    let multi_pop = MultiPopArgs::parse(&args[1..])?;
+    let keys = multi_pop.keys.clone();
+    block_on_keys(
+        &keys,
+        timeout,
+        Box::new(move |database, key| match pop_elements(database, key, multi_pop.end, multi_pop.count) {
+            Ok(popped) => popped.map(|popped| {
+                let elements = popped.into_iter().map(RespValue::BulkString).collect();
+                Ok(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::Array(elements)]))
+            }),
+            Err(error) => Some(Err(error)),
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_helpers::args;
+    use rstest::*;
+
+    fn integers(values: &[i64]) -> RespValue {
+        RespValue::Array(values.iter().map(|value| RespValue::Integer(*value)).collect())
+    }
+
+    #[rstest]
+    #[case(&[], RespValue::Integer(2))]
+    #[case(&["RANK", "2"], RespValue::Integer(6))]
+    #[case(&["RANK", "-1"], RespValue::Integer(7))]
+    #[case(&["COUNT", "0"], integers(&[2, 6, 7]))]
+    #[case(&["RANK", "-2", "COUNT", "2"], integers(&[6, 2]))]
+    #[case(&["COUNT", "0", "MAXLEN", "3"], integers(&[2]))]
+    #[case(&["RANK", "4"], RespValue::NullBulkString)]
+    fn test_lpos(#[case] options: &[&str], #[case] expected: RespValue) {
+        let key = format!("test_lpos {}", options.join(" "));
+        rpush_execute(&args(&[&key, "a", "b", "c", "1", "2", "3", "c", "c"])).unwrap();
+        let mut lpos_args = args(&[&key, "c"]);
+        lpos_args.extend(args(options));
+        assert_eq!(lpos_execute(&lpos_args).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("2", &["a", "b", "c", "x", "d", "x", "e"])]
+    #[case("-2", &["x", "a", "x", "b", "c", "d", "e"])]
+    #[case("0", &["a", "b", "c", "d", "e"])]
+    fn test_lrem(#[case] count: &str, #[case] expected: &[&str]) {
+        let key = format!("test_lrem {}", count);
+        rpush_execute(&args(&[&key, "x", "a", "x", "b", "c", "x", "d", "x", "e"])).unwrap();
+        lrem_execute(&args(&[&key, count, "x"])).unwrap();
+        let remaining = with_store(|database| database.get_list(key.as_bytes()).unwrap().unwrap().clone());
+        assert_eq!(remaining, args(expected));
+    }
+
+    #[test]
+    fn test_blocked_clients_are_served_in_order() {
+        let first = std::thread::spawn(|| blpop_execute(&args(&["test_blpop", "0"])));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let second = std::thread::spawn(|| blpop_execute(&args(&["test_blpop_other", "test_blpop", "0"])));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        rpush_execute(&args(&["test_blpop", "a", "b"])).unwrap();
+        let reply = |key: &str, element: &str| RespValue::Array(vec![RespValue::bulk_string(key), RespValue::bulk_string(element)]);
+        assert_eq!(first.join().unwrap(), Ok(reply("test_blpop", "a")));
+        assert_eq!(second.join().unwrap(), Ok(reply("test_blpop", "b")));
+        assert!(with_store(|database| database.get(b"test_blpop").is_none()));
+    }
+
+    #[test]
+    fn test_blocking_pop_times_out() {
+        assert_eq!(brpop_execute(&args(&["test_brpop_timeout", "0.05"])), Ok(RespValue::NullArray));
+    }
+
+    #[test]
+    fn test_lmove_rotates_same_list() {
+        rpush_execute(&args(&["test_lmove", "a", "b", "c"])).unwrap();
+        lmove_execute(&args(&["test_lmove", "test_lmove", "LEFT", "RIGHT"])).unwrap();
+        let list = with_store(|database| database.get_list(b"test_lmove").unwrap().unwrap().clone());
+        assert_eq!(list, [b"b".to_vec(), b"c".to_vec(), b"a".to_vec()]);
+    }
+}
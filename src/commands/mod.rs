@@ -0,0 +1,687 @@
+mod config;
+mod expire;
+mod hash;
+mod info;
+mod keyspace;
+mod list;
+mod set;
+mod sorted_set;
+mod stream;
+mod string;
+
+use crate::error::RedisError;
+use crate::maxmemory::evict_if_needed;
+use crate::random::MAX_REPEATED_PICKS;
+use crate::serialization::RespValue;
+use crate::stats;
+use config::config_execute;
+use expire::{
+    expire_execute, expireat_execute, expiretime_execute, persist_execute, pexpire_execute, pexpireat_execute,
+    pexpiretime_execute, pttl_execute, ttl_execute,
+};
+use hash::{
+    hdel_execute, hexists_execute, hget_execute, hgetall_execute, hincrby_execute, hincrbyfloat_execute, hkeys_execute,
+    hlen_execute, hmget_execute, hmset_execute, hrandfield_execute, hscan_execute, hset_execute, hsetnx_execute,
+    hstrlen_execute, hvals_execute,
+};
+use info::info_execute;
+use keyspace::{
+    copy_execute, dbsize_execute, del_execute, exists_execute, flushall_execute, flushdb_execute, keys_execute,
+    move_execute, rename_execute, renamenx_execute, scan_execute, select_execute, swapdb_execute, touch_execute,
+    type_execute, unlink_execute,
+};
+use list::{
+    blmove_execute, blmpop_execute, blpop_execute, brpop_execute, brpoplpush_execute, lindex_execute, linsert_execute,
+    llen_execute, lmove_execute, lmpop_execute, lpop_execute, lpos_execute, lpush_execute, lpushx_execute,
+    lrange_execute, lrem_execute, lset_execute, ltrim_execute, rpop_execute, rpoplpush_execute, rpush_execute,
+    rpushx_execute,
+};
+use set::{
+    sadd_execute, scard_execute, sdiff_execute, sdiffstore_execute, sinter_execute, sintercard_execute,
+    sinterstore_execute, sismember_execute, smembers_execute, smismember_execute, smove_execute, spop_execute,
+    srandmember_execute, srem_execute, sscan_execute, sunion_execute, sunionstore_execute,
+};
+use sorted_set::{
+    bzmpop_execute, bzpopmax_execute, bzpopmin_execute, zadd_execute, zcard_execute, zcount_execute, zdiff_execute,
+    zdiffstore_execute, zincrby_execute, zinter_execute, zinterstore_execute, zlexcount_execute, zmpop_execute,
+    zmscore_execute, zpopmax_execute, zpopmin_execute, zrange_execute, zrangebylex_execute, zrangebyscore_execute,
+    zrangestore_execute, zrank_execute, zrem_execute, zremrangebylex_execute, zremrangebyrank_execute,
+    zremrangebyscore_execute, zrevrange_execute, zrevrangebylex_execute, zrevrangebyscore_execute, zrevrank_execute,
+    zscan_execute, zscore_execute, zunion_execute, zunionstore_execute,
+};
+use stream::{
+    xack_execute, xadd_execute, xautoclaim_execute, xclaim_execute, xdel_execute, xgroup_execute, xinfo_execute,
+    xlen_execute, xpending_execute, xrange_execute, xread_execute, xreadgroup_execute, xrevrange_execute, xtrim_execute,
+};
+use string::{
+    append_execute, decr_execute, decrby_execute, get_execute, getdel_execute, getex_execute, getrange_execute,
+    incr_execute, incrby_execute, incrbyfloat_execute, mget_execute, mset_execute, msetnx_execute, set_execute,
+    setnx_execute, setrange_execute, strlen_execute,
+};
+
+/// Result of running a command: the reply to send, or an error to report to the client.
+pub type CommandResult = Result<RespValue, RedisError>;
+
+/// Enum of commands that this Redis server can process.
+/// Add a new entry to this enum to support additional commands.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(strum_macros::Display)]
+#[derive(strum_macros::EnumString)]
+pub enum CommandType {
+    PING,
+    ECHO,
+    SET,
+    GET,
+    CONFIG,
+    EXPIRE,
+    PEXPIRE,
+    EXPIREAT,
+    PEXPIREAT,
+    TTL,
+    PTTL,
+    EXPIRETIME,
+    PEXPIRETIME,
+    PERSIST,
+    MGET,
+    MSET,
+    MSETNX,
+    APPEND,
+    STRLEN,
+    GETRANGE,
+    SETRANGE,
+    GETDEL,
+    GETEX,
+    SETNX,
+    INCR,
+    DECR,
+    INCRBY,
+    DECRBY,
+    INCRBYFLOAT,
+    DEL,
+    UNLINK,
+    EXISTS,
+    TYPE,
+    RENAME,
+    RENAMENX,
+    COPY,
+    TOUCH,
+    KEYS,
+    SCAN,
+    INFO,
+    HSET,
+    HSETNX,
+    HGET,
+    HMGET,
+    HMSET,
+    HDEL,
+    HLEN,
+    HSTRLEN,
+    HEXISTS,
+    HKEYS,
+    HVALS,
+    HGETALL,
+    HINCRBY,
+    HINCRBYFLOAT,
+    HRANDFIELD,
+    HSCAN,
+    LPUSH,
+    RPUSH,
+    LPUSHX,
+    RPUSHX,
+    LPOP,
+    RPOP,
+    LLEN,
+    LRANGE,
+    LINDEX,
+    LSET,
+    LINSERT,
+    LREM,
+    LTRIM,
+    LPOS,
+    LMOVE,
+    RPOPLPUSH,
+    LMPOP,
+    BLPOP,
+    BRPOP,
+    BLMOVE,
+    BRPOPLPUSH,
+    BLMPOP,
+    SADD,
+    SREM,
+    SISMEMBER,
+    SMISMEMBER,
+    SMEMBERS,
+    SCARD,
+    SPOP,
+    SRANDMEMBER,
+    SINTER,
+    SUNION,
+    SDIFF,
+    SINTERSTORE,
+    SUNIONSTORE,
+    SDIFFSTORE,
+    SINTERCARD,
+    SMOVE,
+    SSCAN,
+    ZADD,
+    ZINCRBY,
+    ZREM,
+    ZSCORE,
+    ZMSCORE,
+    ZCARD,
+    ZRANK,
+    ZREVRANK,
+    ZCOUNT,
+    ZLEXCOUNT,
+    ZRANGE,
+    ZREVRANGE,
+    ZRANGEBYSCORE,
+    ZREVRANGEBYSCORE,
+    ZRANGEBYLEX,
+    ZREVRANGEBYLEX,
+    ZRANGESTORE,
+    ZREMRANGEBYRANK,
+    ZREMRANGEBYSCORE,
+    ZREMRANGEBYLEX,
+    ZPOPMIN,
+    ZPOPMAX,
+    ZUNION,
+    ZINTER,
+    ZDIFF,
+    ZUNIONSTORE,
+    ZINTERSTORE,
+    ZDIFFSTORE,
+    ZSCAN,
+    ZMPOP,
+    BZPOPMIN,
+    BZPOPMAX,
+    BZMPOP,
+    XADD,
+    XRANGE,
+    XREVRANGE,
+    XLEN,
+    XTRIM,
+    XDEL,
+    XREAD,
+    XGROUP,
+    XREADGROUP,
+    XACK,
+    XPENDING,
+    XCLAIM,
+    XAUTOCLAIM,
+    XINFO,
+    SELECT,
+    MOVE,
+    SWAPDB,
+    DBSIZE,
+    FLUSHDB,
+    FLUSHALL,
+}
+
+impl CommandType {
+    fn get_function_to_execute(&self) -> fn(&[Vec<u8>]) -> CommandResult {
+        match self {
+            CommandType::PING => ping_execute,
+            CommandType::ECHO => echo_execute,
+            CommandType::SET => set_execute,
+            CommandType::GET => get_execute,
+            CommandType::CONFIG => config_execute,
+            CommandType::EXPIRE => expire_execute,
+            CommandType::PEXPIRE => pexpire_execute,
+            CommandType::EXPIREAT => expireat_execute,
+            CommandType::PEXPIREAT => pexpireat_execute,
+            CommandType::TTL => ttl_execute,
+            CommandType::PTTL => pttl_execute,
+            CommandType::EXPIRETIME => expiretime_execute,
+            CommandType::PEXPIRETIME => pexpiretime_execute,
+            CommandType::PERSIST => persist_execute,
+            CommandType::MGET => mget_execute,
+            CommandType::MSET => mset_execute,
+            CommandType::MSETNX => msetnx_execute,
+            CommandType::APPEND => append_execute,
+            CommandType::STRLEN => strlen_execute,
+            CommandType::GETRANGE => getrange_execute,
+            CommandType::SETRANGE => setrange_execute,
+            CommandType::GETDEL => getdel_execute,
+            CommandType::GETEX => getex_execute,
+            CommandType::SETNX => setnx_execute,
+            CommandType::INCR => incr_execute,
+            CommandType::DECR => decr_execute,
+            CommandType::INCRBY => incrby_execute,
+            CommandType::DECRBY => decrby_execute,
+            CommandType::INCRBYFLOAT => incrbyfloat_execute,
+            CommandType::DEL => del_execute,
+            CommandType::UNLINK => unlink_execute,
+            CommandType::EXISTS => exists_execute,
+            CommandType::TYPE => type_execute,
+            CommandType::RENAME => rename_execute,
+            CommandType::RENAMENX => renamenx_execute,
+            CommandType::COPY => copy_execute,
+            CommandType::TOUCH => touch_execute,
+            CommandType::KEYS => keys_execute,
+            CommandType::SCAN => scan_execute,
+            CommandType::INFO => info_execute,
+            CommandType::HSET => hset_execute,
+            CommandType::HSETNX => hsetnx_execute,
+            CommandType::HGET => hget_execute,
+            CommandType::HMGET => hmget_execute,
+            CommandType::HMSET => hmset_execute,
+            CommandType::HDEL => hdel_execute,
+            CommandType::HLEN => hlen_execute,
+            CommandType::HSTRLEN => hstrlen_execute,
+            CommandType::HEXISTS => hexists_execute,
+            CommandType::HKEYS => hkeys_execute,
+            CommandType::HVALS => hvals_execute,
+            CommandType::HGETALL => hgetall_execute,
+            CommandType::HINCRBY => hincrby_execute,
+            CommandType::HINCRBYFLOAT => hincrbyfloat_execute,
+            CommandType::HRANDFIELD => hrandfield_execute,
+            CommandType::HSCAN => hscan_execute,
+            CommandType::LPUSH => lpush_execute,
+            CommandType::RPUSH => rpush_execute,
+            CommandType::LPUSHX => lpushx_execute,
+            CommandType::RPUSHX => rpushx_execute,
+            CommandType::LPOP => lpop_execute,
+            CommandType::RPOP => rpop_execute,
+            CommandType::LLEN => llen_execute,
+            CommandType::LRANGE => lrange_execute,
+            CommandType::LINDEX => lindex_execute,
+            CommandType::LSET => lset_execute,
+            CommandType::LINSERT => linsert_execute,
+            CommandType::LREM => lrem_execute,
+            CommandType::LTRIM => ltrim_execute,
+            CommandType::LPOS => lpos_execute,
+            CommandType::LMOVE => lmove_execute,
+            CommandType::RPOPLPUSH => rpoplpush_execute,
+            CommandType::LMPOP => lmpop_execute,
+            CommandType::BLPOP => blpop_execute,
+            CommandType::BRPOP => brpop_execute,
+            CommandType::BLMOVE => blmove_execute,
+            CommandType::BRPOPLPUSH => brpoplpush_execute,
+            CommandType::BLMPOP => blmpop_execute,
+            CommandType::SADD => sadd_execute,
+            CommandType::SREM => srem_execute,
+            CommandType::SISMEMBER => sismember_execute,
+            CommandType::SMISMEMBER => smismember_execute,
+            CommandType::SMEMBERS => smembers_execute,
+            CommandType::SCARD => scard_execute,
+            CommandType::SPOP => spop_execute,
+            CommandType::SRANDMEMBER => srandmember_execute,
+            CommandType::SINTER => sinter_execute,
+            CommandType::SUNION => sunion_execute,
+            CommandType::SDIFF => sdiff_execute,
+            CommandType::SINTERSTORE => sinterstore_execute,
+            CommandType::SUNIONSTORE => sunionstore_execute,
+            CommandType::SDIFFSTORE => sdiffstore_execute,
+            CommandType::SINTERCARD => sintercard_execute,
+            CommandType::SMOVE => smove_execute,
+            CommandType::SSCAN => sscan_execute,
+            CommandType::ZADD => zadd_execute,
+            CommandType::ZINCRBY => zincrby_execute,
+            CommandType::ZREM => zrem_execute,
+            CommandType::ZSCORE => zscore_execute,
+            CommandType::ZMSCORE => zmscore_execute,
+            CommandType::ZCARD => zcard_execute,
+            CommandType::ZRANK => zrank_execute,
+            CommandType::ZREVRANK => zrevrank_execute,
+            CommandType::ZCOUNT => zcount_execute,
+            CommandType::ZLEXCOUNT => zlexcount_execute,
+            CommandType::ZRANGE => zrange_execute,
+            CommandType::ZREVRANGE => zrevrange_execute,
+            CommandType::ZRANGEBYSCORE => zrangebyscore_execute,
+            CommandType::ZREVRANGEBYSCORE => zrevrangebyscore_execute,
+            CommandType::ZRANGEBYLEX => zrangebylex_execute,
+            CommandType::ZREVRANGEBYLEX => zrevrangebylex_execute,
+            CommandType::ZRANGESTORE => zrangestore_execute,
+            CommandType::ZREMRANGEBYRANK => zremrangebyrank_execute,
+            CommandType::ZREMRANGEBYSCORE => zremrangebyscore_execute,
+            CommandType::ZREMRANGEBYLEX => zremrangebylex_execute,
+            CommandType::ZPOPMIN => zpopmin_execute,
+            CommandType::ZPOPMAX => zpopmax_execute,
+            CommandType::ZUNION => zunion_execute,
+            CommandType::ZINTER => zinter_execute,
+            CommandType::ZDIFF => zdiff_execute,
+            CommandType::ZUNIONSTORE => zunionstore_execute,
+            CommandType::ZINTERSTORE => zinterstore_execute,
+            CommandType::ZDIFFSTORE => zdiffstore_execute,
+            CommandType::ZSCAN => zscan_execute,
+            CommandType::ZMPOP => zmpop_execute,
+            CommandType::BZPOPMIN => bzpopmin_execute,
+            CommandType::BZPOPMAX => bzpopmax_execute,
+            CommandType::BZMPOP => bzmpop_execute,
+            CommandType::XADD => xadd_execute,
+            CommandType::XRANGE => xrange_execute,
+            CommandType::XREVRANGE => xrevrange_execute,
+            CommandType::XLEN => xlen_execute,
+            CommandType::XTRIM => xtrim_execute,
+            CommandType::XDEL => xdel_execute,
+            CommandType::XREAD => xread_execute,
+            CommandType::XGROUP => xgroup_execute,
+            CommandType::XREADGROUP => xreadgroup_execute,
+            CommandType::XACK => xack_execute,
+            CommandType::XPENDING => xpending_execute,
+            CommandType::XCLAIM => xclaim_execute,
+            CommandType::XAUTOCLAIM => xautoclaim_execute,
+            CommandType::XINFO => xinfo_execute,
+            CommandType::SELECT => select_execute,
+            CommandType::MOVE => move_execute,
+            CommandType::SWAPDB => swapdb_execute,
+            CommandType::DBSIZE => dbsize_execute,
+            CommandType::FLUSHDB => flushdb_execute,
+            CommandType::FLUSHALL => flushall_execute,
+        }
+    }
+
+    /// Number of arguments the command takes, counting the command name like Redis does.
+    /// A negative arity `-N` means at least N.
+    fn arity(&self) -> i32 {
+        match self {
+            CommandType::PING => -1,
+            CommandType::ECHO => 2,
+            CommandType::SET => -3,
+            CommandType::GET => 2,
+            CommandType::CONFIG => -2,
+            CommandType::EXPIRE => -3,
+            CommandType::PEXPIRE => -3,
+            CommandType::EXPIREAT => -3,
+            CommandType::PEXPIREAT => -3,
+            CommandType::TTL => 2,
+            CommandType::PTTL => 2,
+            CommandType::EXPIRETIME => 2,
+            CommandType::PEXPIRETIME => 2,
+            CommandType::PERSIST => 2,
+            CommandType::MGET => -2,
+            CommandType::MSET => -3,
+            CommandType::MSETNX => -3,
+            CommandType::APPEND => 3,
+            CommandType::STRLEN => 2,
+            CommandType::GETRANGE => 4,
+            CommandType::SETRANGE => 4,
+            CommandType::GETDEL => 2,
+            CommandType::GETEX => -2,
+            CommandType::SETNX => 3,
+            CommandType::INCR => 2,
+            CommandType::DECR => 2,
+            CommandType::INCRBY => 3,
+            CommandType::DECRBY => 3,
+            CommandType::INCRBYFLOAT => 3,
+            CommandType::DEL => -2,
+            CommandType::UNLINK => -2,
+            CommandType::EXISTS => -2,
+            CommandType::TYPE => 2,
+            CommandType::RENAME => 3,
+            CommandType::RENAMENX => 3,
+            CommandType::COPY => -3,
+            CommandType::TOUCH => -2,
+            CommandType::KEYS => 2,
+            CommandType::SCAN => -2,
+            CommandType::INFO => -1,
+            CommandType::HSET => -4,
+            CommandType::HSETNX => 4,
+            CommandType::HGET => 3,
+            CommandType::HMGET => -3,
+            CommandType::HMSET => -4,
+            CommandType::HDEL => -3,
+            CommandType::HLEN => 2,
+            CommandType::HSTRLEN => 3,
+            CommandType::HEXISTS => 3,
+            CommandType::HKEYS => 2,
+            CommandType::HVALS => 2,
+            CommandType::HGETALL => 2,
+            CommandType::HINCRBY => 4,
+            CommandType::HINCRBYFLOAT => 4,
+            CommandType::HRANDFIELD => -2,
+            CommandType::HSCAN => -3,
+            CommandType::LPUSH => -3,
+            CommandType::RPUSH => -3,
+            CommandType::LPUSHX => -3,
+            CommandType::RPUSHX => -3,
+            CommandType::LPOP => -2,
+            CommandType::RPOP => -2,
+            CommandType::LLEN => 2,
+            CommandType::LRANGE => 4,
+            CommandType::LINDEX => 3,
+            CommandType::LSET => 4,
+            CommandType::LINSERT => 5,
+            CommandType::LREM => 4,
+            CommandType::LTRIM => 4,
+            CommandType::LPOS => -3,
+            CommandType::LMOVE => 5,
+            CommandType::RPOPLPUSH => 3,
+            CommandType::LMPOP => -4,
+            CommandType::BLPOP => -3,
+            CommandType::BRPOP => -3,
+            CommandType::BLMOVE => 6,
+            CommandType::BRPOPLPUSH => 4,
+            CommandType::BLMPOP => -5,
+            CommandType::SADD => -3,
+            CommandType::SREM => -3,
+            CommandType::SISMEMBER => 3,
+            CommandType::SMISMEMBER => -3,
+            CommandType::SMEMBERS => 2,
+            CommandType::SCARD => 2,
+            CommandType::SPOP => -2,
+            CommandType::SRANDMEMBER => -2,
+            CommandType::SINTER => -2,
+            CommandType::SUNION => -2,
+            CommandType::SDIFF => -2,
+            CommandType::SINTERSTORE => -3,
+            CommandType::SUNIONSTORE => -3,
+            CommandType::SDIFFSTORE => -3,
+            CommandType::SINTERCARD => -3,
+            CommandType::SMOVE => 4,
+            CommandType::SSCAN => -3,
+            CommandType::ZADD => -4,
+            CommandType::ZINCRBY => 4,
+            CommandType::ZREM => -3,
+            CommandType::ZSCORE => 3,
+            CommandType::ZMSCORE => -3,
+            CommandType::ZCARD => 2,
+            CommandType::ZRANK => -3,
+            CommandType::ZREVRANK => -3,
+            CommandType::ZCOUNT => 4,
+            CommandType::ZLEXCOUNT => 4,
+            CommandType::ZRANGE => -4,
+            CommandType::ZREVRANGE => -4,
+            CommandType::ZRANGEBYSCORE => -4,
+            CommandType::ZREVRANGEBYSCORE => -4,
+            CommandType::ZRANGEBYLEX => -4,
+            CommandType::ZREVRANGEBYLEX => -4,
+            CommandType::ZRANGESTORE => -5,
+            CommandType::ZREMRANGEBYRANK => 4,
+            CommandType::ZREMRANGEBYSCORE => 4,
+            CommandType::ZREMRANGEBYLEX => 4,
+            CommandType::ZPOPMIN => -2,
+            CommandType::ZPOPMAX => -2,
+            CommandType::ZUNION => -3,
+            CommandType::ZINTER => -3,
+            CommandType::ZDIFF => -3,
+            CommandType::ZUNIONSTORE => -4,
+            CommandType::ZINTERSTORE => -4,
+            CommandType::ZDIFFSTORE => -4,
+            CommandType::ZSCAN => -3,
+            CommandType::ZMPOP => -4,
+            CommandType::BZPOPMIN => -3,
+            CommandType::BZPOPMAX => -3,
+            CommandType::BZMPOP => -5,
+            CommandType::XADD => -5,
+            CommandType::XRANGE => -4,
+            CommandType::XREVRANGE => -4,
+            CommandType::XLEN => 2,
+            CommandType::XTRIM => -4,
+            CommandType::XDEL => -3,
+            CommandType::XREAD => -4,
+            CommandType::XGROUP => -2,
+            CommandType::XREADGROUP => -7,
+            CommandType::XACK => -4,
+            CommandType::XPENDING => -3,
+            CommandType::XCLAIM => -6,
+            CommandType::XAUTOCLAIM => -6,
+            CommandType::XINFO => -2,
+            CommandType::SELECT => 2,
+            CommandType::MOVE => 3,
+            CommandType::SWAPDB => 3,
+            CommandType::DBSIZE => 1,
+            CommandType::FLUSHDB => -1,
+            CommandType::FLUSHALL => -1,
+        }
+    }
+
+    /// Whether the command may use more memory, in which case it is refused once memory use is over
+    /// `maxmemory` and nothing can be evicted. Same as the commands Redis flags `denyoom`.
+    fn may_use_memory(&self) -> bool {
+        matches!(
+            self,
+            CommandType::SET | CommandType::SETNX | CommandType::MSET | CommandType::MSETNX | CommandType::APPEND
+                | CommandType::SETRANGE | CommandType::INCR | CommandType::DECR | CommandType::INCRBY
+                | CommandType::DECRBY | CommandType::INCRBYFLOAT | CommandType::COPY | CommandType::HSET
+                | CommandType::HSETNX | CommandType::HMSET | CommandType::HINCRBY | CommandType::HINCRBYFLOAT
+                | CommandType::LPUSH | CommandType::RPUSH | CommandType::LPUSHX | CommandType::RPUSHX
+                | CommandType::LSET | CommandType::LINSERT | CommandType::LMOVE | CommandType::RPOPLPUSH
+                | CommandType::BLMOVE | CommandType::BRPOPLPUSH | CommandType::SADD | CommandType::SINTERSTORE
+                | CommandType::SUNIONSTORE | CommandType::SDIFFSTORE | CommandType::SMOVE | CommandType::ZADD
+                | CommandType::ZINCRBY | CommandType::ZRANGESTORE | CommandType::ZUNIONSTORE
+                | CommandType::ZINTERSTORE | CommandType::ZDIFFSTORE | CommandType::XADD
+        )
+    }
+
+    pub fn execute(&self, args: &[Vec<u8>]) -> CommandResult {
+        stats::increment(&stats::TOTAL_COMMANDS_PROCESSED, 1);
+        check_arity(&self.to_string().to_lowercase(), self.arity(), args)?;
+        if !evict_if_needed() && self.may_use_memory() {
+            return Err(RedisError::OutOfMemory);
+        }
+        self.get_function_to_execute()(args)
+    }
+}
+
+/// Validate the number of arguments given to a command (or subcommand) against its arity.
+fn check_arity(name: &str, arity: i32, args: &[Vec<u8>]) -> Result<(), RedisError> {
+    let count = args.len() as i32 + 1;
+    if (arity >= 0 && count != arity) || (arity < 0 && count < -arity) {
+        return Err(RedisError::WrongArity(name.to_owned()));
+    }
+    Ok(())
+}
+
+/// Parse an argument as a signed 64 bit integer, rejecting anything Redis would reject
+/// such as leading zeros, a `+` sign or surrounding spaces.
+pub fn parse_integer(arg: &[u8]) -> Result<i64, RedisError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|text| text.parse::<i64>().ok())
+        .filter(|value| value.to_string().as_bytes() == arg)
+        .ok_or(RedisError::NotInteger)
+}
+
+/// Parse the count argument of HRANDFIELD and SRANDMEMBER, see `random_picks`. A negative count asking for
+/// more than `MAX_REPEATED_PICKS` elements is out of range.
+pub fn parse_pick_count(arg: &[u8]) -> Result<i64, RedisError> {
+    let count = parse_integer(arg)?;
+    if count < 0 && count.unsigned_abs() > MAX_REPEATED_PICKS {
+        return Err(RedisError::Other("value is out of range".to_owned()));
+    }
+    Ok(count)
+}
+
+/// Parse an argument as a finite 64 bit float, accepting the `inf` spellings Redis accepts but not NaN.
+pub fn parse_float(arg: &[u8]) -> Result<f64, RedisError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .filter(|text| !text.is_empty() && text.trim() == *text)
+        .and_then(|text| text.parse::<f64>().ok())
+        .filter(|value| !value.is_nan())
+        .ok_or(RedisError::NotFloat)
+}
+
+/// Format a float for a reply: the shortest representation that parses back to the same value, without
+/// exponent nor trailing `.0` for whole numbers. This is not exactly what Redis replies: INCRBYFLOAT and
+/// HINCRBYFLOAT compute with long doubles printed with `%.17Lf`, so that 0.1 + 0.2 is 0.3 rather than
+/// 0.30000000000000004, and scores use exponents for large magnitudes, 1e+20 rather than 100000000000000000000.
+pub fn format_float(value: f64) -> String {
+    if value.is_infinite() {
+        return if value > 0.0 { "inf".to_owned() } else { "-inf".to_owned() };
+    }
+    value.to_string()
+}
+
+/// Resolve Redis style start and stop indexes, negative ones counting from the end, into an inclusive
+/// range of positions within a collection of `length` elements. Returns None if the range is empty.
+pub fn normalize_range(start: i64, stop: i64, length: usize) -> Option<(usize, usize)> {
+    let length = length as i64;
+    let start = if start < 0 { (length + start).max(0) } else { start };
+    let stop = if stop < 0 { length + stop } else { stop.min(length - 1) };
+    if start > stop || start >= length {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+/// Case-insensitive comparison of an argument against an option keyword.
+pub fn is_keyword(arg: &[u8], keyword: &str) -> bool {
+    arg.eq_ignore_ascii_case(keyword.as_bytes())
+}
+
+fn ping_execute(args: &[Vec<u8>]) -> CommandResult {
+    match args {
+        [] => Ok(RespValue::SimpleString("PONG".to_string())),
+        [message] => Ok(RespValue::bulk_string(message)),
+        _ => Err(RedisError::WrongArity("ping".to_owned())),
+    }
+}
+
+fn echo_execute(args: &[Vec<u8>]) -> CommandResult {
+    Ok(RespValue::bulk_string(&args[0]))
+}
+
+/// Helpers shared by the tests of the command modules.
+#[cfg(test)]
+pub mod test_helpers {
+    /// Command arguments from string literals, as they would be parsed from a request.
+    pub fn args(args: &[&str]) -> Vec<Vec<u8>> {
+        args.iter().map(|arg| arg.as_bytes().to_vec()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("0", Some(0))]
+    #[case("-42", Some(-42))]
+    #[case("9223372036854775807", Some(i64::MAX))]
+    #[case("9223372036854775808", None)]
+    #[case("01", None)]
+    #[case("+1", None)]
+    #[case(" 1", None)]
+    #[case("", None)]
+    fn test_parse_integer(#[case] input: &str, #[case] expected: Option<i64>) {
+        assert_eq!(parse_integer(input.as_bytes()).ok(), expected);
+    }
+
+    #[rstest]
+    #[case(0, -1, 5, Some((0, 4)))]
+    #[case(-3, 2, 5, Some((2, 2)))]
+    #[case(-100, 100, 5, Some((0, 4)))]
+    #[case(3, 1, 5, None)]
+    #[case(5, 10, 5, None)]
+    #[case(0, -6, 5, None)]
+    #[case(0, 0, 0, None)]
+    fn test_normalize_range(#[case] start: i64, #[case] stop: i64, #[case] length: usize, #[case] expected: Option<(usize, usize)>) {
+        assert_eq!(normalize_range(start, stop, length), expected);
+    }
+
+    #[rstest]
+    #[case(3.0, "3")]
+    #[case(10.5, "10.5")]
+    #[case(-0.25, "-0.25")]
+    #[case(f64::INFINITY, "inf")]
+    // Redis replies 0.3 and 1e+20 for these.
+    #[case(0.1 + 0.2, "0.30000000000000004")]
+    #[case(1e20, "100000000000000000000")]
+    fn test_format_float(#[case] input: f64, #[case] expected: &str) {
+        assert_eq!(format_float(input), expected);
+    }
+}
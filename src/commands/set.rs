@@ -0,0 +1,373 @@
+use super::keyspace::{parse_cursor, scan_reply, ScanOptions};
+use super::{is_keyword, parse_integer, parse_pick_count, CommandResult};
+use crate::error::RedisError;
+use crate::random::random_picks;
+use crate::serialization::RespValue;
+use crate::store::{scan_collection, with_store, Database, Set, Ttl, Value};
+
+/// Turn members into an array reply.
+fn members_reply(members: Vec<Vec<u8>>) -> RespValue {
+    RespValue::Array(members.into_iter().map(RespValue::BulkString).collect())
+}
+
+/// SADD key member [member ...]
+pub fn sadd_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let set = database.get_or_create_set(&args[0])?;
+        let added = args[1..].iter().filter(|member| set.insert(member)).count();
+        Ok(RespValue::Integer(added as i64))
+    })
+}
+
+/// SREM key member [member ...]
+/// The key is deleted once its last member is.
+pub fn srem_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let Some(set) = database.get_set_mut(&args[0])? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let removed = args[1..].iter().filter(|member| set.remove(member)).count();
+        database.remove_if_empty(&args[0]);
+        Ok(RespValue::Integer(removed as i64))
+    })
+}
+
+/// SISMEMBER key member
+pub fn sismember_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let is_member = database.get_set(&args[0])?.is_some_and(|set| set.contains(&args[1]));
+        Ok(RespValue::Integer(is_member as i64))
+    })
+}
+
+/// SMISMEMBER key member [member ...]
+pub fn smismember_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let set = database.get_set(&args[0])?;
+        let replies = args[1..]
+            .iter()
+            .map(|member| RespValue::Integer(set.is_some_and(|set| set.contains(member)) as i64))
+            .collect();
+        Ok(RespValue::Array(replies))
+    })
+}
+
+/// SMEMBERS key
+pub fn smembers_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let members = database.get_set(&args[0])?.map(Set::members).unwrap_or_default();
+        Ok(members_reply(members))
+    })
+}
+
+/// SCARD key
+pub fn scard_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let length = database.get_set(&args[0])?.map_or(0, Set::len);
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// SPOP key [count]
+/// Without a count a single member is popped and the reply is a bulk string rather than an array.
+pub fn spop_execute(args: &[Vec<u8>]) -> CommandResult {
+    let count = match args.get(1) {
+        None => None,
+        Some(count) => {
+            let count = parse_integer(count)?;
+            if count < 0 {
+                return Err(RedisError::Other("value is out of range, must be positive".to_owned()));
+            }
+            Some(count as usize)
+        }
+    };
+    if args.len() > 2 {
+        return Err(RedisError::Syntax);
+    }
+    with_store(|database| {
+        let Some(set) = database.get_set_mut(&args[0])? else {
+            return Ok(count.map_or(RespValue::NullBulkString, |_| RespValue::Array(Vec::new())));
+        };
+        let reply = match count {
+            None => set.pop().map_or(RespValue::NullBulkString, RespValue::BulkString),
+            Some(count) => members_reply((0..count).map_while(|_| set.pop()).collect()),
+        };
+        database.remove_if_empty(&args[0]);
+        Ok(reply)
+    })
+}
+
+/// SRANDMEMBER key [count]
+/// Without a count a single member is returned, see `random_picks` for how the count is interpreted.
+pub fn srandmember_execute(args: &[Vec<u8>]) -> CommandResult {
+    let count = args.get(1).map(|count| parse_pick_count(count)).transpose()?;
+    if args.len() > 2 {
+        return Err(RedisError::Syntax);
+    }
+    with_store(|database| {
+        let set = database.get_set(&args[0])?;
+        let Some(count) = count else {
+            return Ok(set.and_then(Set::random).map_or(RespValue::NullBulkString, RespValue::BulkString));
+        };
+        let members = set.map(Set::members).unwrap_or_default();
+        Ok(members_reply(random_picks(&members, count).into_iter().cloned().collect()))
+    })
+}
+
+/// How SINTER, SUNION, SDIFF and their STORE variants combine the sets.
+#[derive(Clone, Copy)]
+enum Operation {
+    Intersection,
+    Union,
+    /// Members of the first set that are in none of the others.
+    Difference,
+}
+
+/// Combine the sets stored under the keys, missing keys counting as empty sets.
+fn combine(database: &mut Database, keys: &[Vec<u8>], operation: Operation) -> Result<Set, RedisError> {
+    let sets = database.get_sets(keys)?;
+    let mut result = Set::default();
+    match operation {
+        Operation::Intersection => {
+            let Some(sets) = sets.into_iter().collect::<Option<Vec<_>>>() else {
+                return Ok(result);
+            };
+            // Only the members of the smallest set can be in all of them.
+            let smallest = sets.iter().min_by_key(|set| set.len()).expect("At least one key");
+            for member in smallest.members() {
+                if sets.iter().all(|set| set.contains(&member)) {
+                    result.insert(&member);
+                }
+            }
+        }
+        Operation::Union => {
+            for set in sets.into_iter().flatten() {
+                for member in set.members() {
+                    result.insert(&member);
+                }
+            }
+        }
+        Operation::Difference => {
+            let Some(first) = sets[0] else {
+                return Ok(result);
+            };
+            for member in first.members() {
+                if !sets[1..].iter().flatten().any(|set| set.contains(&member)) {
+                    result.insert(&member);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn combine_reply(keys: &[Vec<u8>], operation: Operation) -> CommandResult {
+    with_store(|database| Ok(members_reply(combine(database, keys, operation)?.members())))
+}
+
+/// Store the combination of the sets under the destination key, replacing whatever it held.
+/// An empty result deletes the destination instead. Replies with the size of the result.
+fn combine_store(destination: &[u8], keys: &[Vec<u8>], operation: Operation) -> CommandResult {
+    with_store(|database| {
+        let result = combine(database, keys, operation)?;
+        let length = result.len();
+        if result.is_empty() {
+            database.remove(destination);
+        } else {
+            database.set(destination.to_vec(), Value::Set(result), Ttl::Persist);
+        }
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// SINTER key [key ...]
+pub fn sinter_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_reply(args, Operation::Intersection)
+}
+
+/// SUNION key [key ...]
+pub fn sunion_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_reply(args, Operation::Union)
+}
+
+/// SDIFF key [key ...]
+pub fn sdiff_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_reply(args, Operation::Difference)
+}
+
+/// SINTERSTORE destination key [key ...]
+pub fn sinterstore_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_store(&args[0], &args[1..], Operation::Intersection)
+}
+
+/// SUNIONSTORE destination key [key ...]
+pub fn sunionstore_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_store(&args[0], &args[1..], Operation::Union)
+}
+
+/// SDIFFSTORE destination key [key ...]
+pub fn sdiffstore_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_store(&args[0], &args[1..], Operation::Difference)
+}
+
+/// SINTERCARD numkeys key [key ...] [LIMIT limit]
+/// Counts the members of the intersection without building it, stopping early once `limit` are found.
+/// A limit of 0 means no limit.
+pub fn sintercard_execute(args: &[Vec<u8>]) -> CommandResult {
+    let key_count = parse_integer(&args[0])?;
+    if key_count <= 0 {
+        return Err(RedisError::Other("numkeys should be greater than 0".to_owned()));
+    }
+    let Some(keys) = args.get(1..=key_count as usize) else {
+        return Err(RedisError::Other("Number of keys can't be greater than number of args".to_owned()));
+    };
+    let limit = match &args[keys.len() + 1..] {
+        [] => 0,
+        [option, limit] if is_keyword(option, "LIMIT") => {
+            let limit = parse_integer(limit)?;
+            if limit < 0 {
+                return Err(RedisError::Other("LIMIT can't be negative".to_owned()));
+            }
+            limit as usize
+        }
+        _ => return Err(RedisError::Syntax),
+    };
+    with_store(|database| {
+        let Some(sets) = database.get_sets(keys)?.into_iter().collect::<Option<Vec<_>>>() else {
+            return Ok(RespValue::Integer(0));
+        };
+        let smallest = sets.iter().min_by_key(|set| set.len()).expect("At least one key");
+        let mut count = 0;
+        for member in smallest.members() {
+            if sets.iter().all(|set| set.contains(&member)) {
+                count += 1;
+                if count == limit {
+                    break;
+                }
+            }
+        }
+        Ok(RespValue::Integer(count as i64))
+    })
+}
+
+/// SMOVE source destination member
+pub fn smove_execute(args: &[Vec<u8>]) -> CommandResult {
+    let (source, destination, member) = (&args[0], &args[1], &args[2]);
+    with_store(|database| {
+        // The destination must hold a set too, even if nothing ends up being moved.
+        database.get_set(destination)?;
+        let Some(set) = database.get_set_mut(source)? else {
+            return Ok(RespValue::Integer(0));
+        };
+        if source == destination {
+            return Ok(RespValue::Integer(set.contains(member) as i64));
+        }
+        if !set.remove(member) {
+            return Ok(RespValue::Integer(0));
+        }
+        database.remove_if_empty(source);
+        database.get_or_create_set(destination)?.insert(member);
+        Ok(RespValue::Integer(1))
+    })
+}
+
+/// SSCAN key cursor [MATCH pattern] [COUNT count]
+/// Members present during the whole iteration are returned at least once.
+pub fn sscan_execute(args: &[Vec<u8>]) -> CommandResult {
+    let cursor = parse_cursor(&args[1])?;
+    let options = ScanOptions::parse(&args[2..], false)?;
+    with_store(|database| {
+        let members = database.get_set(&args[0])?.map(Set::members).unwrap_or_default();
+        let (cursor, members) = scan_collection(members.iter(), cursor, options.count);
+        let elements = members
+            .into_iter()
+            .filter(|member| options.matches(member))
+            .map(RespValue::bulk_string)
+            .collect();
+        Ok(scan_reply(cursor, elements))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_helpers::args;
+
+    fn sorted_members(key: &str) -> Vec<Vec<u8>> {
+        let RespValue::Array(members) = smembers_execute(&args(&[key])).unwrap() else {
+            panic!("SMEMBERS replies with an array")
+        };
+        let mut members = members
+            .into_iter()
+            .map(|member| match member {
+                RespValue::BulkString(member) => member,
+                _ => panic!("Members are bulk strings"),
+            })
+            .collect::<Vec<_>>();
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        sadd_execute(&args(&["test_sets_a", "1", "2", "3", "x"])).unwrap();
+        sadd_execute(&args(&["test_sets_b", "2", "3", "4", "x"])).unwrap();
+
+        let stored = sinterstore_execute(&args(&["test_sets_inter", "test_sets_a", "test_sets_b"]));
+        assert_eq!(stored.unwrap(), RespValue::Integer(3));
+        assert_eq!(sorted_members("test_sets_inter"), args(&["2", "3", "x"]));
+
+        sunionstore_execute(&args(&["test_sets_union", "test_sets_a", "test_sets_b", "test_sets_missing"])).unwrap();
+        assert_eq!(sorted_members("test_sets_union"), args(&["1", "2", "3", "4", "x"]));
+
+        sdiffstore_execute(&args(&["test_sets_diff", "test_sets_a", "test_sets_b"])).unwrap();
+        assert_eq!(sorted_members("test_sets_diff"), args(&["1"]));
+
+        let stored = sinterstore_execute(&args(&["test_sets_diff", "test_sets_a", "test_sets_missing"]));
+        assert_eq!(stored.unwrap(), RespValue::Integer(0));
+        assert!(with_store(|database| database.get(b"test_sets_diff").is_none()));
+    }
+
+    #[test]
+    fn test_sintercard_limit() {
+        sadd_execute(&args(&["test_sintercard_a", "a", "b", "c", "d"])).unwrap();
+        sadd_execute(&args(&["test_sintercard_b", "b", "c", "d", "e"])).unwrap();
+        let count = |extra: &[&str]| {
+            let mut command = args(&["2", "test_sintercard_a", "test_sintercard_b"]);
+            command.extend(args(extra));
+            sintercard_execute(&command)
+        };
+        assert_eq!(count(&[]), Ok(RespValue::Integer(3)));
+        assert_eq!(count(&["LIMIT", "2"]), Ok(RespValue::Integer(2)));
+        assert_eq!(count(&["LIMIT", "0"]), Ok(RespValue::Integer(3)));
+        assert_eq!(count(&["LIMIT", "-1"]), Err(RedisError::Other("LIMIT can't be negative".to_owned())));
+        assert_eq!(
+            sintercard_execute(&args(&["3", "test_sintercard_a"])),
+            Err(RedisError::Other("Number of keys can't be greater than number of args".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_spop_removes_empty_set() {
+        sadd_execute(&args(&["test_spop", "a", "b"])).unwrap();
+        let RespValue::Array(popped) = spop_execute(&args(&["test_spop", "5"])).unwrap() else {
+            panic!("SPOP with a count replies with an array")
+        };
+        assert_eq!(popped.len(), 2);
+        assert!(with_store(|database| database.get(b"test_spop").is_none()));
+    }
+
+    #[test]
+    fn test_srandmember_counts() {
+        sadd_execute(&args(&["test_srandmember", "a", "b", "c"])).unwrap();
+        let picked = |count: &str| match srandmember_execute(&args(&["test_srandmember", count])) {
+            Ok(RespValue::Array(members)) => members.len(),
+            reply => panic!("Unexpected SRANDMEMBER reply {:?}", reply),
+        };
+        assert_eq!(picked("2"), 2);
+        assert_eq!(picked("10"), 3);
+        assert_eq!(picked("-10"), 10);
+        let reply = srandmember_execute(&args(&["test_srandmember", "-100000000000000"]));
+        assert_eq!(reply, Err(RedisError::Other("value is out of range".to_owned())));
+    }
+}
@@ -0,0 +1,915 @@
+use std::collections::HashMap;
+
+use super::keyspace::{parse_cursor, scan_reply, ScanOptions};
+use super::{format_float, is_keyword, normalize_range, parse_float, parse_integer, CommandResult};
+use crate::blocking::{block_on_keys, parse_timeout};
+use crate::error::RedisError;
+use crate::serialization::RespValue;
+use crate::store::{
+    scan_collection, with_store, Database, LexBound, LexRange, ScoreBound, ScoreRange, Set, SortedSet, Ttl, Value,
+};
+
+/// A member of a sorted set along with its score.
+type ScoredMember = (Vec<u8>, f64);
+
+fn score_reply(score: f64) -> RespValue {
+    RespValue::BulkString(format_float(score).into_bytes())
+}
+
+/// Turn members into an array reply, each followed by its score if `with_scores` is set.
+fn pairs_reply<'a>(pairs: impl Iterator<Item = (&'a [u8], f64)>, with_scores: bool) -> RespValue {
+    let elements = pairs
+        .flat_map(|(member, score)| {
+            let mut elements = vec![RespValue::bulk_string(member)];
+            if with_scores {
+                elements.push(score_reply(score));
+            }
+            elements
+        })
+        .collect();
+    RespValue::Array(elements)
+}
+
+fn owned_pairs_reply(pairs: &[ScoredMember], with_scores: bool) -> RespValue {
+    pairs_reply(pairs.iter().map(|(member, score)| (member.as_slice(), *score)), with_scores)
+}
+
+/// Parse a score range bound, a float optionally prefixed with `(` to exclude it.
+fn parse_score_bound(arg: &[u8]) -> Result<ScoreBound, RedisError> {
+    let (exclusive, score) = match arg.strip_prefix(b"(") {
+        Some(score) => (true, score),
+        None => (false, arg),
+    };
+    let score = parse_float(score).map_err(|_| RedisError::Other("min or max is not a float".to_owned()))?;
+    Ok(ScoreBound { score, exclusive })
+}
+
+fn parse_score_range(min: &[u8], max: &[u8]) -> Result<ScoreRange, RedisError> {
+    Ok(ScoreRange { min: parse_score_bound(min)?, max: parse_score_bound(max)? })
+}
+
+fn parse_lex_bound(arg: &[u8]) -> Result<LexBound, RedisError> {
+    match arg.split_first() {
+        Some((b'-', [])) => Ok(LexBound::Min),
+        Some((b'+', [])) => Ok(LexBound::Max),
+        Some((b'[', member)) => Ok(LexBound::Inclusive(member.to_vec())),
+        Some((b'(', member)) => Ok(LexBound::Exclusive(member.to_vec())),
+        _ => Err(RedisError::Other("min or max not valid string range item".to_owned())),
+    }
+}
+
+fn parse_lex_range(min: &[u8], max: &[u8]) -> Result<LexRange, RedisError> {
+    Ok(LexRange { min: parse_lex_bound(min)?, max: parse_lex_bound(max)? })
+}
+
+/// ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]
+/// Replies with the number of members added, or also changed with CH. With INCR it behaves like
+/// ZINCRBY and replies with the new score, or null if the flags prevented the update.
+pub fn zadd_execute(args: &[Vec<u8>]) -> CommandResult {
+    let (mut nx, mut xx, mut gt, mut lt, mut ch, mut incr) = (false, false, false, false, false, false);
+    let mut index = 1;
+    while let Some(arg) = args.get(index) {
+        let flag = match () {
+            _ if is_keyword(arg, "NX") => &mut nx,
+            _ if is_keyword(arg, "XX") => &mut xx,
+            _ if is_keyword(arg, "GT") => &mut gt,
+            _ if is_keyword(arg, "LT") => &mut lt,
+            _ if is_keyword(arg, "CH") => &mut ch,
+            _ if is_keyword(arg, "INCR") => &mut incr,
+            _ => break,
+        };
+        *flag = true;
+        index += 1;
+    }
+    let pairs = &args[index..];
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return Err(RedisError::Syntax);
+    }
+    if nx && xx {
+        return Err(RedisError::Other("XX and NX options at the same time are not compatible".to_owned()));
+    }
+    if [nx, gt, lt].iter().filter(|flag| **flag).count() > 1 {
+        return Err(RedisError::Other("GT, LT, and/or NX options at the same time are not compatible".to_owned()));
+    }
+    if incr && pairs.len() > 2 {
+        return Err(RedisError::Other("INCR option supports a single increment-element pair".to_owned()));
+    }
+    let scores = pairs.chunks_exact(2).map(|pair| parse_float(&pair[0])).collect::<Result<Vec<_>, _>>()?;
+
+    with_store(|database| {
+        let mut updated = None;
+        let (mut added, mut changed) = (0, 0);
+        // XX never adds members, so it never creates the key either.
+        if !xx || database.get_sorted_set(&args[0])?.is_some() {
+            let sorted_set = database.get_or_create_sorted_set(&args[0])?;
+            for (pair, score) in pairs.chunks_exact(2).zip(scores) {
+                let member = &pair[1];
+                let current = sorted_set.score(member);
+                if (nx && current.is_some()) || (xx && current.is_none()) {
+                    continue;
+                }
+                let score = match current {
+                    Some(current) if incr => current + score,
+                    _ => score,
+                };
+                if score.is_nan() {
+                    return Err(RedisError::Other("resulting score is not a number (NaN)".to_owned()));
+                }
+                match current {
+                    Some(current) if (gt && score <= current) || (lt && score >= current) => continue,
+                    Some(current) => changed += (score != current) as i64,
+                    None => added += 1,
+                }
+                sorted_set.insert(member, score);
+                updated = Some(score);
+            }
+        }
+        if incr {
+            return Ok(updated.map_or(RespValue::NullBulkString, score_reply));
+        }
+        Ok(RespValue::Integer(if ch { added + changed } else { added }))
+    })
+}
+
+/// ZINCRBY key increment member
+pub fn zincrby_execute(args: &[Vec<u8>]) -> CommandResult {
+    let increment = parse_float(&args[1])?;
+    with_store(|database| {
+        let sorted_set = database.get_or_create_sorted_set(&args[0])?;
+        let score = sorted_set.score(&args[2]).unwrap_or(0.0) + increment;
+        if score.is_nan() {
+            return Err(RedisError::Other("resulting score is not a number (NaN)".to_owned()));
+        }
+        sorted_set.insert(&args[2], score);
+        Ok(score_reply(score))
+    })
+}
+
+/// ZREM key member [member ...]
+/// The key is deleted once its last member is.
+pub fn zrem_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let Some(sorted_set) = database.get_sorted_set_mut(&args[0])? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let removed = args[1..].iter().filter(|member| sorted_set.remove(member)).count();
+        database.remove_if_empty(&args[0]);
+        Ok(RespValue::Integer(removed as i64))
+    })
+}
+
+/// ZSCORE key member
+pub fn zscore_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let score = database.get_sorted_set(&args[0])?.and_then(|sorted_set| sorted_set.score(&args[1]));
+        Ok(score.map_or(RespValue::NullBulkString, score_reply))
+    })
+}
+
+/// ZMSCORE key member [member ...]
+pub fn zmscore_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let sorted_set = database.get_sorted_set(&args[0])?;
+        let scores = args[1..]
+            .iter()
+            .map(|member| sorted_set.and_then(|sorted_set| sorted_set.score(member)))
+            .map(|score| score.map_or(RespValue::NullBulkString, score_reply))
+            .collect();
+        Ok(RespValue::Array(scores))
+    })
+}
+
+/// ZCARD key
+pub fn zcard_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let length = database.get_sorted_set(&args[0])?.map_or(0, SortedSet::len);
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// Reply with the rank of a member, counting from the highest score if `reverse` is set.
+fn rank(args: &[Vec<u8>], reverse: bool) -> CommandResult {
+    let with_score = match args.get(2) {
+        None => false,
+        Some(option) if args.len() == 3 && is_keyword(option, "WITHSCORE") => true,
+        Some(_) => return Err(RedisError::Syntax),
+    };
+    with_store(|database| {
+        let sorted_set = database.get_sorted_set(&args[0])?;
+        let rank = sorted_set.and_then(|sorted_set| Some((sorted_set.rank(&args[1], reverse)?, sorted_set)));
+        Ok(match rank {
+            None if with_score => RespValue::NullArray,
+            None => RespValue::NullBulkString,
+            Some((rank, sorted_set)) if with_score => {
+                let score = sorted_set.score(&args[1]).expect("The member has a rank");
+                RespValue::Array(vec![RespValue::Integer(rank as i64), score_reply(score)])
+            }
+            Some((rank, _)) => RespValue::Integer(rank as i64),
+        })
+    })
+}
+
+/// ZRANK key member [WITHSCORE]
+pub fn zrank_execute(args: &[Vec<u8>]) -> CommandResult {
+    rank(args, false)
+}
+
+/// ZREVRANK key member [WITHSCORE]
+pub fn zrevrank_execute(args: &[Vec<u8>]) -> CommandResult {
+    rank(args, true)
+}
+
+/// ZCOUNT key min max
+pub fn zcount_execute(args: &[Vec<u8>]) -> CommandResult {
+    let range = parse_score_range(&args[1], &args[2])?;
+    with_store(|database| {
+        let count = database.get_sorted_set(&args[0])?.map_or(0, |sorted_set| sorted_set.count_by_score(range));
+        Ok(RespValue::Integer(count as i64))
+    })
+}
+
+/// ZLEXCOUNT key min max
+pub fn zlexcount_execute(args: &[Vec<u8>]) -> CommandResult {
+    let range = parse_lex_range(&args[1], &args[2])?;
+    with_store(|database| {
+        let count = database.get_sorted_set(&args[0])?.map_or(0, |sorted_set| sorted_set.count_by_lex(&range));
+        Ok(RespValue::Integer(count as i64))
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RangeKind {
+    Rank,
+    Score,
+    Lex,
+}
+
+/// Which members ZRANGE and friends select.
+enum RangeBy {
+    /// Start and stop ranks, negative ones counting from the end.
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+/// A parsed ZRANGE query.
+struct RangeQuery {
+    by: RangeBy,
+    /// Whether to go from the highest score down.
+    reverse: bool,
+    /// LIMIT offset and count. A negative offset selects nothing and a negative count everything after it.
+    offset: i64,
+    count: i64,
+    with_scores: bool,
+}
+
+impl RangeQuery {
+    /// Parse `min max [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]`. The older range commands
+    /// pass the kind and direction they stand for, which cannot be changed with options then.
+    /// With REV, score and lexicographical ranges are given from max to min.
+    fn parse(args: &[Vec<u8>], fixed: Option<(RangeKind, bool)>, allow_with_scores: bool) -> Result<RangeQuery, RedisError> {
+        let (mut kind, mut reverse) = fixed.unwrap_or((RangeKind::Rank, false));
+        let mut limit = None;
+        let mut with_scores = false;
+        let mut index = 2;
+        while let Some(option) = args.get(index) {
+            if allow_with_scores && is_keyword(option, "WITHSCORES") {
+                with_scores = true;
+            } else if is_keyword(option, "LIMIT") && index + 2 < args.len() {
+                limit = Some((parse_integer(&args[index + 1])?, parse_integer(&args[index + 2])?));
+                index += 2;
+            } else if fixed.is_none() && is_keyword(option, "BYSCORE") {
+                kind = RangeKind::Score;
+            } else if fixed.is_none() && is_keyword(option, "BYLEX") {
+                kind = RangeKind::Lex;
+            } else if fixed.is_none() && is_keyword(option, "REV") {
+                reverse = true;
+            } else {
+                return Err(RedisError::Syntax);
+            }
+            index += 1;
+        }
+        if limit.is_some() && kind == RangeKind::Rank {
+            return Err(RedisError::Other(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX".to_owned(),
+            ));
+        }
+        if with_scores && kind == RangeKind::Lex {
+            return Err(RedisError::Other("syntax error, WITHSCORES not supported in combination with BYLEX".to_owned()));
+        }
+        let (min, max) = if reverse && kind != RangeKind::Rank { (&args[1], &args[0]) } else { (&args[0], &args[1]) };
+        let by = match kind {
+            RangeKind::Rank => RangeBy::Rank(parse_integer(min)?, parse_integer(max)?),
+            RangeKind::Score => RangeBy::Score(parse_score_range(min, max)?),
+            RangeKind::Lex => RangeBy::Lex(parse_lex_range(min, max)?),
+        };
+        let (offset, count) = limit.unwrap_or((0, -1));
+        Ok(RangeQuery { by, reverse, offset, count, with_scores })
+    }
+
+    /// A query selecting every member in the range, from the lowest score up.
+    fn all(by: RangeBy) -> RangeQuery {
+        RangeQuery { by, reverse: false, offset: 0, count: -1, with_scores: false }
+    }
+
+    /// The selected members with their scores, in order.
+    fn run(&self, sorted_set: &SortedSet) -> Vec<ScoredMember> {
+        let Ok(offset) = usize::try_from(self.offset) else {
+            return Vec::new();
+        };
+        let count = usize::try_from(self.count).unwrap_or(usize::MAX);
+        let pairs: Box<dyn Iterator<Item = (&[u8], f64)>> = match &self.by {
+            RangeBy::Rank(start, stop) => match normalize_range(*start, *stop, sorted_set.len()) {
+                Some((start, stop)) => Box::new(sorted_set.range_by_rank(start, stop, self.reverse)),
+                None => return Vec::new(),
+            },
+            RangeBy::Score(range) => Box::new(sorted_set.range_by_score(*range, self.reverse)),
+            RangeBy::Lex(range) => Box::new(sorted_set.range_by_lex(range, self.reverse)),
+        };
+        pairs.skip(offset).take(count).map(|(member, score)| (member.to_vec(), score)).collect()
+    }
+}
+
+fn range(args: &[Vec<u8>], fixed: Option<(RangeKind, bool)>, allow_with_scores: bool) -> CommandResult {
+    let query = RangeQuery::parse(&args[1..], fixed, allow_with_scores)?;
+    with_store(|database| {
+        let pairs = database.get_sorted_set(&args[0])?.map(|sorted_set| query.run(sorted_set)).unwrap_or_default();
+        Ok(owned_pairs_reply(&pairs, query.with_scores))
+    })
+}
+
+/// ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
+pub fn zrange_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(args, None, true)
+}
+
+/// ZREVRANGE key start stop [WITHSCORES]
+pub fn zrevrange_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(args, Some((RangeKind::Rank, true)), true)
+}
+
+/// ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
+pub fn zrangebyscore_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(args, Some((RangeKind::Score, false)), true)
+}
+
+/// ZREVRANGEBYSCORE key max min [WITHSCORES] [LIMIT offset count]
+pub fn zrevrangebyscore_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(args, Some((RangeKind::Score, true)), true)
+}
+
+/// ZRANGEBYLEX key min max [LIMIT offset count]
+pub fn zrangebylex_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(args, Some((RangeKind::Lex, false)), false)
+}
+
+/// ZREVRANGEBYLEX key max min [LIMIT offset count]
+pub fn zrevrangebylex_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(args, Some((RangeKind::Lex, true)), false)
+}
+
+/// Store a sorted set under the key, replacing whatever it held, or delete the key if the set is empty.
+/// Replies with the size of the set.
+fn store_sorted_set(database: &mut Database, key: &[u8], sorted_set: SortedSet) -> CommandResult {
+    let length = sorted_set.len();
+    if sorted_set.is_empty() {
+        database.remove(key);
+    } else {
+        database.set(key.to_vec(), Value::SortedSet(sorted_set), Ttl::Persist);
+    }
+    Ok(RespValue::Integer(length as i64))
+}
+
+/// ZRANGESTORE destination source min max [BYSCORE | BYLEX] [REV] [LIMIT offset count]
+pub fn zrangestore_execute(args: &[Vec<u8>]) -> CommandResult {
+    let query = RangeQuery::parse(&args[2..], None, false)?;
+    with_store(|database| {
+        let pairs = database.get_sorted_set(&args[1])?.map(|sorted_set| query.run(sorted_set)).unwrap_or_default();
+        let mut sorted_set = SortedSet::default();
+        for (member, score) in pairs {
+            sorted_set.insert(&member, score);
+        }
+        store_sorted_set(database, &args[0], sorted_set)
+    })
+}
+
+/// Remove the members in the range, deleting the key if none are left. Replies with how many were removed.
+fn remove_range(key: &[u8], by: RangeBy) -> CommandResult {
+    with_store(|database| {
+        let Some(sorted_set) = database.get_sorted_set_mut(key)? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let removed = RangeQuery::all(by).run(sorted_set);
+        for (member, _) in &removed {
+            sorted_set.remove(member);
+        }
+        database.remove_if_empty(key);
+        Ok(RespValue::Integer(removed.len() as i64))
+    })
+}
+
+/// ZREMRANGEBYRANK key start stop
+pub fn zremrangebyrank_execute(args: &[Vec<u8>]) -> CommandResult {
+    remove_range(&args[0], RangeBy::Rank(parse_integer(&args[1])?, parse_integer(&args[2])?))
+}
+
+/// ZREMRANGEBYSCORE key min max
+pub fn zremrangebyscore_execute(args: &[Vec<u8>]) -> CommandResult {
+    remove_range(&args[0], RangeBy::Score(parse_score_range(&args[1], &args[2])?))
+}
+
+/// ZREMRANGEBYLEX key min max
+pub fn zremrangebylex_execute(args: &[Vec<u8>]) -> CommandResult {
+    remove_range(&args[0], RangeBy::Lex(parse_lex_range(&args[1], &args[2])?))
+}
+
+/// Pop up to `count` members with the lowest scores, or the highest ones if `highest` is set, deleting
+/// the key once empty. Returns None if the key does not exist.
+fn pop_members(
+    database: &mut Database,
+    key: &[u8],
+    highest: bool,
+    count: usize,
+) -> Result<Option<Vec<ScoredMember>>, RedisError> {
+    let Some(sorted_set) = database.get_sorted_set_mut(key)? else {
+        return Ok(None);
+    };
+    let popped = (0..count).map_while(|_| sorted_set.pop(highest)).collect();
+    database.remove_if_empty(key);
+    Ok(Some(popped))
+}
+
+fn pop(args: &[Vec<u8>], highest: bool) -> CommandResult {
+    let count = match args.get(1) {
+        None => 1,
+        Some(count) => {
+            let count = parse_integer(count)?;
+            if count < 0 {
+                return Err(RedisError::Other("value is out of range, must be positive".to_owned()));
+            }
+            count as usize
+        }
+    };
+    if args.len() > 2 {
+        return Err(RedisError::Syntax);
+    }
+    with_store(|database| {
+        let popped = pop_members(database, &args[0], highest, count)?.unwrap_or_default();
+        Ok(owned_pairs_reply(&popped, true))
+    })
+}
+
+/// ZPOPMIN key [count]
+pub fn zpopmin_execute(args: &[Vec<u8>]) -> CommandResult {
+    pop(args, false)
+}
+
+/// ZPOPMAX key [count]
+pub fn zpopmax_execute(args: &[Vec<u8>]) -> CommandResult {
+    pop(args, true)
+}
+
+/// The arguments of ZMPOP, also used by BZMPOP: the keys to try in order, which end to pop from and the count.
+struct MultiPopArgs {
+    keys: Vec<Vec<u8>>,
+    highest: bool,
+    count: usize,
+}
+
+impl MultiPopArgs {
+    /// Parse `numkeys key [key ...] MIN | MAX [COUNT count]`.
+    fn parse(args: &[Vec<u8>]) -> Result<MultiPopArgs, RedisError> {
+        let key_count = parse_integer(&args[0])?;
+        if key_count <= 0 {
+            return Err(RedisError::Other("numkeys should be greater than 0".to_owned()));
+        }
+        let key_count = key_count as usize;
+        let (Some(keys), Some(end)) = (args.get(1..=key_count), args.get(key_count + 1)) else {
+            return Err(RedisError::Syntax);
+        };
+        let highest = match () {
+            _ if is_keyword(end, "MIN") => false,
+            _ if is_keyword(end, "MAX") => true,
+            _ => return Err(RedisError::Syntax),
+        };
+        let count = match &args[key_count + 2..] {
+            [] => 1,
+            [option, count] if is_keyword(option, "COUNT") => {
+                let count = parse_integer(count)?;
+                if count <= 0 {
+                    return Err(RedisError::Other("count should be greater than 0".to_owned()));
+                }
+                count as usize
+            }
+            _ => return Err(RedisError::Syntax),
+        };
+        Ok(MultiPopArgs { keys: keys.to_vec(), highest, count })
+    }
+
+    /// Pop from the key if it exists, replying with the key and the members popped as member/score pairs.
+    fn pop_from(&self, database: &mut Database, key: &[u8]) -> Result<Option<RespValue>, RedisError> {
+        let Some(popped) = pop_members(database, key, self.highest, self.count)? else {
+            return Ok(None);
+        };
+        let pairs = popped
+            .into_iter()
+            .map(|(member, score)| RespValue::Array(vec![RespValue::BulkString(member), score_reply(score)]))
+            .collect();
+        Ok(Some(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::Array(pairs)])))
+    }
+}
+
+/// ZMPOP numkeys key [key ...] MIN | MAX [COUNT count]
+/// Pops from the first of the keys that exists.
+pub fn zmpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    let multi_pop = MultiPopArgs::parse(args)?;
+    with_store(|database| {
+        for key in &multi_pop.keys {
+            if let Some(reply) = multi_pop.pop_from(database, key)? {
+                return Ok(reply);
+            }
+        }
+        Ok(RespValue::NullArray)
+    })
+}
+
+/// Shared implementation of BZPOPMIN and BZPOPMAX.
+fn blocking_pop(args: &[Vec<u8>], highest: bool) -> CommandResult {
+    let (timeout, keys) = args.split_last().expect("Arity checked");
+    let timeout = parse_timeout(timeout)?;
+    block_on_keys(
+        keys,
+        timeout,
+        Box::new(move |database, key| match pop_members(database, key, highest, 1) {
+            Ok(popped) => popped?.pop().map(|(member, score)| {
+                Ok(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::BulkString(member), score_reply(score)]))
+            }),
+            Err(error) => Some(Err(error)),
+        }),
+    )
+}
+
+/// BZPOPMIN key [key ...] timeout
+/// Pops from the first non-empty sorted set, or blocks until another client adds to one of them.
+pub fn bzpopmin_execute(args: &[Vec<u8>]) -> CommandResult {
+    blocking_pop(args, false)
+}
+
+/// BZPOPMAX key [key ...] timeout
+pub fn bzpopmax_execute(args: &[Vec<u8>]) -> CommandResult {
+    blocking_pop(args, true)
+}
+
+/// BZMPOP timeout numkeys key [key ...] MIN | MAX [COUNT count]
+pub fn bzmpop_execute(args: &[Vec<u8>]) -> CommandResult {
+    let timeout = parse_timeout(&args[0])?;
+    let multi_pop = MultiPopArgs::parse(&args[1..])?;
+    let keys = multi_pop.keys.clone();
+    block_on_keys(&keys, timeout, Box::new(move |database, key| multi_pop.pop_from(database, key).transpose()))
+}
+
+/// How ZUNION, ZINTER, ZDIFF and their STORE variants combine the sets.
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Union,
+    Intersection,
+    /// Members of the first set that are in none of the others, keeping their scores.
+    Difference,
+}
+
+/// How the scores of a member found in several sets are combined.
+#[derive(Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(self, total: f64, score: f64) -> f64 {
+        match self {
+            // Like Redis, adding opposite infinities gives 0 rather than NaN.
+            Aggregate::Sum => Some(total + score).filter(|sum| !sum.is_nan()).unwrap_or(0.0),
+            Aggregate::Min => total.min(score),
+            Aggregate::Max => total.max(score),
+        }
+    }
+}
+
+/// The arguments of ZUNION and friends after the destination, if any.
+struct CombineArgs {
+    keys: Vec<Vec<u8>>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    with_scores: bool,
+}
+
+impl CombineArgs {
+    /// Parse `numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM | MIN | MAX] [WITHSCORES]`.
+    /// ZDIFF takes neither WEIGHTS nor AGGREGATE, and the STORE variants do not take WITHSCORES.
+    fn parse(args: &[Vec<u8>], command: &str, operation: Operation, store: bool) -> Result<CombineArgs, RedisError> {
+        let key_count = parse_integer(&args[0])?;
+        if key_count < 1 {
+            return Err(RedisError::Other(format!("at least 1 input key is needed for '{}' command", command)));
+        }
+        let key_count = key_count as usize;
+        let Some(keys) = args.get(1..=key_count) else {
+            return Err(RedisError::Syntax);
+        };
+        let mut parsed = CombineArgs {
+            keys: keys.to_vec(),
+            weights: vec![1.0; key_count],
+            aggregate: Aggregate::Sum,
+            with_scores: false,
+        };
+        let mut index = key_count + 1;
+        while let Some(option) = args.get(index) {
+            let weights_allowed = operation != Operation::Difference;
+            if weights_allowed && is_keyword(option, "WEIGHTS") {
+                let weights = args.get(index + 1..=index + key_count).ok_or(RedisError::Syntax)?;
+                parsed.weights = weights
+                    .iter()
+                    .map(|weight| parse_float(weight))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| RedisError::Other("weight value is not a float".to_owned()))?;
+                index += key_count;
+            } else if weights_allowed && is_keyword(option, "AGGREGATE") {
+                let aggregate = args.get(index + 1).ok_or(RedisError::Syntax)?;
+                parsed.aggregate = match () {
+                    _ if is_keyword(aggregate, "SUM") => Aggregate::Sum,
+                    _ if is_keyword(aggregate, "MIN") => Aggregate::Min,
+                    _ if is_keyword(aggregate, "MAX") => Aggregate::Max,
+                    _ => return Err(RedisError::Syntax),
+                };
+                index += 1;
+            } else if !store && is_keyword(option, "WITHSCORES") {
+                parsed.with_scores = true;
+            } else {
+                return Err(RedisError::Syntax);
+            }
+            index += 1;
+        }
+        Ok(parsed)
+    }
+}
+
+/// An input of ZUNION and friends. Plain sets are accepted too, their members all scoring 1.
+enum Input<'a> {
+    Sorted(&'a SortedSet),
+    Plain(&'a Set),
+}
+
+impl Input<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Input::Sorted(sorted_set) => sorted_set.len(),
+            Input::Plain(set) => set.len(),
+        }
+    }
+
+    fn score(&self, member: &[u8]) -> Option<f64> {
+        match self {
+            Input::Sorted(sorted_set) => sorted_set.score(member),
+            Input::Plain(set) => set.contains(member).then_some(1.0),
+        }
+    }
+
+    fn pairs(&self) -> Vec<ScoredMember> {
+        match self {
+            Input::Sorted(sorted_set) => sorted_set.iter().map(|(member, score)| (member.to_vec(), score)).collect(),
+            Input::Plain(set) => set.members().into_iter().map(|member| (member, 1.0)).collect(),
+        }
+    }
+}
+
+/// Multiply a score by its weight, where 0 times infinity gives 0 like in Redis.
+fn weigh(score: f64, weight: f64) -> f64 {
+    Some(score * weight).filter(|weighted| !weighted.is_nan()).unwrap_or(0.0)
+}
+
+/// Combine the sets stored under the keys, missing keys counting as empty sets.
+fn combine(database: &mut Database, args: &CombineArgs, operation: Operation) -> Result<SortedSet, RedisError> {
+    let inputs = database
+        .get_values(&args.keys)
+        .into_iter()
+        .map(|value| match value {
+            None => Ok(None),
+            Some(Value::SortedSet(sorted_set)) => Ok(Some(Input::Sorted(sorted_set))),
+            Some(Value::Set(set)) => Ok(Some(Input::Plain(set))),
+            Some(_) => Err(RedisError::WrongType),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut result = SortedSet::default();
+    match operation {
+        Operation::Union => {
+            let mut scores = HashMap::<Vec<u8>, f64>::new();
+            for (input, weight) in inputs.iter().zip(&args.weights) {
+                for (member, score) in input.iter().flat_map(Input::pairs) {
+                    let score = weigh(score, *weight);
+                    scores.entry(member).and_modify(|total| *total = args.aggregate.apply(*total, score)).or_insert(score);
+                }
+            }
+            for (member, score) in scores {
+                result.insert(&member, score);
+            }
+        }
+        Operation::Intersection => {
+            let Some(inputs) = inputs.into_iter().collect::<Option<Vec<_>>>() else {
+                return Ok(result);
+            };
+            // Only the members of the smallest set can be in all of them.
+            let smallest = inputs.iter().min_by_key(|input| input.len()).expect("At least one key");
+            for (member, _) in smallest.pairs() {
+                let scores = inputs
+                    .iter()
+                    .zip(&args.weights)
+                    .map(|(input, weight)| input.score(&member).map(|score| weigh(score, *weight)))
+                    .collect::<Option<Vec<_>>>();
+                if let Some(total) = scores.and_then(|scores| scores.into_iter().reduce(|a, b| args.aggregate.apply(a, b))) {
+                    result.insert(&member, total);
+                }
+            }
+        }
+        Operation::Difference => {
+            let Some(first) = &inputs[0] else {
+                return Ok(result);
+            };
+            for (member, score) in first.pairs() {
+                if !inputs[1..].iter().flatten().any(|input| input.score(&member).is_some()) {
+                    result.insert(&member, score);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn combine_reply(args: &[Vec<u8>], command: &str, operation: Operation) -> CommandResult {
+    let parsed = CombineArgs::parse(args, command, operation, false)?;
+    with_store(|database| {
+        let result = combine(database, &parsed, operation)?;
+        Ok(pairs_reply(result.iter(), parsed.with_scores))
+    })
+}
+
+fn combine_store(args: &[Vec<u8>], command: &str, operation: Operation) -> CommandResult {
+    let parsed = CombineArgs::parse(&args[1..], command, operation, true)?;
+    with_store(|database| {
+        let result = combine(database, &parsed, operation)?;
+        store_sorted_set(database, &args[0], result)
+    })
+}
+
+/// ZUNION numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM | MIN | MAX] [WITHSCORES]
+pub fn zunion_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_reply(args, "zunion", Operation::Union)
+}
+
+/// ZINTER numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM | MIN | MAX] [WITHSCORES]
+pub fn zinter_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_reply(args, "zinter", Operation::Intersection)
+}
+
+/// ZDIFF numkeys key [key ...] [WITHSCORES]
+pub fn zdiff_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_reply(args, "zdiff", Operation::Difference)
+}
+
+/// ZUNIONSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM | MIN | MAX]
+pub fn zunionstore_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_store(args, "zunionstore", Operation::Union)
+}
+
+/// ZINTERSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM | MIN | MAX]
+pub fn zinterstore_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_store(args, "zinterstore", Operation::Intersection)
+}
+
+/// ZDIFFSTORE destination numkeys key [key ...]
+pub fn zdiffstore_execute(args: &[Vec<u8>]) -> CommandResult {
+    combine_store(args, "zdiffstore", Operation::Difference)
+}
+
+/// ZSCAN key cursor [MATCH pattern] [COUNT count]
+/// Members present during the whole iteration are returned at least once, along with their scores.
+pub fn zscan_execute(args: &[Vec<u8>]) -> CommandResult {
+    let cursor = parse_cursor(&args[1])?;
+    let options = ScanOptions::parse(&args[2..], false)?;
+    with_store(|database| {
+        let Some(sorted_set) = database.get_sorted_set(&args[0])? else {
+            return Ok(scan_reply(0, Vec::new()));
+        };
+        let (cursor, members) = scan_collection(sorted_set.members(), cursor, options.count);
+        let elements = members
+            .into_iter()
+            .filter(|member| options.matches(member))
+            .flat_map(|member| {
+                let score = sorted_set.score(member).expect("Scanned members are in the set");
+                [RespValue::bulk_string(member), score_reply(score)]
+            })
+            .collect();
+        Ok(scan_reply(cursor, elements))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_helpers::args;
+
+    fn bulk_strings(values: &[&str]) -> RespValue {
+        RespValue::Array(values.iter().map(|value| RespValue::bulk_string(value.as_bytes())).collect())
+    }
+
+    #[test]
+    fn test_zadd_flags() {
+        zadd_execute(&args(&["test_zadd", "1", "a", "2", "b"])).unwrap();
+        assert_eq!(zadd_execute(&args(&["test_zadd", "NX", "5", "a", "3", "c"])), Ok(RespValue::Integer(1)));
+        assert_eq!(zadd_execute(&args(&["test_zadd", "XX", "CH", "5", "a", "3", "d"])), Ok(RespValue::Integer(1)));
+        assert_eq!(zadd_execute(&args(&["test_zadd", "GT", "CH", "4", "a", "4", "b"])), Ok(RespValue::Integer(1)));
+        assert_eq!(zadd_execute(&args(&["test_zadd", "LT", "INCR", "1", "a"])), Ok(RespValue::NullBulkString));
+        assert_eq!(zadd_execute(&args(&["test_zadd", "INCR", "-1.5", "a"])), Ok(RespValue::bulk_string(b"3.5")));
+        assert_eq!(
+            zrange_execute(&args(&["test_zadd", "0", "-1", "WITHSCORES"])),
+            Ok(bulk_strings(&["c", "3", "a", "3.5", "b", "4"]))
+        );
+        assert_eq!(
+            zadd_execute(&args(&["test_zadd", "NX", "XX", "1", "a"])),
+            Err(RedisError::Other("XX and NX options at the same time are not compatible".to_owned()))
+        );
+        assert_eq!(zadd_execute(&args(&["test_zadd_missing", "XX", "1", "a"])), Ok(RespValue::Integer(0)));
+        assert!(with_store(|database| database.get(b"test_zadd_missing").is_none()));
+    }
+
+    #[test]
+    fn test_zrange_variants() {
+        zadd_execute(&args(&["test_zrange", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e"])).unwrap();
+        let range = |extra: &[&str]| zrange_execute(&args(&[&["test_zrange"], extra].concat()));
+        assert_eq!(range(&["1", "2"]), Ok(bulk_strings(&["b", "c"])));
+        assert_eq!(range(&["0", "1", "REV"]), Ok(bulk_strings(&["e", "d"])));
+        assert_eq!(range(&["(1", "4", "BYSCORE", "LIMIT", "1", "2"]), Ok(bulk_strings(&["c", "d"])));
+        assert_eq!(range(&["+inf", "(3", "BYSCORE", "REV"]), Ok(bulk_strings(&["e", "d"])));
+        assert_eq!(range(&["[b", "(d", "BYLEX"]), Ok(bulk_strings(&["b", "c"])));
+        assert_eq!(range(&["+", "[d", "BYLEX", "REV", "LIMIT", "0", "1"]), Ok(bulk_strings(&["e"])));
+        assert!(range(&["0", "-1", "LIMIT", "0", "1"]).is_err());
+        assert_eq!(zcount_execute(&args(&["test_zrange", "(1", "+inf"])), Ok(RespValue::Integer(4)));
+        assert_eq!(zlexcount_execute(&args(&["test_zrange", "-", "(c"])), Ok(RespValue::Integer(2)));
+        assert_eq!(zrevrank_execute(&args(&["test_zrange", "b"])), Ok(RespValue::Integer(3)));
+
+        assert_eq!(zremrangebyscore_execute(&args(&["test_zrange", "2", "(4"])), Ok(RespValue::Integer(2)));
+        assert_eq!(range(&["0", "-1"]), Ok(bulk_strings(&["a", "d", "e"])));
+    }
+
+    #[test]
+    fn test_zunionstore_weights_and_aggregate() {
+        zadd_execute(&args(&["test_zunion_a", "1", "x", "2", "y"])).unwrap();
+        zadd_execute(&args(&["test_zunion_b", "10", "y", "20", "z"])).unwrap();
+        crate::commands::set::sadd_execute(&args(&["test_zunion_c", "z"])).unwrap();
+
+        let command = args(&[
+            "test_zunion_out", "3", "test_zunion_a", "test_zunion_b", "test_zunion_c", "WEIGHTS", "2", "1", "100",
+        ]);
+        assert_eq!(zunionstore_execute(&command), Ok(RespValue::Integer(3)));
+        assert_eq!(
+            zrange_execute(&args(&["test_zunion_out", "0", "-1", "WITHSCORES"])),
+            Ok(bulk_strings(&["x", "2", "y", "14", "z", "120"]))
+        );
+
+        let command = args(&["2", "test_zunion_a", "test_zunion_b", "AGGREGATE", "MIN", "WITHSCORES"]);
+        assert_eq!(zinter_execute(&command), Ok(bulk_strings(&["y", "2"])));
+        assert_eq!(zdiff_execute(&args(&["2", "test_zunion_a", "test_zunion_b"])), Ok(bulk_strings(&["x"])));
+        assert_eq!(
+            zunion_execute(&args(&["0", "test_zunion_a"])),
+            Err(RedisError::Other("at least 1 input key is needed for 'zunion' command".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_bzpopmin_wakes_up_on_zadd() {
+        let blocked = std::thread::spawn(|| bzpopmin_execute(&args(&["test_bzpopmin_other", "test_bzpopmin", "0"])));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        zadd_execute(&args(&["test_bzpopmin", "2", "b", "1", "a"])).unwrap();
+        let reply = RespValue::Array(vec![
+            RespValue::bulk_string(b"test_bzpopmin"),
+            RespValue::bulk_string(b"a"),
+            RespValue::bulk_string(b"1"),
+        ]);
+        assert_eq!(blocked.join().unwrap(), Ok(reply));
+        assert_eq!(zcard_execute(&args(&["test_bzpopmin"])), Ok(RespValue::Integer(1)));
+        assert_eq!(bzpopmax_execute(&args(&["test_bzpopmax_timeout", "0.05"])), Ok(RespValue::NullArray));
+    }
+
+    #[test]
+    fn test_zmpop() {
+        zadd_execute(&args(&["test_zmpop", "1", "a", "2", "b", "3", "c"])).unwrap();
+        let reply = zmpop_execute(&args(&["2", "test_zmpop_missing", "test_zmpop", "MAX", "COUNT", "2"]));
+        let pair = |member: &str, score: &str| bulk_strings(&[member, score]);
+        let expected = RespValue::Array(vec![
+            RespValue::bulk_string(b"test_zmpop"),
+            RespValue::Array(vec![pair("c", "3"), pair("b", "2")]),
+        ]);
+        assert_eq!(reply, Ok(expected));
+        assert_eq!(zmpop_execute(&args(&["1", "test_zmpop_missing", "MIN"])), Ok(RespValue::NullArray));
+    }
+}
@@ -0,0 +1,1024 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{check_arity, is_keyword, parse_integer, CommandResult};
+use crate::blocking::block_on_keys;
+use crate::error::RedisError;
+use crate::serialization::RespValue;
+use crate::store::{now_ms, with_store, Database};
+use crate::stream::{Claim, Fields, NodeLimits, Stream, StreamId, Trim};
+
+fn invalid_id() -> RedisError {
+    RedisError::Other("Invalid stream ID specified as stream command argument".to_owned())
+}
+
+/// Parse a stream ID argument, a lone `ms` getting `missing_seq` as its sequence number.
+pub fn parse_id(arg: &[u8], missing_seq: u64) -> Result<StreamId, RedisError> {
+    StreamId::parse(arg, missing_seq).ok_or_else(invalid_id)
+}
+
+/// Parse the start of an XRANGE interval: `-` for the first entry, or an ID made exclusive by a `(` prefix.
+pub fn parse_range_start(arg: &[u8]) -> Result<StreamId, RedisError> {
+    match arg.strip_prefix(b"(") {
+        _ if arg == b"-" => Ok(StreamId::MIN),
+        Some(id) => parse_id(id, 0)?
+            .next()
+            .ok_or_else(|| RedisError::Other("invalid start ID for the interval".to_owned())),
+        None => parse_id(arg, 0),
+    }
+}
+
+/// Parse the end of an XRANGE interval: `+` for the last entry, or an ID made exclusive by a `(` prefix.
+/// A lone `ms` covers every entry of that millisecond.
+pub fn parse_range_end(arg: &[u8]) -> Result<StreamId, RedisError> {
+    match arg.strip_prefix(b"(") {
+        _ if arg == b"+" => Ok(StreamId::MAX),
+        Some(id) => parse_id(id, u64::MAX)?
+            .previous()
+            .ok_or_else(|| RedisError::Other("invalid end ID for the interval".to_owned())),
+        None => parse_id(arg, u64::MAX),
+    }
+}
+
+/// Reply with an entry as its ID followed by its fields and values.
+pub fn entry_reply(id: StreamId, fields: &Fields) -> RespValue {
+    let fields = fields.iter().map(RespValue::bulk_string).collect();
+    RespValue::Array(vec![RespValue::bulk_string(id.to_string()), RespValue::Array(fields)])
+}
+
+/// The trimming options of XADD and XTRIM.
+struct TrimArgs {
+    trim: Trim,
+    /// Whether `~` was given, allowing the stream to keep a few more entries so whole nodes can be dropped.
+    approximate: bool,
+    /// Most entries an approximate trim may remove, 0 meaning no limit.
+    limit: Option<usize>,
+}
+
+impl TrimArgs {
+    /// Parse `[NOMKSTREAM] [MAXLEN | MINID [= | ~] threshold [LIMIT count]]`, NOMKSTREAM being only
+    /// accepted by XADD. XADD stops at the first argument that is not an option, which is the ID.
+    /// Returns the trimming options if any, whether NOMKSTREAM was given and how many arguments were used.
+    fn parse(args: &[Vec<u8>], xadd: bool) -> Result<(Option<TrimArgs>, bool, usize), RedisError> {
+        let (mut trim, mut approximate, mut limit, mut no_create) = (None, false, None, false);
+        let mut index = 0;
+        while let Some(option) = args.get(index) {
+            let remaining = args.len() - index - 1;
+            let max_length = is_keyword(option, "MAXLEN");
+            if xadd && is_keyword(option, "NOMKSTREAM") {
+                no_create = true;
+            } else if (max_length || is_keyword(option, "MINID")) && remaining >= 1 {
+                if trim.is_some() {
+                    return Err(RedisError::Other(
+                        "syntax error, MAXLEN and MINID options at the same time are not compatible".to_owned(),
+                    ));
+                }
+                let operator = args[index + 1].as_slice();
+                if (operator == b"~" || operator == b"=") && remaining >= 2 {
+                    approximate = operator == b"~";
+                    index += 1;
+                }
+                let threshold = &args[index + 1];
+                trim = Some(if max_length {
+                    let max_length = parse_integer(threshold)?;
+                    if max_length < 0 {
+                        return Err(RedisError::Other("The MAXLEN argument must be >= 0.".to_owned()));
+                    }
+                    Trim::MaxLen(max_length as usize)
+                } else {
+                    Trim::MinId(parse_id(threshold, 0)?)
+                });
+                index += 1;
+            } else if is_keyword(option, "LIMIT") && remaining >= 1 {
+                let count = parse_integer(&args[index + 1])?;
+                if count < 0 {
+                    return Err(RedisError::Other("The LIMIT argument must be >= 0.".to_owned()));
+                }
+                limit = Some(count as usize);
+                index += 1;
+            } else if xadd {
+                break;
+            } else {
+                return Err(RedisError::Syntax);
+            }
+            index += 1;
+        }
+        if limit.is_some() && !approximate {
+            return Err(RedisError::Other("syntax error, LIMIT cannot be used without the special ~ option".to_owned()));
+        }
+        Ok((trim.map(|trim| TrimArgs { trim, approximate, limit }), no_create, index))
+    }
+
+    /// Trim the stream, returning how many entries were removed. Without an explicit LIMIT an approximate
+    /// trim removes at most 100 nodes worth of entries, like in Redis.
+    fn apply(&self, stream: &mut Stream, limits: NodeLimits) -> usize {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None if self.approximate => 100 * limits.max_entries,
+            None => 0,
+        };
+        stream.trim(self.trim, self.approximate, Some(limit).filter(|limit| *limit > 0))
+    }
+}
+
+/// The ID argument of XADD.
+enum NewId {
+    /// `*`: pick one from the current time.
+    Auto,
+    /// `ms-*`: pick the next sequence number within that millisecond.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+fn id_too_small() -> RedisError {
+    RedisError::Other("The ID specified in XADD is equal or smaller than the target stream top item".to_owned())
+}
+
+/// XADD key [NOMKSTREAM] [MAXLEN | MINID [= | ~] threshold [LIMIT count]] * | id field value [field value ...]
+/// Replies with the ID of the new entry.
+pub fn xadd_execute(args: &[Vec<u8>]) -> CommandResult {
+    let key = &args[0];
+    let (trim, no_create, used) = TrimArgs::parse(&args[1..], true)?;
+    let Some((id, fields)) = args[1 + used..].split_first() else {
+        return Err(RedisError::Syntax);
+    };
+    if fields.is_empty() || !fields.len().is_multiple_of(2) {
+        return Err(RedisError::WrongArity("xadd".to_owned()));
+    }
+    let new_id = if id.as_slice() == b"*" {
+        NewId::Auto
+    } else if let Some(ms) = id.strip_suffix(b"-*") {
+        NewId::AutoSeq(parse_id(ms, 0)?.ms)
+    } else {
+        NewId::Explicit(parse_id(id, 0)?)
+    };
+    if let NewId::Explicit(StreamId::MIN) = new_id {
+        return Err(RedisError::Other("The ID specified in XADD must be greater than 0-0".to_owned()));
+    }
+    let limits = NodeLimits::from_config();
+
+    with_store(|database| {
+        if no_create && database.get_stream(key)?.is_none() {
+            return Ok(RespValue::NullBulkString);
+        }
+        let stream = database.get_or_create_stream(key)?;
+        let id = match new_id {
+            NewId::Auto => stream.next_id(now_ms()).ok_or_else(|| {
+                RedisError::Other("The stream has exhausted the last possible ID, unable to add more items".to_owned())
+            })?,
+            NewId::AutoSeq(ms) => stream.next_id_at(ms).ok_or_else(id_too_small)?,
+            NewId::Explicit(id) if id > stream.last_id() => id,
+            NewId::Explicit(_) => return Err(id_too_small()),
+        };
+        stream.add(id, fields.to_vec(), limits);
+        if let Some(trim) = &trim {
+            trim.apply(stream, limits);
+        }
+        // Readers blocked on the stream wait for new entries, not just for the key to be created.
+        database.blocked_clients().signal_key_ready(key);
+        Ok(RespValue::bulk_string(id.to_string()))
+    })
+}
+
+/// XLEN key
+pub fn xlen_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let length = database.get_stream(&args[0])?.map_or(0, Stream::len);
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// XTRIM key MAXLEN | MINID [= | ~] threshold [LIMIT count]
+pub fn xtrim_execute(args: &[Vec<u8>]) -> CommandResult {
+    let (Some(trim), _, _) = TrimArgs::parse(&args[1..], false)? else {
+        return Err(RedisError::Syntax);
+    };
+    let limits = NodeLimits::from_config();
+    with_store(|database| {
+        let removed = database.get_stream_mut(&args[0])?.map_or(0, |stream| trim.apply(stream, limits));
+        Ok(RespValue::Integer(removed as i64))
+    })
+}
+
+/// XDEL key id [id ...]
+pub fn xdel_execute(args: &[Vec<u8>]) -> CommandResult {
+    let ids = args[1..].iter().map(|id| parse_id(id, 0)).collect::<Result<Vec<_>, _>>()?;
+    with_store(|database| {
+        let Some(stream) = database.get_stream_mut(&args[0])? else {
+            return Ok(RespValue::Integer(0));
+        };
+        let deleted = ids.into_iter().filter(|id| stream.delete(*id)).count();
+        Ok(RespValue::Integer(deleted as i64))
+    })
+}
+
+/// Shared implementation of XRANGE and XREVRANGE, given the arguments after the key.
+fn range(key: &[u8], start: &[u8], end: &[u8], options: &[Vec<u8>], reverse: bool) -> CommandResult {
+    let (start, end) = (parse_range_start(start)?, parse_range_end(end)?);
+    let count = match options {
+        [] => usize::MAX,
+        [option, count] if is_keyword(option, "COUNT") => parse_integer(count)?.max(0) as usize,
+        _ => return Err(RedisError::Syntax),
+    };
+    with_store(|database| {
+        let entries = database
+            .get_stream(key)?
+            .map(|stream| stream.range(start, end, reverse).take(count).map(|(id, fields)| entry_reply(id, fields)).collect())
+            .unwrap_or_default();
+        Ok(RespValue::Array(entries))
+    })
+}
+
+/// XRANGE key start end [COUNT count]
+pub fn xrange_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(&args[0], &args[1], &args[2], &args[3..], false)
+}
+
+/// XREVRANGE key end start [COUNT count]
+pub fn xrevrange_execute(args: &[Vec<u8>]) -> CommandResult {
+    range(&args[0], &args[2], &args[1], &args[3..], true)
+}
+
+/// Reply with the key and up to `count` of its entries after the ID, or None if there are none.
+fn read_after(database: &mut Database, key: &[u8], after: StreamId, count: usize) -> Result<Option<RespValue>, RedisError> {
+    let Some((stream, start)) = database.get_stream(key)?.zip(after.next()) else {
+        return Ok(None);
+    };
+    let entries = stream
+        .range(start, StreamId::MAX, false)
+        .take(count)
+        .map(|(id, fields)| entry_reply(id, fields))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::Array(entries)])))
+}
+
+/// Parse the BLOCK argument of XREAD, in milliseconds. 0 means wait forever.
+pub fn parse_block_timeout(arg: &[u8]) -> Result<Option<Duration>, RedisError> {
+    let milliseconds =
+        parse_integer(arg).map_err(|_| RedisError::Other("timeout is not an integer or out of range".to_owned()))?;
+    if milliseconds < 0 {
+        return Err(RedisError::Other("timeout is negative".to_owned()));
+    }
+    Ok(Some(Duration::from_millis(milliseconds as u64)).filter(|timeout| !timeout.is_zero()))
+}
+
+/// Where XREAD and XREADGROUP start reading a stream from.
+#[derive(Clone, Copy)]
+enum ReadFrom {
+    /// `$`: only entries added from now on, for XREAD.
+    Last,
+    /// `>`: the entries the group has not delivered yet, for XREADGROUP.
+    Undelivered,
+    After(StreamId),
+}
+
+/// The options and streams of XREAD and XREADGROUP.
+struct ReadArgs<'a> {
+    count: usize,
+    /// The BLOCK timeout if given, None within meaning forever.
+    block: Option<Option<Duration>>,
+    no_ack: bool,
+    keys: &'a [Vec<u8>],
+    from: Vec<ReadFrom>,
+}
+
+impl ReadArgs<'_> {
+    /// Parse `[COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]`, NOACK being
+    /// only accepted by XREADGROUP.
+    fn parse(args: &[Vec<u8>], group: bool) -> Result<ReadArgs<'_>, RedisError> {
+        let (mut count, mut block, mut no_ack) = (usize::MAX, None, false);
+        let mut index = 0;
+        loop {
+            match &args[index..] {
+                [option, value, ..] if is_keyword(option, "COUNT") => {
+                    // Like in Redis, a count of 0 means no limit.
+                    count = Some(parse_integer(value)?.max(0) as usize).filter(|count| *count > 0).unwrap_or(usize::MAX);
+                }
+                [option, value, ..] if is_keyword(option, "BLOCK") => block = Some(parse_block_timeout(value)?),
+                [option, ..] if group && is_keyword(option, "NOACK") => {
+                    no_ack = true;
+                    index -= 1;
+                }
+                [option, ..] if is_keyword(option, "STREAMS") => break,
+                _ => return Err(RedisError::Syntax),
+            }
+            index += 2;
+        }
+        let streams = &args[index + 1..];
+        if streams.is_empty() || !streams.len().is_multiple_of(2) {
+            let (command, special) = if group { ("xreadgroup", ">") } else { ("xread", "$") };
+            return Err(RedisError::Other(format!(
+                "Unbalanced '{}' list of streams: for each stream key an ID or '{}' must be specified.",
+                command, special
+            )));
+        }
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+        let from = ids
+            .iter()
+            .map(|id| match id.as_slice() {
+                b"$" if group => Err(RedisError::Other(
+                    "The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this \
+                     consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just \
+                     return an empty result set."
+                        .to_owned(),
+                )),
+                b"$" => Ok(ReadFrom::Last),
+                b">" if group => Ok(ReadFrom::Undelivered),
+                b">" => Err(RedisError::Other(
+                    "The > ID can be specified only when calling XREADGROUP using the GROUP <group> <consumer> option."
+                        .to_owned(),
+                )),
+                _ => parse_id(id, 0).map(ReadFrom::After),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ReadArgs { count, block, no_ack, keys, from })
+    }
+}
+
+/// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+/// Replies with the entries after the given ID of every stream that has some. `$` stands for the last ID,
+/// to only get entries added from now on. With BLOCK and nothing to read, waits for an XADD to one of the streams.
+pub fn xread_execute(args: &[Vec<u8>]) -> CommandResult {
+    let ReadArgs { count, block, keys, from, .. } = ReadArgs::parse(args, false)?;
+    let (positions, replies) = with_store(|database| {
+        let mut positions = HashMap::new();
+        let mut replies = Vec::new();
+        for (key, from) in keys.iter().zip(from) {
+            let after = match from {
+                ReadFrom::After(id) => id,
+                _ => database.get_stream(key)?.map_or(StreamId::MIN, Stream::last_id),
+            };
+            positions.insert(key.clone(), after);
+            replies.extend(read_after(database, key, after, count)?);
+        }
+        Ok::<_, RedisError>((positions, replies))
+    })?;
+    let Some(timeout) = block.filter(|_| replies.is_empty()) else {
+        return Ok(if replies.is_empty() { RespValue::NullArray } else { RespValue::Array(replies) });
+    };
+    block_on_keys(
+        keys,
+        timeout,
+        Box::new(move |database, key| {
+            let reply = read_after(database, key, positions[key], count);
+            reply.map(|reply| reply.map(|reply| RespValue::Array(vec![reply]))).transpose()
+        }),
+    )
+}
+
+fn lossy(arg: &[u8]) -> std::borrow::Cow<'_, str> {
+    String::from_utf8_lossy(arg)
+}
+
+fn no_such_group(key: &[u8], group: &[u8]) -> RedisError {
+    RedisError::NoGroup(format!("No such key '{}' or consumer group '{}'", lossy(key), lossy(group)))
+}
+
+/// The stream at the key, which must have the consumer group.
+fn stream_with_group<'a>(database: &'a mut Database, key: &[u8], group: &[u8]) -> Result<&'a mut Stream, RedisError> {
+    match database.get_stream_mut(key)? {
+        Some(stream) if stream.group(group).is_some() => Ok(stream),
+        _ => Err(no_such_group(key, group)),
+    }
+}
+
+/// Reply with the key and up to `count` of the entries the group has not delivered yet, now delivered to the
+/// consumer, or None if there are none.
+fn read_undelivered(
+    database: &mut Database,
+    key: &[u8],
+    group: &[u8],
+    consumer: &[u8],
+    count: usize,
+    no_ack: bool,
+) -> Result<Option<RespValue>, RedisError> {
+    let stream = stream_with_group(database, key, group)?;
+    let entries = stream.read_group(group, consumer, count, no_ack, now_ms()).unwrap_or_default();
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let entries = entries.iter().map(|(id, fields)| entry_reply(*id, fields)).collect();
+    Ok(Some(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::Array(entries)])))
+}
+
+/// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]
+/// With the `>` ID, delivers to the consumer entries the group has not delivered to any consumer yet, which stay
+/// pending until acknowledged with XACK unless NOACK is given. With BLOCK and no such entries, waits for an XADD.
+/// With any other ID, delivers again the entries pending for the consumer after that ID.
+pub fn xreadgroup_execute(args: &[Vec<u8>]) -> CommandResult {
+    if !is_keyword(&args[0], "GROUP") {
+        return Err(RedisError::Syntax);
+    }
+    let (group, consumer) = (args[1].clone(), args[2].clone());
+    let ReadArgs { count, block, no_ack, keys, from } = ReadArgs::parse(&args[3..], true)?;
+    let replies = with_store(|database| {
+        // Check every stream first so that nothing is delivered when the command fails.
+        for key in keys {
+            if database.get_stream(key)?.and_then(|stream| stream.group(&group)).is_none() {
+                return Err(RedisError::NoGroup(format!(
+                    "No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                    lossy(key),
+                    lossy(&group)
+                )));
+            }
+        }
+        let mut replies = Vec::new();
+        for (key, from) in keys.iter().zip(from) {
+            let ReadFrom::After(after) = from else {
+                replies.extend(read_undelivered(database, key, &group, &consumer, count, no_ack)?);
+                continue;
+            };
+            // History is always replied to, even when the consumer has nothing pending.
+            let stream = stream_with_group(database, key, &group)?;
+            let entries = stream.read_pending(&group, &consumer, after, count, now_ms()).unwrap_or_default();
+            let entries = entries
+                .iter()
+                .map(|(id, fields)| match fields {
+                    Some(fields) => entry_reply(*id, fields),
+                    None => RespValue::Array(vec![RespValue::bulk_string(id.to_string()), RespValue::NullArray]),
+                })
+                .collect();
+            replies.push(RespValue::Array(vec![RespValue::bulk_string(key), RespValue::Array(entries)]));
+        }
+        Ok(replies)
+    })?;
+    let Some(timeout) = block.filter(|_| replies.is_empty()) else {
+        return Ok(if replies.is_empty() { RespValue::NullArray } else { RespValue::Array(replies) });
+    };
+    block_on_keys(
+        keys,
+        timeout,
+        Box::new(move |database, key| {
+            if database.get_stream(key).is_ok_and(|stream| stream.and_then(|stream| stream.group(&group)).is_none()) {
+                let message = "the consumer group this client was blocked on no longer exists";
+                return Some(Err(RedisError::NoGroup(message.to_owned())));
+            }
+            let reply = read_undelivered(database, key, &group, &consumer, count, no_ack);
+            reply.map(|reply| reply.map(|reply| RespValue::Array(vec![reply]))).transpose()
+        }),
+    )
+}
+
+/// XACK key group id [id ...]
+pub fn xack_execute(args: &[Vec<u8>]) -> CommandResult {
+    let ids = args[2..].iter().map(|id| parse_id(id, 0)).collect::<Result<Vec<_>, _>>()?;
+    with_store(|database| {
+        let Some(group) = database.get_stream_mut(&args[0])?.and_then(|stream| stream.group_mut(&args[1])) else {
+            return Ok(RespValue::Integer(0));
+        };
+        let acknowledged = ids.into_iter().filter(|id| group.acknowledge(*id)).count();
+        Ok(RespValue::Integer(acknowledged as i64))
+    })
+}
+
+fn parse_entries_read(arg: &[u8]) -> Result<Option<u64>, RedisError> {
+    match parse_integer(arg)? {
+        -1 => Ok(None),
+        entries_read if entries_read >= 0 => Ok(Some(entries_read as u64)),
+        _ => Err(RedisError::Other("value for ENTRIESREAD must be positive or -1".to_owned())),
+    }
+}
+
+/// XGROUP CREATE key group id | $ [MKSTREAM] [ENTRIESREAD entries-read]
+/// XGROUP SETID key group id | $ [ENTRIESREAD entries-read]
+/// XGROUP DESTROY key group
+/// XGROUP CREATECONSUMER key group consumer
+/// XGROUP DELCONSUMER key group consumer
+pub fn xgroup_execute(args: &[Vec<u8>]) -> CommandResult {
+    let subcommand = lossy(&args[0]).to_uppercase();
+    let (name, arity) = match subcommand.as_str() {
+        "CREATE" => ("xgroup|create", -5),
+        "SETID" => ("xgroup|setid", -5),
+        "DESTROY" => ("xgroup|destroy", 4),
+        "CREATECONSUMER" => ("xgroup|createconsumer", 5),
+        "DELCONSUMER" => ("xgroup|delconsumer", 5),
+        _ => return Err(RedisError::UnknownSubcommand { command: "XGROUP".to_owned(), subcommand: lossy(&args[0]).into_owned() }),
+    };
+    check_arity(name, arity, args)?;
+    let (key, group) = (&args[1], &args[2]);
+
+    let (mut create_stream, mut entries_read) = (false, None);
+    let mut options = args.iter().skip(4);
+    if arity < 0 {
+        while let Some(option) = options.next() {
+            if subcommand == "CREATE" && is_keyword(option, "MKSTREAM") {
+                create_stream = true;
+            } else if let (true, Some(value)) = (is_keyword(option, "ENTRIESREAD"), options.clone().next()) {
+                entries_read = parse_entries_read(value)?;
+                options.next();
+            } else {
+                return Err(RedisError::Syntax);
+            }
+        }
+    }
+    let id = match args.get(3).filter(|_| arity < 0) {
+        Some(id) if id.as_slice() == b"$" => None,
+        Some(id) => Some(parse_id(id, 0)?),
+        None => None,
+    };
+
+    with_store(|database| {
+        if create_stream {
+            database.get_or_create_stream(key)?;
+        }
+        let Some(stream) = database.get_stream_mut(key)? else {
+            return Err(RedisError::Other(
+                "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the \
+                 MKSTREAM option to create an empty stream automatically."
+                    .to_owned(),
+            ));
+        };
+        let id = id.unwrap_or(stream.last_id());
+        if subcommand == "CREATE" {
+            if !stream.create_group(group, id, entries_read) {
+                return Err(RedisError::BusyGroup);
+            }
+            return Ok(RespValue::ok());
+        }
+        if subcommand == "DESTROY" {
+            let destroyed = stream.destroy_group(group);
+            // Consumers blocked on the group get an error.
+            database.blocked_clients().signal_key_ready(key);
+            return Ok(RespValue::Integer(destroyed as i64));
+        }
+        let Some(consumer_group) = stream.group_mut(group) else {
+            return Err(RedisError::NoGroup(format!("No such consumer group '{}' for key name '{}'", lossy(group), lossy(key))));
+        };
+        match subcommand.as_str() {
+            "SETID" => {
+                consumer_group.last_delivered_id = id;
+                consumer_group.entries_read = entries_read;
+                Ok(RespValue::ok())
+            }
+            "CREATECONSUMER" => Ok(RespValue::Integer(consumer_group.create_consumer(&args[3], now_ms()) as i64)),
+            _ => Ok(RespValue::Integer(consumer_group.delete_consumer(&args[3]).unwrap_or(0) as i64)),
+        }
+    })
+}
+
+/// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+/// Without a range, replies with the number of pending entries, the smallest and greatest of their IDs and
+/// how many each consumer has. With one, lists the pending entries with their consumer, idle time and
+/// delivery count.
+pub fn xpending_execute(args: &[Vec<u8>]) -> CommandResult {
+    let (key, group) = (&args[0], &args[1]);
+    let (min_idle, range) = match &args[2..] {
+        [option, idle, range @ ..] if is_keyword(option, "IDLE") && matches!(range.len(), 3 | 4) => {
+            (parse_integer(idle)?.max(0) as u64, Some(range))
+        }
+        [] => (0, None),
+        range @ [_, _, _] | range @ [_, _, _, _] => (0, Some(range)),
+        _ => return Err(RedisError::Syntax),
+    };
+    let range = match range {
+        Some(range) => {
+            let (start, end) = (parse_range_start(&range[0])?, parse_range_end(&range[1])?);
+            Some((start, end, parse_integer(&range[2])?.max(0) as usize, range.get(3)))
+        }
+        None => None,
+    };
+    let now = now_ms();
+    with_store(|database| {
+        let stream = stream_with_group(database, key, group)?;
+        let group = stream.group(group).expect("Group checked above");
+        let Some((start, end, count, consumer)) = range else {
+            let (Some((first, _)), Some((last, _))) = (group.pending.first_key_value(), group.pending.last_key_value()) else {
+                return Ok(RespValue::Array(vec![
+                    RespValue::Integer(0),
+                    RespValue::NullBulkString,
+                    RespValue::NullBulkString,
+                    RespValue::NullArray,
+                ]));
+            };
+            let consumers = group
+                .consumers
+                .iter()
+                .filter(|(_, consumer)| !consumer.pending.is_empty())
+                .map(|(name, consumer)| {
+                    let pending = RespValue::bulk_string(consumer.pending.len().to_string());
+                    RespValue::Array(vec![RespValue::bulk_string(name), pending])
+                })
+                .collect();
+            return Ok(RespValue::Array(vec![
+                RespValue::Integer(group.pending.len() as i64),
+                RespValue::bulk_string(first.to_string()),
+                RespValue::bulk_string(last.to_string()),
+                RespValue::Array(consumers),
+            ]));
+        };
+        if start > end {
+            return Ok(RespValue::Array(Vec::new()));
+        }
+        let entries = group
+            .pending
+            .range(start..=end)
+            .filter(|(_, entry)| consumer.is_none_or(|consumer| entry.consumer == *consumer))
+            .filter(|(_, entry)| now.saturating_sub(entry.delivery_time) >= min_idle)
+            .take(count)
+            .map(|(id, entry)| {
+                RespValue::Array(vec![
+                    RespValue::bulk_string(id.to_string()),
+                    RespValue::bulk_string(&entry.consumer),
+                    RespValue::Integer(now.saturating_sub(entry.delivery_time) as i64),
+                    RespValue::Integer(entry.delivery_count as i64),
+                ])
+            })
+            .collect();
+        Ok(RespValue::Array(entries))
+    })
+}
+
+/// Parse the min-idle-time argument of XCLAIM and XAUTOCLAIM.
+fn parse_min_idle(arg: &[u8], command: &str) -> Result<u64, RedisError> {
+    let min_idle = parse_integer(arg)
+        .map_err(|_| RedisError::Other(format!("Invalid min-idle-time argument for {}", command)))?;
+    Ok(min_idle.max(0) as u64)
+}
+
+/// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds] [RETRYCOUNT count]
+///   [FORCE] [JUSTID] [LASTID lastid]
+/// Transfers the pending entries idle for at least min-idle-time milliseconds to the consumer, replying with
+/// those claimed. FORCE also claims entries of the stream no consumer has pending.
+pub fn xclaim_execute(args: &[Vec<u8>]) -> CommandResult {
+    let (key, group, consumer) = (&args[0], &args[1], &args[2]);
+    let min_idle = parse_min_idle(&args[3], "XCLAIM")?;
+    let mut ids = Vec::new();
+    let mut options = args[4..].iter().peekable();
+    while let Some(id) = options.peek().and_then(|id| StreamId::parse(id, 0)) {
+        ids.push(id);
+        options.next();
+    }
+
+    let now = now_ms();
+    let (mut delivery_time, mut delivery_count, mut force, mut just_id, mut last_id) = (now, None, false, false, None);
+    while let Some(option) = options.next() {
+        let upper = lossy(option).to_uppercase();
+        let invalid = || RedisError::Other(format!("Invalid {} option argument for XCLAIM", upper));
+        match upper.as_str() {
+            "FORCE" => force = true,
+            "JUSTID" => just_id = true,
+            "IDLE" | "TIME" | "RETRYCOUNT" | "LASTID" => {
+                let value = options.next().ok_or(RedisError::Syntax)?;
+                match upper.as_str() {
+                    "IDLE" => delivery_time = now.saturating_sub(parse_integer(value).map_err(|_| invalid())?.max(0) as u64),
+                    "TIME" => delivery_time = parse_integer(value).map_err(|_| invalid())?.max(0) as u64,
+                    "RETRYCOUNT" => delivery_count = Some(parse_integer(value).map_err(|_| invalid())?.max(0) as u64),
+                    _ => last_id = Some(parse_id(value, 0)?),
+                }
+            }
+            _ => return Err(RedisError::Other(format!("Unrecognized XCLAIM option '{}'", lossy(option)))),
+        }
+    }
+    // A delivery time in the future makes no sense, like in Redis it is taken as now.
+    let claim = Claim { min_idle, delivery_time: delivery_time.min(now), delivery_count, just_id };
+
+    with_store(|database| {
+        let stream = stream_with_group(database, key, group)?;
+        if let Some(last_id) = last_id {
+            let group = stream.group_mut(group).expect("Group checked above");
+            group.last_delivered_id = group.last_delivered_id.max(last_id);
+        }
+        let claimed = stream.claim(group, consumer, &ids, claim, force, now).unwrap_or_default();
+        Ok(RespValue::Array(claimed_reply(stream, &claimed, just_id)))
+    })
+}
+
+/// Reply with the claimed entries, or only their IDs.
+fn claimed_reply(stream: &Stream, claimed: &[StreamId], just_id: bool) -> Vec<RespValue> {
+    claimed
+        .iter()
+        .map(|id| match stream.get(*id) {
+            Some(fields) if !just_id => entry_reply(*id, fields),
+            _ => RespValue::bulk_string(id.to_string()),
+        })
+        .collect()
+}
+
+/// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+/// Like XCLAIM with the pending entries idle for long enough from start on, up to count of them (100 by default).
+/// Replies with the ID to continue from, 0-0 once the whole list was scanned, the claimed entries, and the IDs
+/// of the entries deleted from the stream, which were dropped from the pending entries list.
+pub fn xautoclaim_execute(args: &[Vec<u8>]) -> CommandResult {
+    let (key, group, consumer) = (&args[0], &args[1], &args[2]);
+    let min_idle = parse_min_idle(&args[3], "XAUTOCLAIM")?;
+    let start = parse_range_start(&args[4])?;
+    let (mut count, mut just_id) = (100, false);
+    let mut options = args[5..].iter();
+    while let Some(option) = options.next() {
+        if is_keyword(option, "JUSTID") {
+            just_id = true;
+        } else if let (true, Some(value)) = (is_keyword(option, "COUNT"), options.clone().next()) {
+            // Redis bounds the count so that ten times it, the most entries looked at, fits.
+            count = Some(parse_integer(value)?)
+                .filter(|count| (1..=i64::MAX / 10).contains(count))
+                .ok_or_else(|| RedisError::Other("COUNT must be > 0".to_owned()))? as usize;
+            options.next();
+        } else {
+            return Err(RedisError::Syntax);
+        }
+    }
+
+    let now = now_ms();
+    let claim = Claim { min_idle, delivery_time: now, delivery_count: None, just_id };
+    with_store(|database| {
+        let stream = stream_with_group(database, key, group)?;
+        let (next, claimed, deleted) = stream.auto_claim(group, consumer, start, count, claim, now).unwrap_or_default();
+        let deleted = deleted.iter().map(|id| RespValue::bulk_string(id.to_string())).collect();
+        Ok(RespValue::Array(vec![
+            RespValue::bulk_string(next.to_string()),
+            RespValue::Array(claimed_reply(stream, &claimed, just_id)),
+            RespValue::Array(deleted),
+        ]))
+    })
+}
+
+fn optional_integer(value: Option<u64>) -> RespValue {
+    value.map_or(RespValue::NullBulkString, |value| RespValue::Integer(value as i64))
+}
+
+fn id_reply(id: StreamId) -> RespValue {
+    RespValue::bulk_string(id.to_string())
+}
+
+/// Flatten field names and values into a reply, like Redis does for maps over RESP2.
+fn fields_reply(fields: Vec<(&str, RespValue)>) -> RespValue {
+    let fields = fields.into_iter().flat_map(|(name, value)| [RespValue::bulk_string(name), value]);
+    RespValue::Array(fields.collect())
+}
+
+/// Reply with the groups of the stream as XINFO STREAM FULL does, listing at most `count` pending entries.
+fn full_groups_reply(stream: &Stream, count: usize) -> RespValue {
+    let groups = stream.groups().map(|(name, group)| {
+        let pending = group.pending.iter().take(count).map(|(id, entry)| {
+            RespValue::Array(vec![
+                id_reply(*id),
+                RespValue::bulk_string(&entry.consumer),
+                RespValue::Integer(entry.delivery_time as i64),
+                RespValue::Integer(entry.delivery_count as i64),
+            ])
+        });
+        let consumers = group.consumers.iter().map(|(name, consumer)| {
+            let pending = consumer.pending.iter().take(count).map(|id| {
+                let entry = &group.pending[id];
+                RespValue::Array(vec![
+                    id_reply(*id),
+                    RespValue::Integer(entry.delivery_time as i64),
+                    RespValue::Integer(entry.delivery_count as i64),
+                ])
+            });
+            fields_reply(vec![
+                ("name", RespValue::bulk_string(name)),
+                ("seen-time", RespValue::Integer(consumer.seen_time as i64)),
+                ("active-time", RespValue::Integer(consumer.active_time.map_or(-1, |time| time as i64))),
+                ("pel-count", RespValue::Integer(consumer.pending.len() as i64)),
+                ("pending", RespValue::Array(pending.collect())),
+            ])
+        });
+        fields_reply(vec![
+            ("name", RespValue::bulk_string(name)),
+            ("last-delivered-id", id_reply(group.last_delivered_id)),
+            ("entries-read", optional_integer(group.entries_read)),
+            ("lag", optional_integer(stream.lag(group))),
+            ("pel-count", RespValue::Integer(group.pending.len() as i64)),
+            ("pending", RespValue::Array(pending.collect())),
+            ("consumers", RespValue::Array(consumers.collect())),
+        ])
+    });
+    RespValue::Array(groups.collect())
+}
+
+/// XINFO STREAM key [FULL [COUNT count]]
+/// XINFO GROUPS key
+/// XINFO CONSUMERS key group
+pub fn xinfo_execute(args: &[Vec<u8>]) -> CommandResult {
+    let subcommand = lossy(&args[0]).to_uppercase();
+    let (name, arity) = match subcommand.as_str() {
+        "STREAM" => ("xinfo|stream", -3),
+        "GROUPS" => ("xinfo|groups", 3),
+        "CONSUMERS" => ("xinfo|consumers", 4),
+        _ => return Err(RedisError::UnknownSubcommand { command: "XINFO".to_owned(), subcommand: lossy(&args[0]).into_owned() }),
+    };
+    check_arity(name, arity, args)?;
+    let key = &args[1];
+    let full = match &args[2..] {
+        [] => None,
+        [option] if is_keyword(option, "FULL") => Some(10),
+        [option, count_option, count] if is_keyword(option, "FULL") && is_keyword(count_option, "COUNT") => {
+            // Like in Redis, a count of 0 lists everything.
+            Some(parse_integer(count)?.max(0) as usize)
+        }
+        _ if subcommand == "STREAM" => return Err(RedisError::Syntax),
+        _ => None,
+    };
+    let full = full.map(|count| if count == 0 { usize::MAX } else { count });
+    let now = now_ms();
+
+    with_store(|database| {
+        let stream = database.get_stream(key)?.ok_or(RedisError::NoSuchKey)?;
+        match subcommand.as_str() {
+            "STREAM" => {
+                // There is no radix tree above the nodes, so they are reported as both its keys and its nodes.
+                let mut fields = vec![
+                    ("length", RespValue::Integer(stream.len() as i64)),
+                    ("radix-tree-keys", RespValue::Integer(stream.node_count() as i64)),
+                    ("radix-tree-nodes", RespValue::Integer(stream.node_count() as i64)),
+                    ("last-generated-id", id_reply(stream.last_id())),
+                    ("max-deleted-entry-id", id_reply(stream.max_deleted_id())),
+                    ("entries-added", RespValue::Integer(stream.entries_added() as i64)),
+                    ("recorded-first-entry-id", id_reply(stream.first_entry().map_or(StreamId::MIN, |(id, _)| id))),
+                ];
+                match full {
+                    Some(count) => {
+                        let entries = stream.range(StreamId::MIN, StreamId::MAX, false).take(count);
+                        let entries = entries.map(|(id, fields)| entry_reply(id, fields)).collect();
+                        fields.push(("entries", RespValue::Array(entries)));
+                        fields.push(("groups", full_groups_reply(stream, count)));
+                    }
+                    None => {
+                        let entry = |entry: Option<(StreamId, &Fields)>| {
+                            entry.map_or(RespValue::NullBulkString, |(id, fields)| entry_reply(id, fields))
+                        };
+                        fields.push(("groups", RespValue::Integer(stream.groups().count() as i64)));
+                        fields.push(("first-entry", entry(stream.first_entry())));
+                        fields.push(("last-entry", entry(stream.last_entry())));
+                    }
+                }
+                Ok(fields_reply(fields))
+            }
+            "GROUPS" => {
+                let groups = stream.groups().map(|(name, group)| {
+                    fields_reply(vec![
+                        ("name", RespValue::bulk_string(name)),
+                        ("consumers", RespValue::Integer(group.consumers.len() as i64)),
+                        ("pending", RespValue::Integer(group.pending.len() as i64)),
+                        ("last-delivered-id", id_reply(group.last_delivered_id)),
+                        ("entries-read", optional_integer(group.entries_read)),
+                        ("lag", optional_integer(stream.lag(group))),
+                    ])
+                });
+                Ok(RespValue::Array(groups.collect()))
+            }
+            _ => {
+                let group = stream.group(&args[2]).ok_or_else(|| {
+                    RedisError::NoGroup(format!("No such consumer group '{}' for key name '{}'", lossy(&args[2]), lossy(key)))
+                })?;
+                let consumers = group.consumers.iter().map(|(name, consumer)| {
+                    let inactive = consumer.active_time.map_or(-1, |time| now.saturating_sub(time) as i64);
+                    fields_reply(vec![
+                        ("name", RespValue::bulk_string(name)),
+                        ("pending", RespValue::Integer(consumer.pending.len() as i64)),
+                        ("idle", RespValue::Integer(now.saturating_sub(consumer.seen_time) as i64)),
+                        ("inactive", RespValue::Integer(inactive)),
+                    ])
+                });
+                Ok(RespValue::Array(consumers.collect()))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_helpers::args;
+
+    fn entry_ids(reply: RespValue) -> Vec<String> {
+        let RespValue::Array(entries) = reply else { panic!("Entries are an array") };
+        entries
+            .into_iter()
+            .map(|entry| match entry {
+                RespValue::Array(entry) => match &entry[0] {
+                    RespValue::BulkString(id) => String::from_utf8(id.clone()).unwrap(),
+                    _ => panic!("IDs are bulk strings"),
+                },
+                _ => panic!("Entries are arrays"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_xadd_ids() {
+        let add = |id: &str| xadd_execute(&args(&["test_xadd_ids", id, "f", "v"]));
+        assert_eq!(add("5-3"), Ok(RespValue::bulk_string("5-3")));
+        assert_eq!(add("5-*"), Ok(RespValue::bulk_string("5-4")));
+        assert_eq!(add("5-4"), Err(id_too_small()));
+        assert_eq!(add("4-*"), Err(id_too_small()));
+        assert_eq!(add("0-0"), Err(RedisError::Other("The ID specified in XADD must be greater than 0-0".to_owned())));
+        assert_eq!(add("banana"), Err(invalid_id()));
+        assert_eq!(xadd_execute(&args(&["test_xadd_ids", "*", "f"])), Err(RedisError::WrongArity("xadd".to_owned())));
+        let reply = xadd_execute(&args(&["test_xadd_missing", "NOMKSTREAM", "*", "f", "v"]));
+        assert_eq!(reply, Ok(RespValue::NullBulkString));
+    }
+
+    #[test]
+    fn test_xrange_bounds() {
+        for id in ["1-0", "1-1", "2-0", "3-0"] {
+            xadd_execute(&args(&["test_xrange", id, "f", "v"])).unwrap();
+        }
+        let range = |start: &str, end: &str| entry_ids(xrange_execute(&args(&["test_xrange", start, end])).unwrap());
+        assert_eq!(range("-", "+"), ["1-0", "1-1", "2-0", "3-0"]);
+        assert_eq!(range("(1-0", "2"), ["1-1", "2-0"]);
+        assert_eq!(range("1", "1"), ["1-0", "1-1"]);
+        assert_eq!(range("2", "(3-0"), ["2-0"]);
+        let reversed = xrevrange_execute(&args(&["test_xrange", "+", "-", "COUNT", "2"])).unwrap();
+        assert_eq!(entry_ids(reversed), ["3-0", "2-0"]);
+    }
+
+    #[test]
+    fn test_xadd_trims() {
+        for _ in 0..10 {
+            xadd_execute(&args(&["test_xadd_trim", "MAXLEN", "3", "*", "f", "v"])).unwrap();
+        }
+        assert_eq!(xlen_execute(&args(&["test_xadd_trim"])), Ok(RespValue::Integer(3)));
+        assert_eq!(
+            xtrim_execute(&args(&["test_xadd_trim", "MAXLEN", "1", "LIMIT", "10"])),
+            Err(RedisError::Other("syntax error, LIMIT cannot be used without the special ~ option".to_owned()))
+        );
+        assert_eq!(xtrim_execute(&args(&["test_xadd_trim", "MAXLEN", "=", "1"])), Ok(RespValue::Integer(2)));
+    }
+
+    #[test]
+    fn test_xread_blocks_until_xadd() {
+        xadd_execute(&args(&["test_xread", "1-1", "f", "v"])).unwrap();
+        let blocked = std::thread::spawn(|| xread_execute(&args(&["BLOCK", "0", "STREAMS", "test_xread", "$"])));
+        std::thread::sleep(Duration::from_millis(50));
+        xadd_execute(&args(&["test_xread", "2-1", "f", "v"])).unwrap();
+
+        let RespValue::Array(streams) = blocked.join().unwrap().unwrap() else { panic!("XREAD replies with an array") };
+        let RespValue::Array(stream) = &streams[0] else { panic!("Each stream is an array") };
+        assert_eq!(stream[0], RespValue::bulk_string("test_xread"));
+        assert_eq!(entry_ids(stream[1].clone()), ["2-1"]);
+
+        let reply = xread_execute(&args(&["BLOCK", "50", "STREAMS", "test_xread", "$"]));
+        assert_eq!(reply, Ok(RespValue::NullArray));
+    }
+
+    #[test]
+    fn test_consumer_group_delivery() {
+        xgroup_execute(&args(&["CREATE", "test_group", "workers", "0", "MKSTREAM"])).unwrap();
+        for id in ["1-0", "2-0", "3-0"] {
+            xadd_execute(&args(&["test_group", id, "f", "v"])).unwrap();
+        }
+        let read = |consumer: &str, id: &str| {
+            let reply = xreadgroup_execute(&args(&["GROUP", "workers", consumer, "COUNT", "2", "STREAMS", "test_group", id]));
+            let RespValue::Array(streams) = reply.unwrap() else { return Vec::new() };
+            let RespValue::Array(stream) = &streams[0] else { panic!("Each stream is an array") };
+            entry_ids(stream[1].clone())
+        };
+        assert_eq!(read("alice", ">"), ["1-0", "2-0"]);
+        assert_eq!(read("bob", ">"), ["3-0"]);
+        assert!(read("bob", ">").is_empty());
+        assert_eq!(xack_execute(&args(&["test_group", "workers", "1-0", "1-0"])), Ok(RespValue::Integer(1)));
+        assert_eq!(read("alice", "0"), ["2-0"]);
+
+        let pending = xpending_execute(&args(&["test_group", "workers", "-", "+", "10"])).unwrap();
+        let RespValue::Array(pending) = pending else { panic!("XPENDING replies with an array") };
+        let counts = pending
+            .iter()
+            .map(|entry| match entry {
+                RespValue::Array(entry) => (entry[1].clone(), entry[3].clone()),
+                _ => panic!("Pending entries are arrays"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [
+                (RespValue::bulk_string("alice"), RespValue::Integer(2)),
+                (RespValue::bulk_string("bob"), RespValue::Integer(1))
+            ]
+        );
+        let reply = xgroup_execute(&args(&["CREATE", "test_group", "workers", "$"]));
+        assert_eq!(reply, Err(RedisError::BusyGroup));
+    }
+
+    #[test]
+    fn test_autoclaim_drops_deleted_entries() {
+        xgroup_execute(&args(&["CREATE", "test_autoclaim", "workers", "0", "MKSTREAM"])).unwrap();
+        for id in ["1-0", "2-0", "3-0"] {
+            xadd_execute(&args(&["test_autoclaim", id, "f", "v"])).unwrap();
+        }
+        xreadgroup_execute(&args(&["GROUP", "workers", "alice", "STREAMS", "test_autoclaim", ">"])).unwrap();
+        xdel_execute(&args(&["test_autoclaim", "1-0"])).unwrap();
+
+        let reply = xautoclaim_execute(&args(&["test_autoclaim", "workers", "bob", "0", "-", "COUNT", "1", "JUSTID"]));
+        let expected = RespValue::Array(vec![
+            RespValue::bulk_string("3-0"),
+            RespValue::Array(vec![RespValue::bulk_string("2-0")]),
+            RespValue::Array(vec![RespValue::bulk_string("1-0")]),
+        ]);
+        assert_eq!(reply, Ok(expected));
+        let reply = xautoclaim_execute(&args(&["test_autoclaim", "workers", "bob", "0", "3-0", "JUSTID"])).unwrap();
+        let RespValue::Array(reply) = reply else { panic!("XAUTOCLAIM replies with an array") };
+        assert_eq!(reply[0], RespValue::bulk_string("0-0"));
+        let expected = RespValue::Array(vec![
+            RespValue::Integer(2),
+            RespValue::bulk_string("2-0"),
+            RespValue::bulk_string("3-0"),
+            RespValue::Array(vec![RespValue::Array(vec![RespValue::bulk_string("bob"), RespValue::bulk_string("2")])]),
+        ]);
+        assert_eq!(xpending_execute(&args(&["test_autoclaim", "workers"])), Ok(expected));
+    }
+}
@@ -0,0 +1,318 @@
+use super::expire::{expire_option, parse_option_expire_time};
+use super::{format_float, is_keyword, parse_float, parse_integer, CommandResult};
+use crate::config::global_config_get;
+use crate::error::RedisError;
+use crate::serialization::RespValue;
+use crate::store::{global_store_get, global_store_set, global_store_update, with_store, SetCondition, SetOptions, Ttl};
+
+/// Turn an optional value into a bulk string reply, or the null bulk string if missing.
+fn bulk_or_null(value: Option<Vec<u8>>) -> RespValue {
+    value.map_or(RespValue::NullBulkString, RespValue::BulkString)
+}
+
+/// Largest string a client may build up, set by `proto-max-bulk-len`.
+fn max_string_length() -> usize {
+    global_config_get("proto-max-bulk-len").and_then(|value| value.parse().ok()).unwrap_or(512 * 1024 * 1024)
+}
+
+/// Reject writes that would grow a string past the maximum size.
+fn check_string_length(length: usize) -> Result<(), RedisError> {
+    if length > max_string_length() {
+        return Err(RedisError::Other("string exceeds maximum allowed size (proto-max-bulk-len)".to_owned()));
+    }
+    Ok(())
+}
+
+/// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+pub fn set_execute(args: &[Vec<u8>]) -> CommandResult {
+    let mut options = SetOptions { condition: SetCondition::Always, ttl: Ttl::Persist, get: false };
+    // Repeating an option is fine, combining mutually exclusive ones is a syntax error.
+    let mut condition_option: Option<&[u8]> = None;
+    let mut ttl_option: Option<&[u8]> = None;
+    let mut index = 2;
+    while index < args.len() {
+        let option = args[index].as_slice();
+        let conflicts = |given: Option<&[u8]>| given.is_some_and(|given| !given.eq_ignore_ascii_case(option));
+        if is_keyword(option, "NX") && !conflicts(condition_option) {
+            options.condition = SetCondition::IfMissing;
+            condition_option = Some(option);
+        } else if is_keyword(option, "XX") && !conflicts(condition_option) {
+            options.condition = SetCondition::IfExists;
+            condition_option = Some(option);
+        } else if is_keyword(option, "GET") {
+            options.get = true;
+        } else if is_keyword(option, "KEEPTTL") && !conflicts(ttl_option) {
+            options.ttl = Ttl::Keep;
+            ttl_option = Some(option);
+        } else if let Some((unit, absolute)) = expire_option(option).filter(|_| !conflicts(ttl_option)) {
+            index += 1;
+            let value = args.get(index).ok_or(RedisError::Syntax)?;
+            options.ttl = Ttl::ExpireAt(parse_option_expire_time(value, unit, absolute, "set")?);
+            ttl_option = Some(option);
+        } else {
+            return Err(RedisError::Syntax);
+        }
+        index += 1;
+    }
+
+    let get = options.get;
+    let (written, previous) = global_store_set(args[0].clone(), args[1].clone(), options)?;
+    Ok(match (get, written) {
+        (true, _) => bulk_or_null(previous),
+        (false, true) => RespValue::ok(),
+        (false, false) => RespValue::NullBulkString,
+    })
+}
+
+/// GET key
+pub fn get_execute(args: &[Vec<u8>]) -> CommandResult {
+    Ok(bulk_or_null(global_store_get(&args[0])?))
+}
+
+/// SETNX key value
+pub fn setnx_execute(args: &[Vec<u8>]) -> CommandResult {
+    let options = SetOptions { condition: SetCondition::IfMissing, ttl: Ttl::Persist, get: false };
+    let (written, _) = global_store_set(args[0].clone(), args[1].clone(), options)?;
+    Ok(RespValue::Integer(written as i64))
+}
+
+/// MGET key [key ...]
+pub fn mget_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        // Keys holding other types read as missing rather than failing the whole command.
+        let values = args.iter()
+            .map(|key| bulk_or_null(database.get_string(key).ok().flatten().cloned()))
+            .collect();
+        Ok(RespValue::Array(values))
+    })
+}
+
+/// Split the arguments of MSET and MSETNX into key/value pairs.
+fn key_value_pairs<'a>(args: &'a [Vec<u8>], command: &str) -> Result<std::slice::ChunksExact<'a, Vec<u8>>, RedisError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(RedisError::WrongArity(command.to_owned()));
+    }
+    Ok(args.chunks_exact(2))
+}
+
+/// MSET key value [key value ...]
+pub fn mset_execute(args: &[Vec<u8>]) -> CommandResult {
+    let pairs = key_value_pairs(args, "mset")?;
+    with_store(|database| {
+        for pair in pairs {
+            database.set(pair[0].clone(), pair[1].clone(), Ttl::Persist);
+        }
+    });
+    Ok(RespValue::ok())
+}
+
+/// MSETNX key value [key value ...]
+/// Sets nothing at all if any of the keys already exists.
+pub fn msetnx_execute(args: &[Vec<u8>]) -> CommandResult {
+    let pairs = key_value_pairs(args, "msetnx")?;
+    with_store(|database| {
+        if args.iter().step_by(2).any(|key| database.get(key).is_some()) {
+            return Ok(RespValue::Integer(0));
+        }
+        for pair in pairs {
+            database.set(pair[0].clone(), pair[1].clone(), Ttl::Persist);
+        }
+        Ok(RespValue::Integer(1))
+    })
+}
+
+/// APPEND key value
+pub fn append_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let Some(string) = database.get_string_mut(&args[0])? else {
+            database.set(args[0].clone(), args[1].clone(), Ttl::Persist);
+            return Ok(RespValue::Integer(args[1].len() as i64));
+        };
+        check_string_length(string.len() + args[1].len())?;
+        string.extend_from_slice(&args[1]);
+        Ok(RespValue::Integer(string.len() as i64))
+    })
+}
+
+/// STRLEN key
+pub fn strlen_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let length = database.get_string(&args[0])?.map_or(0, |string| string.len());
+        Ok(RespValue::Integer(length as i64))
+    })
+}
+
+/// GETRANGE key start end
+/// Negative offsets count from the end of the string and out of range offsets are clamped.
+pub fn getrange_execute(args: &[Vec<u8>]) -> CommandResult {
+    let start = parse_integer(&args[1])?;
+    let end = parse_integer(&args[2])?;
+    with_store(|database| {
+        let Some(string) = database.get_string(&args[0])? else {
+            return Ok(RespValue::bulk_string(""));
+        };
+        let length = string.len() as i64;
+        if length == 0 || (start < 0 && end < 0 && start > end) {
+            return Ok(RespValue::bulk_string(""));
+        }
+        let start = if start < 0 { (length + start).max(0) } else { start };
+        let end = if end < 0 { (length + end).max(0) } else { end.min(length - 1) };
+        if start > end {
+            return Ok(RespValue::bulk_string(""));
+        }
+        Ok(RespValue::bulk_string(&string[start as usize..=end as usize]))
+    })
+}
+
+/// SETRANGE key offset value
+/// The string is padded with zero bytes if the offset lies past its end.
+pub fn setrange_execute(args: &[Vec<u8>]) -> CommandResult {
+    let offset = parse_integer(&args[1])?;
+    if offset < 0 {
+        return Err(RedisError::Other("offset is out of range".to_owned()));
+    }
+    let offset = offset as usize;
+    let value = &args[2];
+    with_store(|database| {
+        let Some(string) = database.get_string_mut(&args[0])? else {
+            // An empty value does not create the key.
+            if value.is_empty() {
+                return Ok(RespValue::Integer(0));
+            }
+            check_string_length(offset + value.len())?;
+            let mut string = vec![0u8; offset];
+            string.extend_from_slice(value);
+            let length = string.len();
+            database.set(args[0].clone(), string, Ttl::Persist);
+            return Ok(RespValue::Integer(length as i64));
+        };
+        if !value.is_empty() {
+            check_string_length(offset + value.len())?;
+            if string.len() < offset + value.len() {
+                string.resize(offset + value.len(), 0);
+            }
+            string[offset..offset + value.len()].copy_from_slice(value);
+        }
+        Ok(RespValue::Integer(string.len() as i64))
+    })
+}
+
+/// GETDEL key
+pub fn getdel_execute(args: &[Vec<u8>]) -> CommandResult {
+    with_store(|database| {
+        let value = database.get_string(&args[0])?.cloned();
+        if value.is_some() {
+            database.remove(&args[0]);
+        }
+        Ok(bulk_or_null(value))
+    })
+}
+
+/// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | PERSIST]
+pub fn getex_execute(args: &[Vec<u8>]) -> CommandResult {
+    let expires_at = match &args[1..] {
+        [] => None,
+        [option] if is_keyword(option, "PERSIST") => Some(None),
+        [option, value] => match expire_option(option) {
+            Some((unit, absolute)) => Some(Some(parse_option_expire_time(value, unit, absolute, "getex")?)),
+            None => return Err(RedisError::Syntax),
+        },
+        _ => return Err(RedisError::Syntax),
+    };
+    with_store(|database| {
+        let value = database.get_string(&args[0])?.cloned();
+        if let (Some(_), Some(expires_at)) = (&value, expires_at) {
+            database.set_expiry(&args[0], expires_at);
+        }
+        Ok(bulk_or_null(value))
+    })
+}
+
+/// Shared implementation of INCR, DECR, INCRBY and DECRBY. A missing key counts as 0.
+fn incr_by(key: &[u8], increment: i64) -> CommandResult {
+    global_store_update(key, |value| {
+        let current = value.map_or(Ok(0), parse_integer)?;
+        let result = current.checked_add(increment).ok_or(RedisError::Overflow)?;
+        Ok((result.to_string().into_bytes(), RespValue::Integer(result)))
+    })
+}
+
+/// INCR key
+pub fn incr_execute(args: &[Vec<u8>]) -> CommandResult {
+    incr_by(&args[0], 1)
+}
+
+/// DECR key
+pub fn decr_execute(args: &[Vec<u8>]) -> CommandResult {
+    incr_by(&args[0], -1)
+}
+
+/// INCRBY key increment
+pub fn incrby_execute(args: &[Vec<u8>]) -> CommandResult {
+    incr_by(&args[0], parse_integer(&args[1])?)
+}
+
+/// DECRBY key decrement
+pub fn decrby_execute(args: &[Vec<u8>]) -> CommandResult {
+    let decrement = parse_integer(&args[1])?;
+    let increment = decrement.checked_neg().ok_or_else(|| RedisError::Other("decrement would overflow".to_owned()))?;
+    incr_by(&args[0], increment)
+}
+
+/// INCRBYFLOAT key increment
+pub fn incrbyfloat_execute(args: &[Vec<u8>]) -> CommandResult {
+    let increment = parse_float(&args[1])?;
+    global_store_update(&args[0], |value| {
+        let current = value.map_or(Ok(0.0), parse_float)?;
+        let result = current + increment;
+        if !result.is_finite() {
+            return Err(RedisError::Other("increment would produce NaN or Infinity".to_owned()));
+        }
+        let formatted = format_float(result).into_bytes();
+        Ok((formatted.clone(), RespValue::BulkString(formatted)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_helpers::args;
+    use crate::config::global_config_set;
+    use rstest::*;
+
+    #[rstest]
+    #[case("0", "3", "This")]
+    #[case("-3", "-1", "ing")]
+    #[case("0", "-1", "This is a string")]
+    #[case("10", "100", "string")]
+    #[case("-100", "3", "This")]
+    #[case("5", "3", "")]
+    #[case("-1", "-5", "")]
+    #[case("100", "200", "")]
+    fn test_getrange(#[case] start: &str, #[case] end: &str, #[case] expected: &str) {
+        mset_execute(&args(&["test_getrange", "This is a string"])).unwrap();
+        let reply = getrange_execute(&args(&["test_getrange", start, end])).unwrap();
+        assert_eq!(reply, RespValue::bulk_string(expected));
+    }
+
+    #[test]
+    fn test_setrange_pads_with_zero_bytes() {
+        assert_eq!(setrange_execute(&args(&["test_setrange", "3", "ab"])).unwrap(), RespValue::Integer(5));
+        assert_eq!(setrange_execute(&args(&["test_setrange", "1", "c"])).unwrap(), RespValue::Integer(5));
+        assert_eq!(get_execute(&args(&["test_setrange"])).unwrap(), RespValue::bulk_string(b"\0c\0ab"));
+        assert_eq!(setrange_execute(&args(&["test_setrange_empty", "3", ""])).unwrap(), RespValue::Integer(0));
+        assert_eq!(get_execute(&args(&["test_setrange_empty"])).unwrap(), RespValue::NullBulkString);
+    }
+
+    #[test]
+    fn test_string_length_follows_proto_max_bulk_len() {
+        let limit = |value: &str| global_config_set(&[("proto-max-bulk-len".to_owned(), value.to_owned())]).unwrap();
+        limit("1mb");
+        let reply = setrange_execute(&args(&["test_proto_max_bulk_len", "1048576", "a"]));
+        limit("512mb");
+        let too_long = RedisError::Other("string exceeds maximum allowed size (proto-max-bulk-len)".to_owned());
+        assert_eq!(reply, Err(too_long));
+        let reply = setrange_execute(&args(&["test_proto_max_bulk_len", "1048576", "a"]));
+        assert_eq!(reply, Ok(RespValue::Integer(1048577)));
+    }
+}
@@ -0,0 +1,720 @@
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::error::RedisError;
+use crate::glob::glob_match;
+use crate::maxmemory::{apply_lfu_decay_time, apply_lfu_log_factor, apply_maxmemory};
+use crate::store::MAX_DATABASES;
+
+lazy_static! {
+    /// Global configuration registry. Thread safe.
+    static ref CONFIG: Mutex<Registry> = {
+        let mut registry = Registry::default();
+        populate_config_defaults(&mut registry);
+        Mutex::new(registry)
+    };
+}
+
+/// Hook run when a parameter is given a new value, so the change takes effect right away.
+/// Receives the normalized value and returns the reason it could not be applied, if any.
+type ApplyHook = fn(&str) -> Result<(), String>;
+
+/// The kind of value a config parameter holds, deciding how a new value is validated.
+#[derive(Clone, Copy)]
+enum ConfigType {
+    /// `yes` or `no`.
+    Bool,
+    /// A signed integer within an inclusive range.
+    Integer { min: i64, max: i64 },
+    /// A byte count, optionally with a unit such as `100mb`. Stored as a plain number of bytes.
+    Memory,
+    /// One of a fixed set of lowercase words.
+    Enum(&'static [&'static str]),
+    /// Free form text.
+    String,
+    /// Space separated words, such as the addresses of `bind`. Written to the config file unquoted.
+    Words,
+}
+
+impl ConfigType {
+    /// Validate a value given to CONFIG SET and turn it into the form CONFIG GET replies with.
+    /// Errors hold the reason in the words Redis uses.
+    fn normalize(&self, value: &str) -> Result<String, String> {
+        match self {
+            ConfigType::Bool => match value.to_lowercase().as_str() {
+                "yes" => Ok("yes".to_owned()),
+                "no" => Ok("no".to_owned()),
+                _ => Err("argument must be 'yes' or 'no'".to_owned()),
+            },
+            ConfigType::Integer { min, max } => {
+                let number = value
+                    .parse::<i64>()
+                    .map_err(|_| "argument couldn't be parsed into an integer".to_owned())?;
+                if number < *min || number > *max {
+                    return Err(format!("argument must be between {} and {} inclusive", min, max));
+                }
+                Ok(number.to_string())
+            }
+            ConfigType::Memory => parse_memory(value)
+                .map(|bytes| bytes.to_string())
+                .ok_or_else(|| "argument must be a memory value".to_owned()),
+            ConfigType::Enum(allowed) => {
+                let lowercase = value.to_lowercase();
+                if allowed.contains(&lowercase.as_str()) {
+                    Ok(lowercase)
+                } else {
+                    Err(format!("argument(s) must be one of the following: {}", allowed.join(", ")))
+                }
+            }
+            ConfigType::String => Ok(value.to_owned()),
+            ConfigType::Words => Ok(value.split_whitespace().collect::<Vec<_>>().join(" ")),
+        }
+    }
+
+    /// Format a normalized value as the arguments of a config file directive.
+    fn format(&self, value: &str) -> String {
+        match self {
+            ConfigType::Memory => format_memory(value.parse().unwrap_or_default()),
+            ConfigType::String => quote(value),
+            ConfigType::Words if value.is_empty() => quote(value),
+            ConfigType::Words => value.split(' ').map(quote_if_needed).collect::<Vec<_>>().join(" "),
+            _ => value.to_owned(),
+        }
+    }
+}
+
+/// Parse a byte count with an optional unit like Redis does: `k`, `m` and `g` are powers of 1000
+/// while `kb`, `mb` and `gb` are powers of 1024. Units are case insensitive.
+pub fn parse_memory(value: &str) -> Option<u64> {
+    let lowercase = value.to_lowercase();
+    let digits_end = lowercase.find(|c: char| !c.is_ascii_digit()).unwrap_or(lowercase.len());
+    let (digits, unit) = lowercase.split_at(digits_end);
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Write a byte count with the largest unit that divides it, as Redis does when rewriting its config file.
+fn format_memory(bytes: u64) -> String {
+    const UNITS: [(u64, &str); 3] = [(1024 * 1024 * 1024, "gb"), (1024 * 1024, "mb"), (1024, "kb")];
+    UNITS
+        .iter()
+        .find(|(size, _)| bytes != 0 && bytes.is_multiple_of(*size))
+        .map(|(size, unit)| format!("{}{}", bytes / size, unit))
+        .unwrap_or_else(|| bytes.to_string())
+}
+
+/// Double quote a value so that reading it back from a config file gives the same bytes.
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for byte in value.bytes() {
+        match byte {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            byte if byte.is_ascii_graphic() || byte == b' ' => quoted.push(byte as char),
+            byte => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn quote_if_needed(word: &str) -> String {
+    if !word.is_empty() && word.bytes().all(|byte| byte.is_ascii_graphic() && byte != b'"' && byte != b'\'') {
+        word.to_owned()
+    } else {
+        quote(word)
+    }
+}
+
+/// The config file lines describing the current value of a parameter, used by CONFIG REWRITE.
+pub struct ConfigDirectives {
+    pub name: &'static str,
+    pub lines: Vec<String>,
+    pub is_default: bool,
+}
+
+/// A single config parameter and its current value.
+pub struct Parameter {
+    kind: ConfigType,
+    default: String,
+    value: String,
+    mutable: bool,
+    /// Repeated directives in a config file add up instead of replacing each other, like `save`.
+    repeatable: bool,
+    apply: Option<ApplyHook>,
+}
+
+impl Parameter {
+    /// Refuse changes through CONFIG SET, for settings that only make sense at startup.
+    fn immutable(&mut self) -> &mut Parameter {
+        self.mutable = false;
+        self
+    }
+
+    /// Let repeated config file directives append to the value rather than replace it.
+    fn repeatable(&mut self) -> &mut Parameter {
+        self.repeatable = true;
+        self
+    }
+
+    /// Run `hook` whenever the value is changed, by CONFIG SET or while loading the config file.
+    fn on_apply(&mut self, hook: ApplyHook) -> &mut Parameter {
+        self.apply = Some(hook);
+        self
+    }
+}
+
+/// Every config parameter the server knows, by name.
+/// Old names such as `slave-read-only` are aliases sharing the value of the current name.
+#[derive(Default)]
+pub struct Registry {
+    parameters: HashMap<&'static str, Parameter>,
+    aliases: HashMap<&'static str, &'static str>,
+}
+
+impl Registry {
+    fn register(&mut self, name: &'static str, kind: ConfigType, default: &str) -> &mut Parameter {
+        let value = kind
+            .normalize(default)
+            .unwrap_or_else(|reason| panic!("Invalid default for config '{}': {}", name, reason));
+        self.parameters.entry(name).or_insert(Parameter {
+            kind,
+            default: value.clone(),
+            value,
+            mutable: true,
+            repeatable: false,
+            apply: None,
+        })
+    }
+
+    fn boolean(&mut self, name: &'static str, default: &str) -> &mut Parameter {
+        self.register(name, ConfigType::Bool, default)
+    }
+
+    fn integer(&mut self, name: &'static str, default: &str, min: impl Into<i64>, max: impl Into<i64>) -> &mut Parameter {
+        self.register(name, ConfigType::Integer { min: min.into(), max: max.into() }, default)
+    }
+
+    fn memory(&mut self, name: &'static str, default: &str) -> &mut Parameter {
+        self.register(name, ConfigType::Memory, default)
+    }
+
+    fn enumeration(&mut self, name: &'static str, default: &str, allowed: &'static [&'static str]) -> &mut Parameter {
+        self.register(name, ConfigType::Enum(allowed), default)
+    }
+
+    fn string(&mut self, name: &'static str, default: &str) -> &mut Parameter {
+        self.register(name, ConfigType::String, default)
+    }
+
+    fn words(&mut self, name: &'static str, default: &str) -> &mut Parameter {
+        self.register(name, ConfigType::Words, default)
+    }
+
+    fn alias(&mut self, alias: &'static str, name: &'static str) {
+        self.aliases.insert(alias, name);
+    }
+
+    /// The current name of a parameter given any of its names, in any case.
+    fn resolve(&self, name: &str) -> Option<&'static str> {
+        let lowercase = name.to_lowercase();
+        let lowercase = self.aliases.get(lowercase.as_str()).copied().unwrap_or(lowercase.as_str());
+        self.parameters.get_key_value(lowercase).map(|(name, _)| *name)
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        self.resolve(name).map(|name| self.parameters[name].value.clone())
+    }
+
+    /// Config file directives holding the current value of every parameter, ordered by name.
+    fn directives(&self) -> Vec<ConfigDirectives> {
+        let mut names = self.parameters.keys().copied().collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+            .into_iter()
+            .map(|name| {
+                let parameter = &self.parameters[name];
+                let words = parameter.value.split(' ').collect::<Vec<_>>();
+                let lines = if parameter.repeatable && !parameter.value.is_empty() {
+                    // One directive per pair of words, e.g. `save 900 1`.
+                    words.chunks(2).map(|pair| format!("{} {}", name, parameter.kind.format(&pair.join(" ")))).collect()
+                } else {
+                    vec![format!("{} {}", name, parameter.kind.format(&parameter.value))]
+                };
+                ConfigDirectives { name, lines, is_default: parameter.value == parameter.default }
+            })
+            .collect()
+    }
+
+    /// Names, aliases included, of the parameters matching a glob pattern.
+    fn matching(&self, pattern: &str) -> Vec<String> {
+        self.parameters
+            .keys()
+            .chain(self.aliases.keys())
+            .filter(|name| glob_match(pattern.as_bytes(), name.as_bytes(), true))
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Set several parameters at once. Every pair is validated before anything changes, and if an
+    /// apply hook fails the parameters changed so far are restored, so either all pairs take effect or none.
+    fn set(&mut self, pairs: &[(String, String)]) -> Result<(), RedisError> {
+        let mut changes: Vec<(&str, &'static str, String)> = Vec::new();
+        for (name, value) in pairs {
+            let canonical = self.resolve(name).ok_or_else(|| {
+                RedisError::Other(format!("Unknown option or number of arguments for CONFIG SET - '{}'", name))
+            })?;
+            let parameter = &self.parameters[canonical];
+            if !parameter.mutable {
+                return Err(config_set_failed(name, "can't set immutable config"));
+            }
+            if changes.iter().any(|(_, other, _)| *other == canonical) {
+                return Err(config_set_failed(name, "duplicate parameter"));
+            }
+            let value = parameter.kind.normalize(value).map_err(|reason| config_set_failed(name, &reason))?;
+            changes.push((name, canonical, value));
+        }
+
+        let mut previous_values: Vec<(&'static str, String)> = Vec::new();
+        for (name, canonical, value) in changes {
+            let parameter = self.parameters.get_mut(canonical).unwrap();
+            if let Err(reason) = parameter.apply.map_or(Ok(()), |apply| apply(&value)) {
+                self.restore(previous_values);
+                return Err(config_set_failed(name, &reason));
+            }
+            previous_values.push((canonical, std::mem::replace(&mut parameter.value, value)));
+        }
+        Ok(())
+    }
+
+    /// Apply the directives of a config file at startup, each one being a parameter name followed by its values.
+    /// Unlike CONFIG SET immutable parameters can be set. The first directive for a repeatable parameter
+    /// replaces its default and the following ones append to it, while an empty value clears it.
+    /// Errors hold the index of the offending directive and the reason.
+    fn load(&mut self, directives: &[Vec<String>]) -> Result<(), (usize, String)> {
+        let mut seen: HashSet<&'static str> = HashSet::new();
+        for (index, directive) in directives.iter().enumerate() {
+            let canonical = match directive.split_first() {
+                Some((name, values)) if !values.is_empty() => self.resolve(name),
+                _ => None,
+            };
+            let canonical =
+                canonical.ok_or_else(|| (index, "Bad directive or wrong number of arguments".to_owned()))?;
+            let parameter = self.parameters.get_mut(canonical).unwrap();
+            let mut value = directive[1..].join(" ");
+            let first = seen.insert(canonical);
+            if parameter.repeatable && !first && !value.is_empty() && !parameter.value.is_empty() {
+                value = format!("{} {}", parameter.value, value);
+            }
+            let value = parameter.kind.normalize(&value).map_err(|reason| (index, reason))?;
+            if let Some(apply) = parameter.apply {
+                apply(&value).map_err(|reason| (index, reason))?;
+            }
+            parameter.value = value;
+        }
+        Ok(())
+    }
+
+    /// Undo changes made by a failed CONFIG SET, most recent first.
+    fn restore(&mut self, previous_values: Vec<(&'static str, String)>) {
+        for (name, value) in previous_values.into_iter().rev() {
+            let parameter = self.parameters.get_mut(name).unwrap();
+            if let Some(Err(reason)) = parameter.apply.map(|apply| apply(&value)) {
+                log::warn!("Could not restore config '{}' to '{}': {}", name, value, reason);
+            }
+            parameter.value = value;
+        }
+    }
+}
+
+fn config_set_failed(argument: &str, reason: &str) -> RedisError {
+    RedisError::ConfigSetFailed { argument: argument.to_owned(), reason: reason.to_owned() }
+}
+
+/// Current value of a config parameter, looked up by any of its names.
+pub fn global_config_get(name: &str) -> Option<String> {
+    CONFIG.lock().unwrap().get(name)
+}
+
+/// The current name of a parameter given any of its names.
+pub fn global_config_resolve(name: &str) -> Option<&'static str> {
+    CONFIG.lock().unwrap().resolve(name)
+}
+
+/// Config file directives for every parameter, see `Registry::directives`.
+pub fn global_config_directives() -> Vec<ConfigDirectives> {
+    CONFIG.lock().unwrap().directives()
+}
+
+/// Names of all config parameters matching a Redis glob pattern, ignoring case like Redis does.
+pub fn global_config_get_keys(pattern: &str) -> Vec<String> {
+    CONFIG.lock().unwrap().matching(pattern)
+}
+
+/// Atomically set several config parameters, as CONFIG SET does.
+pub fn global_config_set(pairs: &[(String, String)]) -> Result<(), RedisError> {
+    CONFIG.lock().unwrap().set(pairs)
+}
+
+/// Apply config file directives at startup, see `Registry::load`.
+pub fn global_config_load(directives: &[Vec<String>]) -> Result<(), (usize, String)> {
+    CONFIG.lock().unwrap().load(directives)
+}
+
+/// Map the Redis log levels onto the ones of the `log` crate.
+pub fn apply_loglevel(value: &str) -> Result<(), String> {
+    let level = match value {
+        "debug" => log::LevelFilter::Trace,
+        "verbose" => log::LevelFilter::Debug,
+        "notice" => log::LevelFilter::Info,
+        "warning" => log::LevelFilter::Warn,
+        _ => log::LevelFilter::Off,
+    };
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Change the working directory, where files such as the RDB dump are written.
+fn apply_dir(value: &str) -> Result<(), String> {
+    std::env::set_current_dir(value).map_err(|error| {
+        // Drop the "(os error N)" suffix so the reason reads like the one Redis gives.
+        let message = error.to_string();
+        message.split(" (os error").next().unwrap_or_default().to_owned()
+    })
+}
+
+/// Register every parameter with the default a vanilla Redis server would respond with.
+fn populate_config_defaults(r: &mut Registry) {
+    r.boolean("replica-read-only", "yes");
+    r.memory("stream-node-max-bytes", "4096");
+    r.integer("auto-aof-rewrite-percentage", "100", 0, i32::MAX);
+    r.string("bind-source-addr", "");
+    r.string("tls-client-key-file", "");
+    r.string("notify-keyspace-events", "");
+    r.integer("set-max-intset-entries", "512", 0, i64::MAX);
+    r.integer("slowlog-log-slower-than", "10000", -1, i64::MAX);
+    r.alias("cluster-slave-no-failover", "cluster-replica-no-failover");
+    r.enumeration("syslog-facility", "local0", &["user", "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7"]).immutable();
+    r.string("tls-cert-file", "");
+    r.string("masterauth", "");
+    r.enumeration("repl-diskless-load", "disabled", &["disabled", "on-empty-db", "swapdb"]);
+    r.enumeration("loglevel", "notice", &["debug", "verbose", "notice", "warning", "nothing"]).on_apply(apply_loglevel);
+    r.integer("repl-ping-replica-period", "10", 1, i32::MAX);
+    r.string("tls-ca-cert-dir", "");
+    r.integer("cluster-migration-barrier", "1", 0, i32::MAX);
+    r.string("proc-title-template", "{title} {listen-addr} {server-mode}");
+    r.string("cluster-config-file", "nodes.conf").immutable();
+    r.string("tls-ciphers", "");
+    r.string("replica-announce-ip", "");
+    r.boolean("rdbcompression", "yes");
+    r.integer("cluster-announce-tls-port", "0", 0, 65535);
+    r.string("tls-dh-params-file", "");
+    r.integer("maxclients", "10000", 1, i32::MAX);
+    r.boolean("aof-load-truncated", "yes");
+    r.memory("hll-sparse-max-bytes", "3000");
+    r.integer("cluster-link-sendbuf-limit", "0", 0, i32::MAX);
+    r.memory("maxmemory", "0").on_apply(apply_maxmemory);
+    r.words("bind", "* -::*").immutable();
+    r.boolean("aof-timestamp-enabled", "no");
+    r.alias("hash-max-ziplist-entries", "hash-max-listpack-entries");
+    r.enumeration("supervised", "no", &["upstart", "systemd", "auto", "no"]).immutable();
+    r.integer("active-defrag-cycle-max", "25", 1, 99);
+    r.string("tls-client-key-file-pass", "");
+    r.string("masteruser", "");
+    r.alias("zset-max-ziplist-value", "zset-max-listpack-value");
+    r.alias("list-max-ziplist-size", "list-max-listpack-size");
+    r.alias("repl-ping-slave-period", "repl-ping-replica-period");
+    r.alias("min-slaves-max-lag", "min-replicas-max-lag");
+    r.words("oom-score-adj-values", "0 200 800");
+    r.boolean("tls-session-caching", "yes");
+    r.integer("lfu-decay-time", "1", 0, i32::MAX).on_apply(apply_lfu_decay_time);
+    r.integer("timeout", "0", 0, i32::MAX);
+    r.integer("min-replicas-to-write", "0", 0, i32::MAX);
+    r.integer("maxmemory-clients", "0", 0, i32::MAX);
+    r.string("cluster-announce-human-nodename", "");
+    r.enumeration("cluster-preferred-endpoint-type", "ip", &["ip", "hostname", "unknown-endpoint"]);
+    r.memory("active-defrag-ignore-bytes", "104857600");
+    r.string("pidfile", "").immutable();
+    r.integer("port", "6379", 0, 65535).immutable();
+    r.alias("slave-read-only", "replica-read-only");
+    r.enumeration("maxmemory-policy", "noeviction", &["volatile-lru", "volatile-lfu", "volatile-random", "volatile-ttl", "allkeys-lru", "allkeys-lfu", "allkeys-random", "noeviction"]);
+    r.integer("tls-port", "0", 0, 65535);
+    r.memory("set-max-listpack-value", "64");
+    r.enumeration("propagation-error-behavior", "ignore", &["ignore", "panic", "panic-on-replicas"]);
+    r.memory("client-query-buffer-limit", "1073741824");
+    r.integer("active-defrag-max-scan-fields", "1000", 0, i32::MAX);
+    r.string("tls-protocols", "");
+    r.enumeration("oom-score-adj", "no", &["no", "yes", "relative", "absolute"]);
+    r.memory("proto-max-bulk-len", "536870912");
+    r.boolean("aof-use-rdb-preamble", "yes");
+    r.string("aof_rewrite_cpulist", "").immutable();
+    r.words("save", "3600 1 300 100 60 10000").repeatable();
+    r.integer("list-compress-depth", "0", 0, i32::MAX);
+    r.integer("databases", "16", 1, MAX_DATABASES).immutable();
+    r.integer("cluster-node-timeout", "15000", 0, i64::MAX);
+    r.integer("busy-reply-threshold", "5000", 0, i64::MAX);
+    r.integer("maxmemory-eviction-tenacity", "10", 0, 100);
+    r.boolean("rdbchecksum", "yes");
+    r.integer("cluster-port", "0", 0, 65535).immutable();
+    r.boolean("repl-disable-tcp-nodelay", "no");
+    r.boolean("cluster-replica-no-failover", "no");
+    r.string("ignore-warnings", "");
+    r.boolean("daemonize", "no").immutable();
+    r.string("appenddirname", "appendonlydir").immutable();
+    r.boolean("activerehashing", "yes");
+    r.integer("lfu-log-factor", "10", 0, i32::MAX).on_apply(apply_lfu_log_factor);
+    r.integer("list-max-listpack-size", "-2", i32::MIN, i32::MAX);
+    r.alias("cluster-slave-validity-factor", "cluster-replica-validity-factor");
+    r.boolean("io-threads-do-reads", "no").immutable();
+    r.string("tls-key-file-pass", "");
+    r.memory("auto-aof-rewrite-min-size", "67108864");
+    r.boolean("dynamic-hz", "yes");
+    r.boolean("set-proc-title", "yes").immutable();
+    r.integer("unixsocketperm", "0", 0, i32::MAX).immutable();
+    r.string("dbfilename", "dump.rdb");
+    r.integer("cluster-replica-validity-factor", "10", 0, i32::MAX);
+    r.boolean("cluster-allow-reads-when-down", "no");
+    r.integer("active-expire-effort", "1", 1, 10);
+    r.boolean("cluster-require-full-coverage", "yes");
+    r.integer("latency-monitor-threshold", "0", 0, i64::MAX);
+    r.enumeration("tls-auth-clients", "yes", &["no", "yes", "optional"]);
+    r.string("tls-client-cert-file", "");
+    r.boolean("replica-lazy-flush", "no");
+    r.integer("replica-priority", "100", 0, i32::MAX);
+    r.alias("slave-announce-ip", "replica-announce-ip");
+    r.boolean("tls-replication", "no");
+    r.boolean("cluster-allow-replica-migration", "yes");
+    r.enumeration("enable-debug-command", "no", &["no", "yes", "local"]).immutable();
+    r.string("tls-key-file", "");
+    r.boolean("latency-tracking", "yes");
+    r.alias("slave-ignore-maxmemory", "replica-ignore-maxmemory");
+    r.memory("hash-max-listpack-value", "64");
+    r.boolean("rdb-save-incremental-fsync", "yes");
+    r.boolean("always-show-logo", "no").immutable();
+    r.string("bio_cpulist", "").immutable();
+    r.string("server_cpulist", "").immutable();
+    r.integer("tcp-backlog", "511", 0, i32::MAX).immutable();
+    r.boolean("rdb-del-sync-files", "no");
+    r.boolean("lazyfree-lazy-expire", "no");
+    r.string("dir", "/data").on_apply(apply_dir);
+    r.integer("io-threads", "1", 1, 128).immutable();
+    r.integer("active-defrag-threshold-lower", "10", 0, 1000);
+    r.boolean("cluster-allow-pubsubshard-when-down", "yes");
+    r.string("logfile", "").immutable();
+    r.enumeration("enable-protected-configs", "no", &["no", "yes", "local"]).immutable();
+    r.memory("zset-max-listpack-value", "64");
+    r.string("requirepass", "");
+    r.integer("hz", "10", 1, 500);
+    r.string("appendfilename", "appendonly.aof");
+    r.string("tls-ciphersuites", "");
+    r.boolean("aof-rewrite-incremental-fsync", "yes");
+    r.boolean("lazyfree-lazy-server-del", "no");
+    r.integer("cluster-announce-bus-port", "0", 0, 65535);
+    r.integer("active-defrag-threshold-upper", "100", 0, 1000);
+    r.enumeration("acl-pubsub-default", "resetchannels", &["allchannels", "resetchannels"]);
+    r.boolean("lazyfree-lazy-user-del", "no");
+    r.integer("shutdown-timeout", "10", 0, i32::MAX);
+    r.string("unixsocket", "").immutable();
+    r.string("cluster-announce-ip", "");
+    r.alias("slave-announce-port", "replica-announce-port");
+    r.boolean("cluster-enabled", "no").immutable();
+    r.boolean("tls-cluster", "no");
+    r.string("cluster-announce-hostname", "");
+    r.alias("hash-max-ziplist-value", "hash-max-listpack-value");
+    r.memory("repl-backlog-size", "1048576");
+    r.boolean("protected-mode", "no");
+    r.boolean("activedefrag", "no");
+    r.alias("slave-lazy-flush", "replica-lazy-flush");
+    r.words("latency-tracking-info-percentiles", "50 99 99.9");
+    r.enumeration("sanitize-dump-payload", "no", &["no", "yes", "clients"]);
+    r.integer("maxmemory-samples", "5", 1, 64);
+    r.integer("socket-mark-id", "0", 0, i32::MAX);
+    r.boolean("crash-memcheck-enabled", "yes");
+    r.integer("replica-announce-port", "0", 0, 65535);
+    r.boolean("appendonly", "no");
+    r.integer("active-defrag-cycle-min", "1", 1, 99);
+    r.alias("slave-priority", "replica-priority");
+    r.string("bgsave_cpulist", "").immutable();
+    r.boolean("replica-announced", "yes");
+    r.string("aclfile", "").immutable();
+    r.boolean("lazyfree-lazy-user-flush", "no");
+    r.boolean("crash-log-enabled", "yes");
+    r.integer("min-replicas-max-lag", "10", 0, i32::MAX);
+    r.integer("slowlog-max-len", "128", 0, i32::MAX);
+    r.string("shutdown-on-sigterm", "default");
+    r.integer("repl-diskless-sync-delay", "5", 0, i32::MAX);
+    r.integer("tcp-keepalive", "300", 0, i32::MAX);
+    r.integer("repl-timeout", "60", 1, i32::MAX);
+    r.integer("tls-session-cache-size", "20480", 0, i32::MAX);
+    r.boolean("replica-ignore-disk-write-errors", "no");
+    r.boolean("tls-prefer-server-ciphers", "no");
+    r.integer("zset-max-listpack-entries", "128", 0, i32::MAX);
+    r.integer("tracking-table-max-keys", "1000000", 0, i32::MAX);
+    r.boolean("replica-serve-stale-data", "yes");
+    r.string("tls-ca-cert-file", "");
+    r.string("locale-collate", "");
+    r.words("client-output-buffer-limit", "normal 0 0 0 slave 268435456 67108864 60 pubsub 33554432 8388608 60");
+    r.boolean("jemalloc-bg-thread", "yes");
+    r.integer("set-max-listpack-entries", "128", 0, i32::MAX);
+    r.words("replicaof", "");
+    r.enumeration("enable-module-command", "no", &["no", "yes", "local"]).immutable();
+    r.integer("lua-time-limit", "5000", 0, i64::MAX);
+    r.boolean("syslog-enabled", "no").immutable();
+    r.boolean("replica-ignore-maxmemory", "yes");
+    r.integer("tls-session-cache-timeout", "300", 0, i32::MAX);
+    r.boolean("lazyfree-lazy-eviction", "no");
+    r.boolean("repl-diskless-sync", "yes");
+    r.string("shutdown-on-sigint", "default");
+    r.boolean("disable-thp", "yes").immutable();
+    r.enumeration("appendfsync", "everysec", &["always", "everysec", "no"]);
+    r.integer("cluster-announce-port", "0", 0, 65535);
+    r.boolean("no-appendfsync-on-rewrite", "no");
+    r.alias("zset-max-ziplist-entries", "zset-max-listpack-entries");
+    r.integer("acllog-max-len", "128", 0, i32::MAX);
+    r.integer("hash-max-listpack-entries", "512", 0, i32::MAX);
+    r.string("syslog-ident", "redis").immutable();
+    r.integer("repl-diskless-sync-max-replicas", "0", 0, i32::MAX);
+    r.integer("stream-node-max-entries", "100", 0, i32::MAX);
+    r.integer("repl-backlog-ttl", "3600", 0, i32::MAX);
+    r.boolean("stop-writes-on-bgsave-error", "yes");
+    r.alias("slaveof", "replicaof");
+    r.alias("slave-serve-stale-data", "replica-serve-stale-data");
+    r.alias("min-slaves-to-write", "min-replicas-to-write");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn registry() -> Registry {
+        let mut registry = Registry::default();
+        populate_config_defaults(&mut registry);
+        registry
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[rstest]
+    #[case("0", Some(0))]
+    #[case("100", Some(100))]
+    #[case("1k", Some(1000))]
+    #[case("1KB", Some(1024))]
+    #[case("100mb", Some(100 * 1024 * 1024))]
+    #[case("2g", Some(2_000_000_000))]
+    #[case("-1", None)]
+    #[case("1tb", None)]
+    #[case("mb", None)]
+    fn test_parse_memory(#[case] input: &str, #[case] expected: Option<u64>) {
+        assert_eq!(parse_memory(input), expected);
+    }
+
+    #[test]
+    fn test_set_normalizes_values() {
+        let mut registry = registry();
+        registry.set(&pairs(&[("MaxMemory", "1kb"), ("appendonly", "YES"), ("maxmemory-policy", "AllKeys-LRU")])).unwrap();
+        assert_eq!(registry.get("maxmemory").unwrap(), "1024");
+        assert_eq!(registry.get("appendonly").unwrap(), "yes");
+        assert_eq!(registry.get("maxmemory-policy").unwrap(), "allkeys-lru");
+    }
+
+    #[test]
+    fn test_aliases_share_values() {
+        let mut registry = registry();
+        registry.set(&pairs(&[("slave-read-only", "no")])).unwrap();
+        assert_eq!(registry.get("replica-read-only").unwrap(), "no");
+        let error = registry.set(&pairs(&[("replica-read-only", "yes"), ("slave-read-only", "no")])).unwrap_err();
+        assert_eq!(error, config_set_failed("slave-read-only", "duplicate parameter"));
+    }
+
+    #[rstest]
+    #[case("hz", "0", "argument must be between 1 and 500 inclusive")]
+    #[case("hz", "ten", "argument couldn't be parsed into an integer")]
+    #[case("appendonly", "maybe", "argument must be 'yes' or 'no'")]
+    #[case("maxmemory", "lots", "argument must be a memory value")]
+    #[case("appendfsync", "never", "argument(s) must be one of the following: always, everysec, no")]
+    #[case("daemonize", "yes", "can't set immutable config")]
+    fn test_set_rejects_invalid_values(#[case] name: &str, #[case] value: &str, #[case] reason: &str) {
+        let mut registry = registry();
+        assert_eq!(registry.set(&pairs(&[(name, value)])).unwrap_err(), config_set_failed(name, reason));
+    }
+
+    #[test]
+    fn test_load_appends_repeated_save_directives() {
+        let mut registry = registry();
+        let directives = vec![
+            vec!["save".to_owned(), "900".to_owned(), "1".to_owned()],
+            vec!["SAVE".to_owned(), "60".to_owned(), "100".to_owned()],
+            vec!["daemonize".to_owned(), "yes".to_owned()],
+            vec!["maxmemory".to_owned(), "1gb".to_owned()],
+        ];
+        registry.load(&directives).unwrap();
+        assert_eq!(registry.get("save").unwrap(), "900 1 60 100");
+        assert_eq!(registry.get("daemonize").unwrap(), "yes");
+        assert_eq!(registry.get("maxmemory").unwrap(), "1073741824");
+
+        registry.load(&[vec!["save".to_owned(), "".to_owned()]]).unwrap();
+        assert_eq!(registry.get("save").unwrap(), "");
+    }
+
+    #[rstest]
+    #[case(&["nonsense", "1"], "Bad directive or wrong number of arguments")]
+    #[case(&["port"], "Bad directive or wrong number of arguments")]
+    #[case(&["port", "many"], "argument couldn't be parsed into an integer")]
+    #[case(&["databases", "1000000000"], "argument must be between 1 and 1024 inclusive")]
+    fn test_load_rejects_bad_directives(#[case] directive: &[&str], #[case] reason: &str) {
+        let directive = directive.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(registry().load(&[directive]), Err((0, reason.to_owned())));
+    }
+
+    #[test]
+    fn test_directives() {
+        let mut registry = registry();
+        registry.load(&[
+            vec!["save".to_owned(), "900 1".to_owned(), "60".to_owned(), "100".to_owned()],
+            vec!["maxmemory".to_owned(), "3mb".to_owned()],
+            vec!["requirepass".to_owned(), "my \"secret\"".to_owned()],
+            vec!["bind".to_owned(), "127.0.0.1".to_owned(), "-::1".to_owned()],
+        ])
+        .unwrap();
+        let directives = registry.directives();
+        let lines = |name: &str| directives.iter().find(|directive| directive.name == name).unwrap().lines.clone();
+        assert_eq!(lines("save"), ["save 900 1", "save 60 100"]);
+        assert_eq!(lines("maxmemory"), ["maxmemory 3mb"]);
+        assert_eq!(lines("requirepass"), ["requirepass \"my \\\"secret\\\"\""]);
+        assert_eq!(lines("bind"), ["bind 127.0.0.1 -::1"]);
+        assert_eq!(lines("hz"), ["hz 10"]);
+        assert!(directives.iter().find(|directive| directive.name == "hz").unwrap().is_default);
+        assert!(!directives.iter().find(|directive| directive.name == "requirepass").unwrap().is_default);
+    }
+
+    #[test]
+    fn test_set_is_atomic() {
+        let mut registry = registry();
+        assert!(registry.set(&pairs(&[("hz", "20"), ("maxmemory", "lots")])).is_err());
+        assert_eq!(registry.get("hz").unwrap(), "10");
+
+        registry.parameters.get_mut("appendonly").unwrap().on_apply(|_| Err("nope".to_owned()));
+        let error = registry.set(&pairs(&[("hz", "20"), ("appendonly", "yes")])).unwrap_err();
+        assert_eq!(error, config_set_failed("appendonly", "nope"));
+        assert_eq!(registry.get("hz").unwrap(), "10");
+        assert_eq!(registry.get("appendonly").unwrap(), "no");
+    }
+}
@@ -0,0 +1,313 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::config::{global_config_directives, global_config_load, global_config_resolve, ConfigDirectives};
+
+/// Comment placed before the directives CONFIG REWRITE adds to the end of the file, like Redis does.
+const REWRITE_SIGNATURE: &str = "# Generated by CONFIG REWRITE";
+
+lazy_static! {
+    /// Absolute path of the config file the server was started with, if any, for CONFIG REWRITE.
+    static ref CONFIG_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// A config directive split into its arguments, with where it came from for error messages.
+struct Directive {
+    args: Vec<String>,
+    source: String,
+    line_number: usize,
+    line: String,
+}
+
+/// Load the server configuration from the command line, in the form Redis accepts:
+/// an optional config file path followed by `--name value...` options, e.g.
+/// `oxide /etc/oxide.conf --port 7000 --bind 127.0.0.1`.
+/// Options given on the command line win over the file. Errors hold a report to print before exiting.
+pub fn load_server_config(args: &[String]) -> Result<(), String> {
+    let (file, options) = match args.split_first() {
+        Some((path, options)) if !path.starts_with("--") => (Some(path), options),
+        _ => (None, args),
+    };
+
+    let mut directives = Vec::new();
+    if let Some(path) = file {
+        read_config_file(path, &mut directives)?;
+        // Resolved now since the `dir` directive may change the working directory.
+        *CONFIG_FILE.lock().unwrap() = fs::canonicalize(path).ok();
+    }
+    directives.extend(parse_command_line(options)?);
+
+    let args = directives.iter().map(|directive| directive.args.clone()).collect::<Vec<_>>();
+    global_config_load(&args).map_err(|(index, reason)| {
+        let directive = &directives[index];
+        fatal_config_error(&directive.source, directive.line_number, &directive.line, &reason)
+    })
+}
+
+/// The report printed when the configuration can not be loaded, worded like the Redis one.
+fn fatal_config_error(source: &str, line_number: usize, line: &str, reason: &str) -> String {
+    format!(
+        "\n*** FATAL CONFIG FILE ERROR ***\nReading the configuration file {}, at line {}\n>>> '{}'\n{}",
+        source, line_number, line, reason
+    )
+}
+
+/// Turn `--name value...` options into directives, one per option.
+fn parse_command_line(options: &[String]) -> Result<Vec<Directive>, String> {
+    let mut directives: Vec<Directive> = Vec::new();
+    for (index, option) in options.iter().enumerate() {
+        if let Some(name) = option.strip_prefix("--") {
+            directives.push(Directive {
+                args: vec![name.to_owned()],
+                source: "from the command line".to_owned(),
+                line_number: index + 1,
+                line: String::new(),
+            });
+        } else if let Some(directive) = directives.last_mut() {
+            directive.args.push(option.clone());
+        } else {
+            return Err(format!("Invalid argument '{}', options must start with --", option));
+        }
+    }
+    for directive in directives.iter_mut() {
+        // `--save` on its own disables snapshots, like `save ""` in a file.
+        if directive.args.len() == 1 && directive.args[0].eq_ignore_ascii_case("save") {
+            directive.args.push(String::new());
+        }
+        directive.line = directive.args.join(" ");
+    }
+    Ok(directives)
+}
+
+/// Read the directives of a config file, following `include` directives in place.
+fn read_config_file(path: &str, directives: &mut Vec<Directive>) -> Result<(), String> {
+    let contents = fs::read(path).map_err(|error| format!("Fatal error, can't open config file '{}': {}", path, error))?;
+    let contents = String::from_utf8_lossy(&contents);
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let source = format!("'{}'", path);
+        let Some(args) = split_args(line) else {
+            return Err(fatal_config_error(&source, index + 1, line, "Unbalanced quotes in configuration line"));
+        };
+        match args.as_slice() {
+            [] => continue,
+            [name, included] if name.eq_ignore_ascii_case("include") => read_config_file(included, directives)?,
+            _ => directives.push(Directive { args, source, line_number: index + 1, line: line.to_owned() }),
+        }
+    }
+    Ok(())
+}
+
+/// Write the current configuration back to the file the server was started with.
+/// Comments, ordering and directives that have not changed are kept, changed directives are updated
+/// in place and parameters missing from the file are appended unless they hold their default value.
+pub fn rewrite_config_file() -> Result<(), String> {
+    let path = CONFIG_FILE.lock().unwrap().clone().ok_or("The server is running without a config file")?;
+    // A config file deleted since startup is rewritten from scratch.
+    let contents = match fs::read(&path) {
+        Ok(contents) => String::from_utf8_lossy(&contents).into_owned(),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(format!("Rewriting config file: {}", error)),
+    };
+    let rewritten = rewrite_contents(&contents, &global_config_directives(), global_config_resolve);
+
+    // Write a temporary file and rename it over the old one, so a crash never leaves a truncated config.
+    let temporary = path.with_extension(format!("tmp-{}", std::process::id()));
+    fs::write(&temporary, rewritten)
+        .and_then(|_| fs::rename(&temporary, &path))
+        .map_err(|error| {
+            let _ = fs::remove_file(&temporary);
+            format!("Rewriting config file: {}", error)
+        })
+}
+
+/// Rewrite the contents of a config file so its directives match `directives`.
+/// `resolve` maps a directive name, possibly an alias, to the current name of the parameter.
+fn rewrite_contents(
+    contents: &str,
+    directives: &[ConfigDirectives],
+    resolve: impl Fn(&str) -> Option<&'static str>,
+) -> String {
+    let mut lines = contents.lines().map(|line| Some(line.to_owned())).collect::<Vec<_>>();
+    let mut positions: HashMap<&'static str, Vec<usize>> = HashMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        // Lines that are not directives of a known parameter, like `include`, are kept as is.
+        if let Some(name) = split_args(line).and_then(|args| args.first().and_then(|name| resolve(name))) {
+            positions.entry(name).or_default().push(index);
+        }
+    }
+
+    let mut appended = Vec::new();
+    for directive in directives {
+        let old_positions = positions.remove(directive.name).unwrap_or_default();
+        if old_positions.is_empty() && directive.is_default {
+            continue;
+        }
+        for (index, line) in directive.lines.iter().enumerate() {
+            match old_positions.get(index) {
+                Some(&position) => lines[position] = Some(line.clone()),
+                None => appended.push(line.clone()),
+            }
+        }
+        // Left over lines of a directive now written on fewer lines, or repeated by mistake.
+        for &position in old_positions.iter().skip(directive.lines.len()) {
+            lines[position] = None;
+        }
+    }
+
+    let mut lines = lines.into_iter().flatten().collect::<Vec<_>>();
+    if !appended.is_empty() && !lines.iter().any(|line| line == REWRITE_SIGNATURE) {
+        lines.push(REWRITE_SIGNATURE.to_owned());
+    }
+    lines.extend(appended);
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Split a config line into arguments the way Redis does: arguments are separated by spaces and may
+/// be double quoted, with escapes such as `\n` or `\x41`, or single quoted, where only `\'` is an escape.
+/// Returns None if the quotes are unbalanced or a closing quote is not followed by a space.
+pub fn split_args(line: &str) -> Option<Vec<String>> {
+    let bytes = line.as_bytes();
+    let mut args = Vec::new();
+    let mut position = 0;
+    loop {
+        while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        if position == bytes.len() {
+            return Some(args);
+        }
+
+        let mut arg: Vec<u8> = Vec::new();
+        let mut quote: Option<u8> = None;
+        loop {
+            let Some(&byte) = bytes.get(position) else {
+                // The line ended inside quotes.
+                if quote.is_some() {
+                    return None;
+                }
+                break;
+            };
+            match quote {
+                Some(b'"') if byte == b'\\' && position + 1 < bytes.len() => {
+                    let hex = bytes.get(position + 2..position + 4).and_then(|digits| {
+                        std::str::from_utf8(digits).ok().and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    });
+                    match (bytes[position + 1], hex) {
+                        (b'x', Some(value)) => {
+                            arg.push(value);
+                            position += 2;
+                        }
+                        (b'n', _) => arg.push(b'\n'),
+                        (b'r', _) => arg.push(b'\r'),
+                        (b't', _) => arg.push(b'\t'),
+                        (b'b', _) => arg.push(0x08),
+                        (b'a', _) => arg.push(0x07),
+                        (other, _) => arg.push(other),
+                    }
+                    position += 1;
+                }
+                Some(b'\'') if byte == b'\\' && bytes.get(position + 1) == Some(&b'\'') => {
+                    arg.push(b'\'');
+                    position += 1;
+                }
+                Some(closing) if byte == closing => {
+                    // A closing quote must be followed by a space or the end of the line.
+                    if bytes.get(position + 1).is_some_and(|next| !next.is_ascii_whitespace()) {
+                        return None;
+                    }
+                    position += 1;
+                    break;
+                }
+                Some(_) => arg.push(byte),
+                None if byte.is_ascii_whitespace() => break,
+                None if byte == b'"' || byte == b'\'' => quote = Some(byte),
+                None => arg.push(byte),
+            }
+            position += 1;
+        }
+        args.push(String::from_utf8_lossy(&arg).into_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("port 7000", Some(vec!["port", "7000"]))]
+    #[case("  save   900 1  ", Some(vec!["save", "900", "1"]))]
+    #[case("logfile \"my log.txt\"", Some(vec!["logfile", "my log.txt"]))]
+    #[case("save \"\"", Some(vec!["save", ""]))]
+    #[case("requirepass \"a\\\"b\\x41\\n\"", Some(vec!["requirepass", "a\"bA\n"]))]
+    #[case("requirepass 'it\\'s'", Some(vec!["requirepass", "it's"]))]
+    #[case("requirepass \"open", None)]
+    #[case("requirepass \"closed\"glued", None)]
+    fn test_split_args(#[case] line: &str, #[case] expected: Option<Vec<&str>>) {
+        let expected = expected.map(|args| args.into_iter().map(str::to_owned).collect::<Vec<_>>());
+        assert_eq!(split_args(line), expected);
+    }
+
+    #[test]
+    fn test_parse_command_line() {
+        let options = ["--port", "7000", "--save", "--bind", "127.0.0.1", "::1"].map(str::to_owned);
+        let directives = parse_command_line(&options).unwrap();
+        let args = directives.iter().map(|directive| directive.args.join(" ")).collect::<Vec<_>>();
+        assert_eq!(args, ["port 7000", "save ", "bind 127.0.0.1 ::1"]);
+        assert!(parse_command_line(&["7000".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_rewrite_contents() {
+        let contents = "# my config\nslave-read-only no\nsave 900 1\nsave 300 10\ninclude other.conf\n\nhz 10\n";
+        let directive = |name: &'static str, lines: &[&str], is_default: bool| ConfigDirectives {
+            name,
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+            is_default,
+        };
+        let directives = [
+            directive("hz", &["hz 20"], false),
+            directive("maxmemory", &["maxmemory 1gb"], false),
+            directive("port", &["port 6379"], true),
+            directive("replica-read-only", &["replica-read-only no"], false),
+            directive("save", &["save 60 100"], false),
+        ];
+        let resolve = |name: &str| match name {
+            "slave-read-only" | "replica-read-only" => Some("replica-read-only"),
+            "save" => Some("save"),
+            "hz" => Some("hz"),
+            _ => None,
+        };
+        let expected = "# my config\nreplica-read-only no\nsave 60 100\ninclude other.conf\n\nhz 20\n\
+            # Generated by CONFIG REWRITE\nmaxmemory 1gb\n";
+        assert_eq!(rewrite_contents(contents, &directives, resolve), expected);
+    }
+
+    #[test]
+    fn test_read_config_file_follows_includes() {
+        let directory = std::env::temp_dir().join(format!("oxide-config-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let included = directory.join("included.conf");
+        let main = directory.join("main.conf");
+        fs::write(&included, "maxclients 50\n").unwrap();
+        fs::write(&main, format!("# comment\nport 7000\ninclude {}\n\nsave 900 1\n", included.display())).unwrap();
+
+        let mut directives = Vec::new();
+        read_config_file(main.to_str().unwrap(), &mut directives).unwrap();
+        let args = directives.iter().map(|directive| directive.args.join(" ")).collect::<Vec<_>>();
+        assert_eq!(args, ["port 7000", "maxclients 50", "save 900 1"]);
+        assert_eq!(directives[2].line_number, 5);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
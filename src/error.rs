@@ -0,0 +1,122 @@
+use std::fmt;
+
+use crate::serialization::RespValue;
+
+/// Longest prefix of a client supplied value quoted back in an error message, same as Redis.
+const MAX_QUOTED_LENGTH: usize = 128;
+
+/// Everything that can go wrong while decoding or executing a command.
+/// Each variant is sent to the client as an error reply worded like the real Redis one,
+/// since client libraries match on these prefixes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedisError {
+    /// The client sent bytes that are not valid RESP. The connection is closed afterwards.
+    Protocol(String),
+    UnknownCommand { name: String, args: Vec<String> },
+    UnknownSubcommand { command: String, subcommand: String },
+    /// Holds the lowercase command name, e.g. `set` or `config|get`.
+    WrongArity(String),
+    Syntax,
+    /// A command was given a key holding a value of another type.
+    WrongType,
+    NotInteger,
+    NotFloat,
+    Overflow,
+    NoSuchKey,
+    /// Holds the lowercase command name the expire time was given to.
+    InvalidExpireTime(String),
+    /// CONFIG SET refused a value, holding the parameter name as given and the reason.
+    ConfigSetFailed { argument: String, reason: String },
+    /// A stream command named a consumer group that does not exist, holding the message without the
+    /// `NOGROUP ` prefix.
+    NoGroup(String),
+    /// XGROUP CREATE was given the name of an existing consumer group.
+    BusyGroup,
+    /// A command that may use more memory was refused because memory use is over `maxmemory` and
+    /// nothing could be evicted.
+    OutOfMemory,
+    /// Any other error, holding the message without the `ERR ` prefix.
+    Other(String),
+}
+
+impl fmt::Display for RedisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedisError::Protocol(message) => write!(f, "ERR Protocol error: {}", message),
+            RedisError::UnknownCommand { name, args } => {
+                write!(f, "ERR unknown command '{}', with args beginning with: ", truncate(name, MAX_QUOTED_LENGTH))?;
+                let mut remaining = MAX_QUOTED_LENGTH;
+                for arg in args {
+                    if remaining == 0 {
+                        break;
+                    }
+                    let quoted = truncate(arg, remaining);
+                    remaining -= quoted.len();
+                    write!(f, "'{}' ", quoted)?;
+                }
+                Ok(())
+            }
+            RedisError::UnknownSubcommand { command, subcommand } => write!(
+                f, "ERR unknown subcommand '{}'. Try {} HELP.", truncate(subcommand, MAX_QUOTED_LENGTH), command
+            ),
+            RedisError::WrongArity(command) => write!(f, "ERR wrong number of arguments for '{}' command", command),
+            RedisError::Syntax => write!(f, "ERR syntax error"),
+            RedisError::WrongType => write!(f, "WRONGTYPE Operation against a key holding the wrong kind of value"),
+            RedisError::NotInteger => write!(f, "ERR value is not an integer or out of range"),
+            RedisError::NotFloat => write!(f, "ERR value is not a valid float"),
+            RedisError::Overflow => write!(f, "ERR increment or decrement would overflow"),
+            RedisError::NoSuchKey => write!(f, "ERR no such key"),
+            RedisError::InvalidExpireTime(command) => write!(f, "ERR invalid expire time in '{}' command", command),
+            RedisError::ConfigSetFailed { argument, reason } => {
+                write!(f, "ERR CONFIG SET failed (possibly related to argument '{}') - {}", argument, reason)
+            }
+            RedisError::NoGroup(message) => write!(f, "NOGROUP {}", message),
+            RedisError::BusyGroup => write!(f, "BUSYGROUP Consumer Group name already exists"),
+            RedisError::OutOfMemory => write!(f, "OOM command not allowed when used memory > 'maxmemory'."),
+            RedisError::Other(message) => write!(f, "ERR {}", message),
+        }
+    }
+}
+
+impl From<RedisError> for RespValue {
+    fn from(error: RedisError) -> RespValue {
+        RespValue::Error(error.to_string())
+    }
+}
+
+/// Cut a string down to at most `max_length` bytes without splitting a character.
+fn truncate(text: &str, max_length: usize) -> &str {
+    if text.len() <= max_length {
+        return text;
+    }
+    let mut end = max_length;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(RedisError::WrongArity("set".to_owned()), "ERR wrong number of arguments for 'set' command")]
+    #[case(RedisError::Protocol("invalid bulk length".to_owned()), "ERR Protocol error: invalid bulk length")]
+    #[case(
+        RedisError::UnknownCommand { name: "foo".to_owned(), args: vec!["a".to_owned(), "b".to_owned()] },
+        "ERR unknown command 'foo', with args beginning with: 'a' 'b' "
+    )]
+    #[case(
+        RedisError::UnknownSubcommand { command: "CONFIG".to_owned(), subcommand: "bar".to_owned() },
+        "ERR unknown subcommand 'bar'. Try CONFIG HELP."
+    )]
+    #[case(
+        RedisError::ConfigSetFailed { argument: "hz".to_owned(), reason: "argument couldn't be parsed into an integer".to_owned() },
+        "ERR CONFIG SET failed (possibly related to argument 'hz') - argument couldn't be parsed into an integer"
+    )]
+    fn test_error_message(#[case] error: RedisError, #[case] expected: &str) {
+        assert_eq!(error.to_string(), expected);
+    }
+}
@@ -0,0 +1,142 @@
+/// Redis style glob matching, as used by KEYS, SCAN and CONFIG GET.
+///
+/// Supported syntax:
+/// - `*` matches any sequence of bytes, including an empty one
+/// - `?` matches exactly one byte
+/// - `[abc]`, `[a-z]` and `[^a-z]` match one byte in (or not in) a set of bytes and ranges
+/// - `\` escapes the next byte, both in and outside of brackets
+///
+/// Matching backtracks to the most recent `*` only, so it runs in O(pattern * string) time
+/// even for patterns made of many stars.
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let mut pattern_index = 0;
+    let mut string_index = 0;
+    // Where to resume after the last `*`: the pattern right after it and the string position it currently absorbs up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while string_index < string.len() {
+        if pattern.get(pattern_index) == Some(&b'*') {
+            while pattern.get(pattern_index) == Some(&b'*') {
+                pattern_index += 1;
+            }
+            if pattern_index == pattern.len() {
+                return true;
+            }
+            backtrack = Some((pattern_index, string_index));
+            continue;
+        }
+        if pattern_index < pattern.len() {
+            let (matched, next) = match_one(pattern, pattern_index, string[string_index], nocase);
+            if matched {
+                pattern_index = next;
+                string_index += 1;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((star_pattern_index, star_string_index)) => {
+                pattern_index = star_pattern_index;
+                string_index = star_string_index + 1;
+                backtrack = Some((star_pattern_index, string_index));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[pattern_index..].iter().all(|&byte| byte == b'*')
+}
+
+/// Match a single byte against the pattern token starting at `index`, which is not a `*`.
+/// Returns whether it matched and the index of the next token.
+fn match_one(pattern: &[u8], index: usize, byte: u8, nocase: bool) -> (bool, usize) {
+    let equals = |a: u8, b: u8| if nocase { a.eq_ignore_ascii_case(&b) } else { a == b };
+    match pattern[index] {
+        b'?' => (true, index + 1),
+        b'\\' if index + 1 < pattern.len() => (equals(pattern[index + 1], byte), index + 2),
+        b'[' => match_class(pattern, index + 1, byte, nocase),
+        literal => (equals(literal, byte), index + 1),
+    }
+}
+
+/// Match a byte against a bracket expression whose content starts at `index`.
+/// An unterminated bracket extends to the end of the pattern, like in Redis.
+fn match_class(pattern: &[u8], mut index: usize, byte: u8, nocase: bool) -> (bool, usize) {
+    let fold = |value: u8| if nocase { value.to_ascii_lowercase() } else { value };
+    let byte = fold(byte);
+    let negate = pattern.get(index) == Some(&b'^');
+    if negate {
+        index += 1;
+    }
+
+    let mut matched = false;
+    while index < pattern.len() {
+        match pattern[index] {
+            b']' => {
+                index += 1;
+                return (matched != negate, index);
+            }
+            b'\\' if index + 1 < pattern.len() => {
+                matched |= fold(pattern[index + 1]) == byte;
+                index += 2;
+            }
+            start if pattern.get(index + 1) == Some(&b'-') && index + 2 < pattern.len() => {
+                let (mut low, mut high) = (fold(start), fold(pattern[index + 2]));
+                if low > high {
+                    std::mem::swap(&mut low, &mut high);
+                }
+                matched |= (low..=high).contains(&byte);
+                index += 3;
+            }
+            literal => {
+                matched |= fold(literal) == byte;
+                index += 1;
+            }
+        }
+    }
+    (matched != negate, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("*", "", true)]
+    #[case("*", "anything", true)]
+    #[case("", "", true)]
+    #[case("", "a", false)]
+    #[case("h?llo", "hello", true)]
+    #[case("h?llo", "hllo", false)]
+    #[case("h*llo", "heeeello", true)]
+    #[case("h*llo", "hllo", true)]
+    #[case("h[ae]llo", "hallo", true)]
+    #[case("h[ae]llo", "hillo", false)]
+    #[case("h[^e]llo", "hallo", true)]
+    #[case("h[^e]llo", "hello", false)]
+    #[case("h[a-b]llo", "hbllo", true)]
+    #[case("h[b-a]llo", "hallo", true)]
+    #[case("h[a-b]llo", "hcllo", false)]
+    #[case("h\\*llo", "h*llo", true)]
+    #[case("h\\*llo", "hello", false)]
+    #[case("[\\]]", "]", true)]
+    #[case("*max*", "maxmemory", true)]
+    #[case("*max*", "proto-max-bulk-len", true)]
+    #[case("*max*", "port", false)]
+    #[case("maxmemory-*", "maxmemory-policy", true)]
+    #[case("maxmemory-*", "maxmemory", false)]
+    #[case("a*b*c", "aXbYbZc", true)]
+    #[case("a*b*c", "aXbYbZ", false)]
+    #[case("*a*a*a*a*a*a*a*a*b", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", false)]
+    #[case("[abc", "b", true)]
+    fn test_glob_match(#[case] pattern: &str, #[case] string: &str, #[case] expected: bool) {
+        assert_eq!(glob_match(pattern.as_bytes(), string.as_bytes(), false), expected);
+    }
+
+    #[test]
+    fn test_glob_match_nocase() {
+        assert!(glob_match(b"MAX*", b"maxclients", true));
+        assert!(glob_match(b"[A-C]x", b"bX", true));
+        assert!(!glob_match(b"MAX*", b"maxclients", false));
+    }
+}
@@ -2,15 +2,42 @@ mod server;
 mod commands;
 mod serialization;
 mod store;
+mod config;
+mod config_file;
+mod stats;
+mod error;
+mod random;
+mod glob;
+mod active_expire;
+mod blocking;
+mod skiplist;
+mod stream;
+mod maxmemory;
 
 use env_logger::Builder;
+use log::LevelFilter;
+use crate::config::{apply_loglevel, global_config_get};
+use crate::config_file::load_server_config;
+use crate::active_expire::start_active_expire;
 use crate::server::start_server;
 
 fn main() {
+    // Everything goes through the logger, what gets printed is decided by the `loglevel` config
+    // unless LOG_LEVEL is set.
     Builder::new()
+        .filter_level(LevelFilter::Trace)
         .parse_env("LOG_LEVEL")
         .init();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Err(report) = load_server_config(&args) {
+        eprintln!("{}", report);
+        std::process::exit(1);
+    }
+    if std::env::var_os("LOG_LEVEL").is_none() {
+        let _ = apply_loglevel(&global_config_get("loglevel").unwrap_or_default());
+    }
     log::info!("Starting");
 
+    start_active_expire();
     start_server();
 }
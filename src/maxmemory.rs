@@ -0,0 +1,239 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::config::global_config_get;
+use crate::random::random_u64;
+use crate::stats;
+use crate::store::{now_ms, with_databases, Database};
+
+/// Bytes currently allocated on the heap, the `used_memory` Redis compares against maxmemory.
+static USED_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+/// The `maxmemory` config in bytes, 0 meaning no limit. Cached here by its apply hook since it is
+/// checked before every command.
+static MAXMEMORY: AtomicU64 = AtomicU64::new(0);
+
+/// The `lfu-log-factor` and `lfu-decay-time` configs, cached by their apply hooks since every key access
+/// uses them.
+static LFU_LOG_FACTOR: AtomicU64 = AtomicU64::new(10);
+static LFU_DECAY_TIME: AtomicU64 = AtomicU64::new(1);
+
+/// Frequency counter of new keys, so that they are not evicted before they get a chance to be accessed again.
+const LFU_INIT_VAL: u8 = 5;
+
+/// The system allocator, keeping count of the bytes in use.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = unsafe { System.alloc(layout) };
+        if !pointer.is_null() {
+            USED_MEMORY.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        pointer
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let pointer = unsafe { System.alloc_zeroed(layout) };
+        if !pointer.is_null() {
+            USED_MEMORY.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(pointer, layout) };
+        USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, pointer: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_pointer = unsafe { System.realloc(pointer, layout, new_size) };
+        if !new_pointer.is_null() {
+            USED_MEMORY.fetch_add(new_size, Ordering::Relaxed);
+            USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_pointer
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+pub fn used_memory() -> usize {
+    USED_MEMORY.load(Ordering::Relaxed)
+}
+
+pub fn maxmemory() -> u64 {
+    MAXMEMORY.load(Ordering::Relaxed)
+}
+
+/// Apply hook of the `maxmemory` config, given the size in bytes.
+pub fn apply_maxmemory(value: &str) -> Result<(), String> {
+    let limit = value.parse().map_err(|_| "argument must be a memory value".to_owned())?;
+    MAXMEMORY.store(limit, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Apply hook of the `lfu-log-factor` config.
+pub fn apply_lfu_log_factor(value: &str) -> Result<(), String> {
+    let factor = value.parse().map_err(|_| "argument couldn't be parsed into an integer".to_owned())?;
+    LFU_LOG_FACTOR.store(factor, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Apply hook of the `lfu-decay-time` config, given in minutes.
+pub fn apply_lfu_decay_time(value: &str) -> Result<(), String> {
+    let minutes = value.parse().map_err(|_| "argument couldn't be parsed into an integer".to_owned())?;
+    LFU_DECAY_TIME.store(minutes, Ordering::Relaxed);
+    Ok(())
+}
+
+/// How often a key is accessed, kept like Redis does for the `-lfu` policies: a counter growing
+/// logarithmically with the number of accesses, that goes down by one for every `lfu-decay-time` minutes
+/// the key is not accessed.
+#[derive(Clone, Copy)]
+pub struct Frequency {
+    counter: u8,
+    /// Unix time in minutes the counter was last decayed.
+    decayed_at: u64,
+}
+
+impl Default for Frequency {
+    fn default() -> Frequency {
+        Frequency { counter: LFU_INIT_VAL, decayed_at: now_ms() / 60_000 }
+    }
+}
+
+impl Frequency {
+    /// The counter, decayed for the time elapsed since the key was last accessed.
+    pub fn counter(&self) -> u8 {
+        let decay_time = LFU_DECAY_TIME.load(Ordering::Relaxed);
+        if decay_time == 0 {
+            return self.counter;
+        }
+        let periods = (now_ms() / 60_000).saturating_sub(self.decayed_at) / decay_time;
+        self.counter.saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// Count an access. The counter is incremented with a probability that falls as it grows, so that with
+    /// the default `lfu-log-factor` it takes about a million accesses to saturate.
+    pub fn touch(&mut self) {
+        self.counter = self.counter();
+        self.decayed_at = now_ms() / 60_000;
+        if self.counter == u8::MAX {
+            return;
+        }
+        let base = self.counter.saturating_sub(LFU_INIT_VAL) as f64;
+        let probability = 1.0 / (base * LFU_LOG_FACTOR.load(Ordering::Relaxed) as f64 + 1.0);
+        if (random_u64() as f64) < probability * u64::MAX as f64 {
+            self.counter += 1;
+        }
+    }
+}
+
+/// Called before every command: if more memory than `maxmemory` is in use, evict keys following
+/// `maxmemory-policy` until it is not. Returns false if memory use is still over the limit, because the
+/// policy is `noeviction` or there is nothing left to evict, in which case commands that may use more
+/// memory are refused.
+pub fn evict_if_needed() -> bool {
+    let limit = maxmemory();
+    if limit == 0 || used_memory() as u64 <= limit {
+        return true;
+    }
+    let policy = global_config_get("maxmemory-policy").unwrap_or_default();
+    if policy == "noeviction" {
+        return false;
+    }
+    let samples = global_config_get("maxmemory-samples").and_then(|value| value.parse().ok()).unwrap_or(5);
+    with_databases(|databases, _| {
+        while used_memory() as u64 > limit {
+            let Some((index, key)) = pick_victim(databases, &policy, samples) else {
+                return false;
+            };
+            // Freed right away rather than in the background, so that memory use goes down before the command runs.
+            databases[index].remove(&key);
+            stats::increment(&stats::EVICTED_KEYS, 1);
+        }
+        true
+    })
+}
+
+/// Pick the key to evict next among `samples` keys picked at random, like Redis approximates its policies.
+/// The `volatile-` policies only consider keys with a time to live. The `-ttl` policy evicts the key
+/// expiring first, the `-lru` ones the key accessed least recently, the `-lfu` ones the key accessed least
+/// frequently and the `-random` ones any key.
+fn pick_victim(databases: &[Database], policy: &str, samples: usize) -> Option<(usize, Vec<u8>)> {
+    let volatile = policy.starts_with("volatile-");
+    let candidates = databases
+        .iter()
+        .enumerate()
+        .filter(|(_, database)| if volatile { database.volatile_count() > 0 } else { database.key_count() > 0 })
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return None;
+    }
+    let mut victim: Option<(u64, usize, Vec<u8>)> = None;
+    for _ in 0..samples.max(1) {
+        let index = candidates[random_u64() as usize % candidates.len()];
+        let database = &databases[index];
+        let key = if volatile { database.random_volatile_key() } else { database.random_key() };
+        let Some((key, entry)) = key.and_then(|key| database.peek(&key).map(|entry| (key, entry))) else {
+            continue;
+        };
+        let rank = if policy.ends_with("-random") {
+            return Some((index, key));
+        } else if policy.ends_with("-ttl") {
+            entry.expires_at().unwrap_or(u64::MAX)
+        } else if policy.ends_with("-lfu") {
+            entry.frequency() as u64
+        } else {
+            entry.accessed()
+        };
+        if victim.as_ref().is_none_or(|(lowest, _, _)| rank < *lowest) {
+            victim = Some((rank, index, key));
+        }
+    }
+    victim.map(|(_, index, key)| (index, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Ttl;
+
+    #[test]
+    fn test_pick_victim() {
+        let mut databases = vec![Database::new(), Database::new()];
+        databases[1].set(b"persistent".to_vec(), b"v".to_vec(), Ttl::Persist);
+        databases[1].set(b"later".to_vec(), b"v".to_vec(), Ttl::ExpireAt(u64::MAX));
+        databases[1].set(b"sooner".to_vec(), b"v".to_vec(), Ttl::ExpireAt(u64::MAX - 1));
+
+        // Sampling many more keys than there are always finds the best victim.
+        assert_eq!(pick_victim(&databases, "volatile-ttl", 100), Some((1, b"sooner".to_vec())));
+        let (index, key) = pick_victim(&databases, "volatile-random", 1).unwrap();
+        assert!(index == 1 && key != b"persistent");
+        assert!(pick_victim(&databases[..1], "allkeys-lru", 100).is_none());
+    }
+
+    #[test]
+    fn test_pick_victim_by_frequency() {
+        let mut databases = vec![Database::new()];
+        databases[0].set(b"rare".to_vec(), b"v".to_vec(), Ttl::Persist);
+        databases[0].set(b"frequent".to_vec(), b"v".to_vec(), Ttl::Persist);
+        for _ in 0..100 {
+            databases[0].get(b"frequent");
+        }
+        assert!(databases[0].peek(b"frequent").unwrap().frequency() > LFU_INIT_VAL);
+        assert_eq!(pick_victim(&databases, "allkeys-lfu", 100), Some((0, b"rare".to_vec())));
+    }
+
+    #[test]
+    fn test_frequency_decays() {
+        let mut frequency = Frequency { counter: 20, decayed_at: now_ms() / 60_000 - 3 };
+        assert_eq!(frequency.counter(), 17);
+        frequency.touch();
+        assert!(frequency.counter() == 17 || frequency.counter() == 18);
+        assert_eq!(frequency.decayed_at, now_ms() / 60_000);
+    }
+}
@@ -0,0 +1,70 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+thread_local! {
+    /// Per-thread xorshift state, seeded from the randomly keyed std hasher so threads diverge.
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(crate::store::now_ms());
+    // Xorshift gets stuck on zero.
+    hasher.finish() | 1
+}
+
+/// Fast non-cryptographic random number, good enough for sampling keys and picking members.
+pub fn random_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
+}
+
+/// Most elements a negative count may ask `random_picks` for. The picks are all built while the keyspace is
+/// locked, so an unbounded count could exhaust memory.
+pub const MAX_REPEATED_PICKS: u64 = 1_000_000;
+
+/// Pick random elements the way HRANDFIELD and friends do with a count argument: a positive count
+/// picks that many distinct elements, or all of them if there are fewer, while a negative count picks
+/// exactly `-count` elements that may repeat.
+pub fn random_picks<T>(elements: &[T], count: i64) -> Vec<&T> {
+    if elements.is_empty() {
+        return Vec::new();
+    }
+    if count < 0 {
+        return (0..count.unsigned_abs())
+            .map(|_| &elements[random_u64() as usize % elements.len()])
+            .collect();
+    }
+    // Partial Fisher-Yates shuffle of the indexes, stopping once enough are picked.
+    let count = (count as usize).min(elements.len());
+    let mut indexes = (0..elements.len()).collect::<Vec<_>>();
+    for picked in 0..count {
+        let swap = picked + random_u64() as usize % (elements.len() - picked);
+        indexes.swap(picked, swap);
+    }
+    indexes[..count].iter().map(|index| &elements[*index]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_picks() {
+        let elements = [1, 2, 3, 4, 5];
+        let mut distinct = random_picks(&elements, 3);
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 3);
+        assert_eq!(random_picks(&elements, 10).len(), 5);
+        assert_eq!(random_picks(&elements, -10).len(), 10);
+        assert!(random_picks(&[] as &[i32], -3).is_empty());
+    }
+}
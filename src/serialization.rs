@@ -4,10 +4,17 @@ use strum_macros::EnumIter;
 use std::str::FromStr;
 
 use crate::commands::CommandType;
+use crate::error::RedisError;
 
-const LINE_TERMINATOR: &'static str = "\r\n";
-pub const NULL: &'static str = "$-1\r\n";
-pub const OK: &'static str = "OK";
+const LINE_TERMINATOR: &str = "\r\n";
+/// Largest number of arguments accepted in a single command, same as Redis.
+const MAX_MULTIBULK_LENGTH: i64 = 1024 * 1024;
+/// Largest bulk string accepted from a client, matching the `proto-max-bulk-len` default.
+const MAX_BULK_LENGTH: i64 = 512 * 1024 * 1024;
+/// Header lines longer than these cannot hold a valid length and are rejected early.
+const MAX_MULTIBULK_LENGTH_SIZE: usize = 64 * 1024;
+const MAX_BULK_LENGTH_SIZE: usize = 64 * 1024;
+pub const OK: &str = "OK";
 
 #[derive(Debug, EnumIter)]
 #[derive(PartialEq)]
@@ -31,124 +38,212 @@ impl DataType {
     } 
 }
 
-pub struct Command<'a> {
-    name: Option<&'a str>,
-    args: Vec<&'a str>
+pub struct Command {
+    name: Vec<u8>,
+    args: Vec<Vec<u8>>
 }
 
-impl<'a> Command<'a> {
-    pub fn new() -> Command<'a> {
-        Command {
-            name: None,
-            args: Vec::new()
-        }
+impl Command {
+    /// Run the command and build the reply, turning any failure into an error reply.
+    pub fn execute(&self) -> RespValue {
+        let name = String::from_utf8_lossy(&self.name);
+        let Ok(command_type) = CommandType::from_str(&name.to_uppercase()) else {
+            return RedisError::UnknownCommand {
+                name: name.into_owned(),
+                args: self.args.iter().map(|arg| String::from_utf8_lossy(arg).into_owned()).collect(),
+            }.into();
+        };
+        command_type.execute(&self.args).unwrap_or_else(RespValue::from)
     }
+}
 
-    pub fn set_name(&mut self, name: &'a str) {
-        self.name = Some(name);
-    }
+fn get_message_data_type(message: &[u8]) -> Option<DataType> {
+    DataType::iter().find(|data_type| message.first() == Some(&(data_type.value() as u8)))
+}
 
-    pub fn add_arg(&mut self, arg: &'a str) {
-        self.args.push(arg);
+/// Streaming decoder for commands sent as RESP arrays of bulk strings.
+/// Bytes are fed in as they arrive from the socket; the decoder remembers how far into the
+/// current command it got, so a frame split across any number of reads is picked up where it left off.
+pub struct CommandDecoder {
+    buffer: Vec<u8>,
+    /// Index of the first byte in `buffer` that has not been consumed yet.
+    position: usize,
+    /// Number of bulk strings the command being decoded is made of, once its header was read.
+    array_length: Option<usize>,
+    /// Length of the bulk string being decoded, once its header was read.
+    bulk_length: Option<usize>,
+    parts: Vec<Vec<u8>>,
+}
+
+impl CommandDecoder {
+    pub fn new() -> CommandDecoder {
+        CommandDecoder {
+            buffer: Vec::new(),
+            position: 0,
+            array_length: None,
+            bulk_length: None,
+            parts: Vec::new(),
+        }
     }
 
-    pub fn execute(&mut self) -> String {
-        CommandType::from_str(
-            &self.name.expect("Command cannot be null").to_uppercase()
-        ).expect(&format!("Unknown command: {}", self.name.unwrap())).execute(&self.args)
+    /// Append bytes received from the client.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        // Drop everything already decoded so the buffer does not grow with the connection's lifetime.
+        self.buffer.drain(..self.position);
+        self.position = 0;
+        self.buffer.extend_from_slice(bytes);
     }
-}
 
-fn deserialize_integer(message: &str) -> i64 {
-    return message.parse::<i64>()
-        .expect("Received an invalid int message")
-}
+    /// Decode the next command from the buffered bytes.
+    /// Returns `Ok(None)` when more data is needed to complete the command.
+    pub fn decode_command(&mut self) -> Result<Option<Command>, RedisError> {
+        loop {
+            let array_length = match self.array_length {
+                Some(array_length) => array_length,
+                None => {
+                    let Some(header) = self.read_header(DataType::Array, MAX_MULTIBULK_LENGTH_SIZE)? else {
+                        return Ok(None);
+                    };
+                    // Redis silently ignores empty and null arrays in place of a command.
+                    if header <= 0 {
+                        continue;
+                    }
+                    if header > MAX_MULTIBULK_LENGTH {
+                        return Err(RedisError::Protocol("invalid multibulk length".to_owned()));
+                    }
+                    self.array_length = Some(header as usize);
+                    header as usize
+                }
+            };
 
-fn get_message_data_type(message: &String) -> Option<DataType> {
-    for data_type in DataType::iter() {
-        if message.starts_with(data_type.value()) {
-            return Some(data_type)
+            while self.parts.len() < array_length {
+                let bulk_length = match self.bulk_length {
+                    Some(bulk_length) => bulk_length,
+                    None => {
+                        let Some(header) = self.read_header(DataType::BulkString, MAX_BULK_LENGTH_SIZE)? else {
+                            return Ok(None);
+                        };
+                        if !(0..=MAX_BULK_LENGTH).contains(&header) {
+                            return Err(RedisError::Protocol("invalid bulk length".to_owned()));
+                        }
+                        self.bulk_length = Some(header as usize);
+                        header as usize
+                    }
+                };
+
+                let end = self.position + bulk_length;
+                if self.buffer.len() < end + LINE_TERMINATOR.len() {
+                    return Ok(None);
+                }
+                if &self.buffer[end..end + LINE_TERMINATOR.len()] != LINE_TERMINATOR.as_bytes() {
+                    return Err(RedisError::Protocol("bulk string is not terminated by CRLF".to_owned()));
+                }
+                self.parts.push(self.buffer[self.position..end].to_vec());
+                self.position = end + LINE_TERMINATOR.len();
+                self.bulk_length = None;
+            }
+
+            self.array_length = None;
+            let mut parts = std::mem::take(&mut self.parts).into_iter();
+            let command = Command {
+                name: parts.next().unwrap_or_default(),
+                args: parts.collect(),
+            };
+            log::trace!("Command: {}", String::from_utf8_lossy(&command.name));
+            for argument in &command.args {
+                log::trace!("Args: {}", String::from_utf8_lossy(argument));
+            }
+            return Ok(Some(command));
         }
     }
-    None
-}
 
-/// Decode an incoming string command and write the output into the given Command struct.
-pub fn decode_command<'a>(command: &'a String, result: &mut Command<'a>) -> String {
-    // Command arrives as an array of bulk strings.
-    let data_type = get_message_data_type(command)
-        .expect("Invalid message type");
-    assert_eq!(data_type, DataType::Array);
-    assert!(command.len() > 1);
-
-    let mut bulk_strings = command[1..].split(LINE_TERMINATOR);
-
-    let array_length = deserialize_integer(
-        bulk_strings
-        .next()
-        .expect("Could not find first command")
-    );
-    log::trace!("Command length: {}", array_length);
-
-    // Get the command body - second element
-    // Push ahead by 1 + (length size) + (line terminator size)
-    let mut command_body = &command[
-        (2 + f32::floor(f32::log10(array_length as f32)) as usize + LINE_TERMINATOR.len())..
-    ];
-    for index in 0..array_length {
-        let data_type = get_message_data_type(&String::from(command_body))
-            .expect("Invalid message type");
-        assert_eq!(data_type, DataType::BulkString);
-        let string_length = deserialize_integer(
-            command_body[1..].split(LINE_TERMINATOR).next()
-            .expect("Could not find string length in bulk string")
-        );
-        // Push ahead by 1 + (length size) + (line terminator size)
-        // not sure why I'm using 2
-        command_body = &command_body[
-            (2 + f32::floor(f32::log10(string_length as f32)) as usize + LINE_TERMINATOR.len())..
-        ];
-        if index == 0 {
-            result.set_name(&command_body[..string_length as usize]);
-        } else {
-            result.add_arg(&command_body[..string_length as usize]);
+    /// Read a `<type><integer>\r\n` header line, advancing past it once it is complete.
+    fn read_header(&mut self, expected: DataType, max_size: usize) -> Result<Option<i64>, RedisError> {
+        let remaining = &self.buffer[self.position..];
+        if remaining.is_empty() {
+            return Ok(None);
         }
-
-        // Push ahead by length size + line terminator size
-        command_body = &command_body[string_length as usize + LINE_TERMINATOR.len()..];
-    }
-    log::trace!("Command: {}", result.name.expect("Command should not be None"));
-    for argument in &result.args {
-        log::trace!("Args: {}", argument);
+        if get_message_data_type(remaining).as_ref() != Some(&expected) {
+            return Err(RedisError::Protocol(format!(
+                "expected '{}', got '{}'", expected.value(), remaining[0] as char
+            )));
+        }
+        let Some(line_length) = remaining
+            .windows(LINE_TERMINATOR.len())
+            .position(|window| window == LINE_TERMINATOR.as_bytes()) else {
+            if remaining.len() > max_size {
+                return Err(RedisError::Protocol("too big count string".to_owned()));
+            }
+            return Ok(None);
+        };
+        let value = std::str::from_utf8(&remaining[1..line_length])
+            .ok()
+            .and_then(|text| text.parse::<i64>().ok());
+        let Some(value) = value else {
+            return Err(RedisError::Protocol(match expected {
+                DataType::Array => "invalid multibulk length".to_owned(),
+                _ => "invalid bulk length".to_owned(),
+            }));
+        };
+        self.position += line_length + LINE_TERMINATOR.len();
+        Ok(Some(value))
     }
-    // Return remaining string
-    command_body.to_string()
 }
 
-/// Encode a string as the Redis Simple String type.
-pub fn encode_simple_string(response: String) -> String {
-    format!("{}{}{}", DataType::SimpleString.value(), response, LINE_TERMINATOR)
+/// A value that can be sent back to the client.
+/// Command handlers build these and the connection serializes them with `write_to`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RespValue {
+    SimpleString(String),
+    Error(String),
+    Integer(i64),
+    BulkString(Vec<u8>),
+    /// The null bulk string, `$-1`, used for missing keys.
+    NullBulkString,
+    /// The null array, `*-1`, used e.g. when a blocking command times out.
+    NullArray,
+    Array(Vec<RespValue>),
 }
 
-/// Encode a bulk string as the Redis Bulk String type.
-pub fn encode_bulk_string(response: String) -> String {
-    format!("{}{}{}{}{}",
-        DataType::BulkString.value(),
-        response.len(),
-        LINE_TERMINATOR,
-        response,
-        LINE_TERMINATOR
-    )
+impl RespValue {
+    /// The `+OK` reply.
+    pub fn ok() -> RespValue {
+        RespValue::SimpleString(OK.to_owned())
+    }
+
+    /// Build a bulk string reply from anything that can be viewed as bytes.
+    pub fn bulk_string(value: impl AsRef<[u8]>) -> RespValue {
+        RespValue::BulkString(value.as_ref().to_vec())
+    }
+
+    /// Serialize this value, including any nested values, at the end of the given buffer.
+    pub fn write_to(&self, buffer: &mut Vec<u8>) {
+        match self {
+            RespValue::SimpleString(value) => write_line(buffer, DataType::SimpleString, value),
+            RespValue::Error(message) => write_line(buffer, DataType::Error, message),
+            RespValue::Integer(value) => write_line(buffer, DataType::Integer, &value.to_string()),
+            RespValue::BulkString(value) => {
+                write_line(buffer, DataType::BulkString, &value.len().to_string());
+                buffer.extend_from_slice(value);
+                buffer.extend_from_slice(LINE_TERMINATOR.as_bytes());
+            }
+            RespValue::NullBulkString => write_line(buffer, DataType::BulkString, "-1"),
+            RespValue::NullArray => write_line(buffer, DataType::Array, "-1"),
+            RespValue::Array(items) => {
+                write_line(buffer, DataType::Array, &items.len().to_string());
+                for item in items {
+                    item.write_to(buffer);
+                }
+            }
+        }
+    }
 }
 
-/// Encode an array of Strings as the Redis Array type.
-pub fn encode_array(response: Vec<String>) -> String {
-    format!("{}{}{}{}",
-        DataType::Array.value(),
-        response.len(),
-        LINE_TERMINATOR,
-        response.iter().map(|item| encode_bulk_string(item.to_string())).collect::<Vec<String>>().join(""),
-    )
+/// Write a `<type><content>\r\n` line.
+fn write_line(buffer: &mut Vec<u8>, data_type: DataType, content: &str) {
+    buffer.push(data_type.value() as u8);
+    buffer.extend_from_slice(content.as_bytes());
+    buffer.extend_from_slice(LINE_TERMINATOR.as_bytes());
 }
 
 #[cfg(test)]
@@ -158,8 +253,8 @@ mod tests {
 
     #[test] 
     fn test_get_message_data_type() {
-        assert_eq!(get_message_data_type(&String::from("+test")), Some(DataType::SimpleString));
-        assert_eq!(get_message_data_type(&String::from("sedx")), None);
+        assert_eq!(get_message_data_type(b"+test"), Some(DataType::SimpleString));
+        assert_eq!(get_message_data_type(b"sedx"), None);
     }
 
 
@@ -167,17 +262,79 @@ mod tests {
     #[case("*1\r\n$4\r\nping\r\n", Some("ping"), 0)]
     #[case("*2\r\n$4\r\necho\r\n$11\r\nhello world\r\n", Some("echo"), 1)]
     #[case("*2\r\n$3\r\nget\r\n$3\r\nkey\r\n", Some("get"), 1)]
+    #[case("*3\r\n$3\r\nset\r\n$10\r\n0123456789\r\n$0\r\n\r\n", Some("set"), 2)]
+    #[case("*2\r\n$3\r\nget\r\n$3\r\nke", None, 0)]
     fn test_decode_command(#[case] input: &str, #[case] expected_command_name: Option<&str>, #[case] expected_arg_length: usize) {
-        let input = &String::from(input);
-        let command = &mut Command::new();
-        decode_command(input, command);
+        let mut decoder = CommandDecoder::new();
+        decoder.feed(input.as_bytes());
+        let command = decoder.decode_command().unwrap();
 
         if expected_command_name.is_none() {
-            assert!(command.name.is_none());
+            assert!(command.is_none());
         } else {
-            assert!(command.name.is_some());
-            assert_eq!(command.name, expected_command_name);
+            let command = command.unwrap();
+            assert_eq!(Some(command.name.as_slice()), expected_command_name.map(str::as_bytes));
+            assert_eq!(command.args.len(), expected_arg_length);
+        }
+    }
+
+    #[test]
+    fn test_decode_command_split_across_reads() {
+        let input = b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$100\r\n".iter()
+            .chain([b'x'; 100].iter())
+            .chain(b"\r\n*1\r\n$4\r\nping\r\n".iter())
+            .copied()
+            .collect::<Vec<u8>>();
+        let mut decoder = CommandDecoder::new();
+        let mut commands = Vec::new();
+        for byte in input {
+            decoder.feed(&[byte]);
+            while let Some(command) = decoder.decode_command().unwrap() {
+                commands.push(command);
+            }
         }
-        assert_eq!(command.args.len(), expected_arg_length);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].args, vec![b"key".to_vec(), vec![b'x'; 100]]);
+        assert_eq!(commands[1].name, b"ping");
+    }
+
+    #[test]
+    fn test_decode_command_binary_safe() {
+        let mut decoder = CommandDecoder::new();
+        decoder.feed(b"*3\r\n$3\r\nset\r\n$2\r\n\xff\xfe\r\n$4\r\n\x00\r\n\x1f\r\n");
+        let command = decoder.decode_command().unwrap().unwrap();
+        assert_eq!(command.args, vec![vec![0xff, 0xfe], vec![0x00, b'\r', b'\n', 0x1f]]);
+    }
+
+    #[rstest]
+    #[case("PING\r\n", "expected '*', got 'P'")]
+    #[case("*x\r\n", "invalid multibulk length")]
+    #[case("*1\r\n+ping\r\n", "expected '$', got '+'")]
+    #[case("*1\r\n$-3\r\n", "invalid bulk length")]
+    #[case("*1\r\n$4\r\npingxx", "bulk string is not terminated by CRLF")]
+    fn test_decode_command_protocol_error(#[case] input: &str, #[case] expected_error: &str) {
+        let mut decoder = CommandDecoder::new();
+        decoder.feed(input.as_bytes());
+        assert_eq!(decoder.decode_command().err(), Some(RedisError::Protocol(expected_error.to_owned())));
+    }
+
+    #[rstest]
+    #[case(RespValue::ok(), "+OK\r\n")]
+    #[case(RespValue::Error("ERR bad".to_owned()), "-ERR bad\r\n")]
+    #[case(RespValue::Integer(-42), ":-42\r\n")]
+    #[case(RespValue::bulk_string(""), "$0\r\n\r\n")]
+    #[case(RespValue::NullBulkString, "$-1\r\n")]
+    #[case(RespValue::NullArray, "*-1\r\n")]
+    #[case(
+        RespValue::Array(vec![
+            RespValue::bulk_string("0"),
+            RespValue::Array(vec![RespValue::bulk_string("key"), RespValue::Integer(1)]),
+        ]),
+        "*2\r\n$1\r\n0\r\n*2\r\n$3\r\nkey\r\n:1\r\n"
+    )]
+    fn test_write_to(#[case] value: RespValue, #[case] expected: &str) {
+        let mut buffer = Vec::new();
+        value.write_to(&mut buffer);
+        assert_eq!(String::from_utf8(buffer).unwrap(), expected);
     }
 }
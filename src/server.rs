@@ -1,55 +1,167 @@
-
 use std::{
-    io::{prelude::*, BufReader},
-    net::{TcpListener, TcpStream}
+    cell::RefCell,
+    io::{prelude::*, ErrorKind},
+    net::{TcpListener, TcpStream},
+    thread,
 };
-use threadpool::ThreadPool;
-use crate::serialization::{decode_command, Command};
+use crate::config::global_config_get;
+use crate::serialization::{CommandDecoder, RespValue};
+use crate::stats;
+
 
+const READ_CHUNK_SIZE: usize = 16 * 1024;
 
-const THREAD_COUNT: usize = 5;
+thread_local! {
+    /// The connection served by the current thread, so blocking commands can tell when it goes away.
+    static CONNECTION: RefCell<Option<TcpStream>> = const { RefCell::new(None) };
+}
 
 
-/// Start the Redis multithreaded server.
+/// Start the Redis multithreaded server, listening on every address of the `bind` config at `port`.
+/// Every client gets its own thread for as long as its connection stays open.
 pub fn start_server() {
-    let listener = TcpListener::bind("0.0.0.0:6379").unwrap();
+    let port = global_config_get("port").and_then(|port| port.parse::<u16>().ok()).unwrap_or(6379);
+    let bind = global_config_get("bind").unwrap_or_default();
+    let listeners = bind_listeners(&bind, port);
+    if listeners.is_empty() {
+        log::error!("Failed listening on port {}, aborting.", port);
+        std::process::exit(1);
+    }
 
-    let thread_pool = ThreadPool::new(THREAD_COUNT);
+    let accept_threads = listeners
+        .into_iter()
+        .map(|listener| thread::spawn(move || accept_connections(listener)))
+        .collect::<Vec<_>>();
+    for accept_thread in accept_threads {
+        let _ = accept_thread.join();
+    }
+}
 
+/// Bind a listener per address of a `bind` config value.
+/// Like in Redis `*` stands for every IPv4 address, `::*` for every IPv6 address, and addresses
+/// prefixed with `-` are optional: failing to bind them is not an error.
+fn bind_listeners(bind: &str, port: u16) -> Vec<TcpListener> {
+    let mut listeners = Vec::new();
+    for address in bind.split_whitespace() {
+        let (optional, address) = match address.strip_prefix('-') {
+            Some(address) => (true, address),
+            None => (false, address),
+        };
+        let host = match address {
+            "*" => "0.0.0.0",
+            "::*" => "::",
+            host => host,
+        };
+        match TcpListener::bind((host, port)) {
+            Ok(listener) => {
+                log::info!("Listening on {}:{}", host, port);
+                listeners.push(listener);
+            }
+            Err(error) if optional => log::debug!("Skipping optional address {}:{}: {}", host, port, error),
+            Err(error) => {
+                log::error!("Could not create server TCP listening socket {}:{}: {}", host, port, error);
+                std::process::exit(1);
+            }
+        }
+    }
+    listeners
+}
+
+/// Accept clients on a listener, turning them away once `maxclients` are connected.
+fn accept_connections(listener: TcpListener) {
     for stream in listener.incoming() {
-        let stream = stream.unwrap();
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                log::warn!("Failed to accept connection: {}", error);
+                continue;
+            }
+        };
+
+        let max_clients = global_config_get("maxclients").and_then(|value| value.parse::<u64>().ok()).unwrap_or(10000);
+        if stats::read(&stats::CONNECTED_CLIENTS) >= max_clients {
+            stats::increment(&stats::REJECTED_CONNECTIONS, 1);
+            let _ = stream.write_all(b"-ERR max number of clients reached\r\n");
+            continue;
+        }
 
-        thread_pool.execute(|| {
+        stats::increment(&stats::TOTAL_CONNECTIONS_RECEIVED, 1);
+        stats::increment(&stats::CONNECTED_CLIENTS, 1);
+        thread::spawn(|| {
             handle_connection(stream);
+            stats::decrement(&stats::CONNECTED_CLIENTS);
         });
     }
 }
 
-/// Response to a TCP connection with the correct Redis command response.
+/// Serve a client connection until the peer closes it.
+/// Bytes are fed to a streaming decoder so that commands split over several TCP packets are
+/// only executed once complete, and every complete command is answered in order.
 fn handle_connection(mut stream: TcpStream) {
-    let mut buf_reader = BufReader::new(&mut stream);
-    let http_request: Vec<_> = buf_reader.fill_buf().unwrap().to_vec();
-
-    buf_reader.consume(http_request.len());
-
-    let raw_command = String::from_utf8(http_request.clone()).expect("Could not decode to utf8");
-    let mut response_list: Vec<String> = Vec::new();
-    let mut remaining_body = raw_command;
-
-    // WARNING! This behavior is incorrect.
-    // Multiple commands should output as a merged array - this returns separate arrays for each command.
-    while remaining_body.len() > 0 {
-        let command: &mut Command = &mut Command::new();
-        let old_body = remaining_body.clone();
-        remaining_body = decode_command(
-            &old_body,
-            command
-        );
-        // let response = "+PONG\r\n";
-        let response = command.execute();
-        response_list.push(response);
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    log::debug!("Client connected: {}", peer);
+    CONNECTION.with(|connection| *connection.borrow_mut() = stream.try_clone().ok());
+
+    let mut decoder = CommandDecoder::new();
+    let mut chunk = [0u8; READ_CHUNK_SIZE];
+    loop {
+        let read = match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) => {
+                log::debug!("Read from {} failed: {}", peer, error);
+                break;
+            }
+        };
+        decoder.feed(&chunk[..read]);
+
+        let mut response: Vec<u8> = Vec::new();
+        let mut protocol_error = None;
+        loop {
+            match decoder.decode_command() {
+                Ok(Some(command)) => command.execute().write_to(&mut response),
+                Ok(None) => break,
+                Err(error) => {
+                    RespValue::from(error.clone()).write_to(&mut response);
+                    protocol_error = Some(error);
+                    break;
+                }
+            }
+        }
+
+        if !response.is_empty() {
+            log::debug!("Response: {:#?}", String::from_utf8_lossy(&response));
+            if let Err(error) = stream.write_all(&response) {
+                log::debug!("Write to {} failed: {}", peer, error);
+                break;
+            }
+        }
+        if let Some(error) = protocol_error {
+            log::debug!("Closing connection to {}: {}", peer, error);
+            break;
+        }
     }
-    stream.write_all(response_list.join("").as_bytes()).unwrap();
-    log::debug!("Request: {:#?}", String::from_utf8(http_request));
-    log::debug!("Response: {:#?}", response_list.join(""));
+    log::debug!("Client disconnected: {}", peer);
+}
+
+/// Whether the client served by the current thread closed its connection.
+/// Only meant to be called while a command is running, when nothing else reads from the connection.
+pub fn client_disconnected() -> bool {
+    CONNECTION.with(|connection| {
+        let connection = connection.borrow();
+        let Some(stream) = connection.as_ref() else {
+            return false;
+        };
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        // Peeking leaves pipelined commands in place for the read loop.
+        let mut byte = [0u8; 1];
+        let disconnected = match stream.peek(&mut byte) {
+            Ok(read) => read == 0,
+            Err(error) => error.kind() != ErrorKind::WouldBlock,
+        };
+        let _ = stream.set_nonblocking(false);
+        disconnected
+    })
 }
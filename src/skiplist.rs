@@ -0,0 +1,329 @@
+use std::cmp::Ordering;
+
+use crate::random::random_u64;
+
+/// Most levels a node can have, plenty for any number of elements with a 1/4 promotion probability.
+const MAX_LEVEL: usize = 32;
+
+/// The head node, which holds no element and links to the first node at every level.
+const HEAD: NodeId = 0;
+
+/// Index of a node in the skiplist arena, valid until the node is removed.
+pub type NodeId = usize;
+
+#[derive(Clone, Copy)]
+struct Level {
+    forward: Option<NodeId>,
+    /// Number of elements the forward link skips over, counting the one it lands on.
+    span: usize,
+}
+
+#[derive(Clone)]
+struct Node {
+    member: Vec<u8>,
+    score: f64,
+    backward: Option<NodeId>,
+    levels: Vec<Level>,
+}
+
+/// Members ordered by score, and by member for equal scores, like the Redis zskiplist.
+/// Links record how many elements they skip, so finding the rank of an element or the element at a
+/// rank takes O(log n) like the lookups by score or member do.
+/// Nodes live in an arena and refer to each other by index, the slots of removed nodes being reused.
+#[derive(Clone)]
+pub struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<NodeId>,
+    tail: Option<NodeId>,
+    /// Number of levels in use by at least one node, at least 1.
+    level: usize,
+    length: usize,
+}
+
+impl Default for SkipList {
+    fn default() -> SkipList {
+        let head = Node {
+            member: Vec::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![Level { forward: None, span: 0 }; MAX_LEVEL],
+        };
+        SkipList { nodes: vec![head], free: Vec::new(), tail: None, level: 1, length: 0 }
+    }
+}
+
+fn compare(score: f64, member: &[u8], other_score: f64, other_member: &[u8]) -> Ordering {
+    score.partial_cmp(&other_score).expect("Scores are never NaN").then_with(|| member.cmp(other_member))
+}
+
+/// Level of a new node: each level is reached by a quarter of the nodes of the level below.
+fn random_level() -> usize {
+    let mut level = 1;
+    while level < MAX_LEVEL && random_u64().is_multiple_of(4) {
+        level += 1;
+    }
+    level
+}
+
+impl SkipList {
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn score(&self, node: NodeId) -> f64 {
+        self.nodes[node].score
+    }
+
+    pub fn member(&self, node: NodeId) -> &[u8] {
+        &self.nodes[node].member
+    }
+
+    pub fn first(&self) -> Option<NodeId> {
+        self.nodes[HEAD].levels[0].forward
+    }
+
+    pub fn last(&self) -> Option<NodeId> {
+        self.tail
+    }
+
+    pub fn next(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node].levels[0].forward
+    }
+
+    pub fn previous(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node].backward
+    }
+
+    /// Walk the list from `from`, towards the end or towards the start if `reverse` is set.
+    pub fn walk(&self, from: Option<NodeId>, reverse: bool) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(from, move |node| if reverse { self.previous(*node) } else { self.next(*node) })
+    }
+
+    /// Go down the levels from the head, moving forward on each as long as `before` holds for the next node.
+    /// Returns for each level the last node reached and its rank counting from 1, the head being 0.
+    /// `before` must hold for a prefix of the list and not after it.
+    fn search(&self, before: impl Fn(f64, &[u8]) -> bool) -> ([NodeId; MAX_LEVEL], [usize; MAX_LEVEL]) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut node = HEAD;
+        let mut traversed = 0;
+        for level in (0..self.level).rev() {
+            while let Some(next) = self.nodes[node].levels[level].forward {
+                if !before(self.nodes[next].score, &self.nodes[next].member) {
+                    break;
+                }
+                traversed += self.nodes[node].levels[level].span;
+                node = next;
+            }
+            update[level] = node;
+            rank[level] = traversed;
+        }
+        (update, rank)
+    }
+
+    /// The first node for which `before` does not hold, with its rank counting from 0.
+    pub fn first_from(&self, before: impl Fn(f64, &[u8]) -> bool) -> Option<(usize, NodeId)> {
+        let (update, rank) = self.search(before);
+        self.nodes[update[0]].levels[0].forward.map(|node| (rank[0], node))
+    }
+
+    /// The last node for which `within` holds, with its rank counting from 0.
+    pub fn last_within(&self, within: impl Fn(f64, &[u8]) -> bool) -> Option<(usize, NodeId)> {
+        let (update, rank) = self.search(within);
+        (update[0] != HEAD).then(|| (rank[0] - 1, update[0]))
+    }
+
+    /// The rank of the element counting from 0, if it is in the list.
+    pub fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let (rank, node) = self.last_within(|other_score, other_member| {
+            compare(other_score, other_member, score, member) != Ordering::Greater
+        })?;
+        (self.nodes[node].member == member).then_some(rank)
+    }
+
+    /// The node at the rank counting from 0.
+    pub fn get_by_rank(&self, rank: usize) -> Option<NodeId> {
+        if rank >= self.length {
+            return None;
+        }
+        let target = rank + 1;
+        let mut node = HEAD;
+        let mut traversed = 0;
+        for level in (0..self.level).rev() {
+            while let Some(next) = self.nodes[node].levels[level].forward {
+                if traversed + self.nodes[node].levels[level].span > target {
+                    break;
+                }
+                traversed += self.nodes[node].levels[level].span;
+                node = next;
+            }
+            if traversed == target {
+                return Some(node);
+            }
+        }
+        None
+    }
+
+    /// Add an element, which must not be in the list already.
+    pub fn insert(&mut self, score: f64, member: Vec<u8>) {
+        let (mut update, mut rank) =
+            self.search(|other_score, other_member| compare(other_score, other_member, score, &member).is_lt());
+        let level = random_level();
+        for new_level in self.level..level {
+            update[new_level] = HEAD;
+            rank[new_level] = 0;
+            self.nodes[HEAD].levels[new_level].span = self.length;
+        }
+        self.level = self.level.max(level);
+
+        let node = Node { member, score, backward: None, levels: vec![Level { forward: None, span: 0 }; level] };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        for (level, previous) in update.iter().copied().enumerate().take(level) {
+            let skipped = rank[0] - rank[level];
+            self.nodes[id].levels[level] = Level {
+                forward: self.nodes[previous].levels[level].forward,
+                span: self.nodes[previous].levels[level].span - skipped,
+            };
+            self.nodes[previous].levels[level] = Level { forward: Some(id), span: skipped + 1 };
+        }
+        // Links passing over the new node at the levels it does not reach now skip one more element.
+        for (level, previous) in update.iter().copied().enumerate().take(self.level).skip(level) {
+            self.nodes[previous].levels[level].span += 1;
+        }
+
+        self.nodes[id].backward = Some(update[0]).filter(|previous| *previous != HEAD);
+        match self.nodes[id].levels[0].forward {
+            Some(next) => self.nodes[next].backward = Some(id),
+            None => self.tail = Some(id),
+        }
+        self.length += 1;
+    }
+
+    /// Remove an element, returning false if it was not in the list.
+    pub fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let (update, _) =
+            self.search(|other_score, other_member| compare(other_score, other_member, score, member).is_lt());
+        match self.nodes[update[0]].levels[0].forward {
+            Some(node) if self.nodes[node].member == member && self.nodes[node].score == score => {
+                self.unlink(node, &update);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Change the score of an element that is in the list.
+    pub fn update_score(&mut self, score: f64, member: &[u8], new_score: f64) {
+        let (update, _) =
+            self.search(|other_score, other_member| compare(other_score, other_member, score, member).is_lt());
+        let node = self.nodes[update[0]].levels[0].forward.expect("The element is in the list");
+        // Keep the node where it is if the new score does not change its position.
+        let after_previous = self.nodes[node]
+            .backward
+            .is_none_or(|previous| compare(self.nodes[previous].score, &self.nodes[previous].member, new_score, member).is_lt());
+        let before_next = self.nodes[node].levels[0]
+            .forward
+            .is_none_or(|next| compare(self.nodes[next].score, &self.nodes[next].member, new_score, member).is_gt());
+        if after_previous && before_next {
+            self.nodes[node].score = new_score;
+            return;
+        }
+        let member = std::mem::take(&mut self.nodes[node].member);
+        self.unlink(node, &update);
+        self.insert(new_score, member);
+    }
+
+    fn unlink(&mut self, node: NodeId, update: &[NodeId; MAX_LEVEL]) {
+        for (level, previous) in update.iter().copied().enumerate().take(self.level) {
+            if self.nodes[previous].levels[level].forward == Some(node) {
+                let Level { forward, span } = self.nodes[node].levels[level];
+                self.nodes[previous].levels[level].span += span;
+                self.nodes[previous].levels[level].span -= 1;
+                self.nodes[previous].levels[level].forward = forward;
+            } else {
+                self.nodes[previous].levels[level].span -= 1;
+            }
+        }
+        let backward = self.nodes[node].backward;
+        match self.nodes[node].levels[0].forward {
+            Some(next) => self.nodes[next].backward = backward,
+            None => self.tail = backward,
+        }
+        while self.level > 1 && self.nodes[HEAD].levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+        self.length -= 1;
+        self.nodes[node].member = Vec::new();
+        self.nodes[node].levels = Vec::new();
+        self.free.push(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(list: &SkipList) -> Vec<Vec<u8>> {
+        list.walk(list.first(), false).map(|node| list.member(node).to_vec()).collect()
+    }
+
+    #[test]
+    fn test_ranks_stay_consistent() {
+        let mut list = SkipList::default();
+        for index in 0..500 {
+            list.insert((index % 50) as f64, format!("member{:03}", index).into_bytes());
+        }
+        for index in (0..500).step_by(3) {
+            assert!(list.remove((index % 50) as f64, format!("member{:03}", index).as_bytes()));
+        }
+        assert!(!list.remove(0.0, b"member000"));
+
+        let ordered = members(&list);
+        assert_eq!(ordered.len(), list.len());
+        for (rank, member) in ordered.iter().enumerate() {
+            let node = list.get_by_rank(rank).unwrap();
+            assert_eq!(list.member(node), member.as_slice());
+            assert_eq!(list.rank(list.score(node), member), Some(rank));
+        }
+        assert_eq!(list.get_by_rank(list.len()), None);
+        let mut reversed = list.walk(list.last(), true).map(|node| list.member(node).to_vec()).collect::<Vec<_>>();
+        reversed.reverse();
+        assert_eq!(reversed, ordered);
+    }
+
+    #[test]
+    fn test_update_score() {
+        let mut list = SkipList::default();
+        for (score, member) in [(1.0, "a"), (2.0, "b"), (3.0, "c")] {
+            list.insert(score, member.as_bytes().to_vec());
+        }
+        list.update_score(2.0, b"b", 2.5);
+        list.update_score(1.0, b"a", 10.0);
+        assert_eq!(members(&list), vec![b"b".to_vec(), b"c".to_vec(), b"a".to_vec()]);
+        assert_eq!(list.rank(10.0, b"a"), Some(2));
+        assert_eq!(list.rank(1.0, b"a"), None);
+    }
+
+    #[test]
+    fn test_range_lookups() {
+        let mut list = SkipList::default();
+        for score in 0..10 {
+            list.insert(score as f64, score.to_string().into_bytes());
+        }
+        let (rank, node) = list.first_from(|score, _| score < 3.5).unwrap();
+        assert_eq!((rank, list.score(node)), (4, 4.0));
+        let (rank, node) = list.last_within(|score, _| score <= 7.0).unwrap();
+        assert_eq!((rank, list.score(node)), (7, 7.0));
+        assert!(list.first_from(|score, _| score < 100.0).is_none());
+        assert!(list.last_within(|score, _| score < 0.0).is_none());
+    }
+}
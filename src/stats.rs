@@ -0,0 +1,33 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Server wide counters reported by INFO, cleared by CONFIG RESETSTAT.
+pub static TOTAL_CONNECTIONS_RECEIVED: AtomicU64 = AtomicU64::new(0);
+pub static TOTAL_COMMANDS_PROCESSED: AtomicU64 = AtomicU64::new(0);
+pub static REJECTED_CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+pub static EXPIRED_KEYS: AtomicU64 = AtomicU64::new(0);
+pub static EVICTED_KEYS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of clients connected right now. Not a counter, so CONFIG RESETSTAT leaves it alone.
+pub static CONNECTED_CLIENTS: AtomicU64 = AtomicU64::new(0);
+/// Number of clients waiting in a blocking command right now.
+pub static BLOCKED_CLIENTS: AtomicU64 = AtomicU64::new(0);
+
+pub fn increment(counter: &AtomicU64, by: u64) {
+    counter.fetch_add(by, Ordering::Relaxed);
+}
+
+pub fn decrement(counter: &AtomicU64) {
+    counter.fetch_sub(1, Ordering::Relaxed);
+}
+
+pub fn read(counter: &AtomicU64) -> u64 {
+    counter.load(Ordering::Relaxed)
+}
+
+/// Reset every counter to zero.
+pub fn reset_stats() {
+    let counters = [&TOTAL_CONNECTIONS_RECEIVED, &TOTAL_COMMANDS_PROCESSED, &REJECTED_CONNECTIONS, &EXPIRED_KEYS, &EVICTED_KEYS];
+    for counter in counters {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
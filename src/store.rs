@@ -1,253 +1,1094 @@
-
 use lazy_static::lazy_static;
-use regex::Regex;
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hasher};
+use std::sync::mpsc::{channel, Sender};
 use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::blocking::{serve_ready_keys, BlockedClients};
+use crate::config::global_config_get;
+use crate::error::RedisError;
+use crate::maxmemory::Frequency;
+use crate::random::random_u64;
+use crate::skiplist::{NodeId, SkipList};
+use crate::stream::Stream;
+use crate::stats;
 
-use crate::serialization::NULL;
+/// Upper bound of the `databases` config. The databases are all allocated at startup, so that even a
+/// config file asking for billions of them can't exhaust memory before the server gets to run.
+pub const MAX_DATABASES: i32 = 1024;
 
 lazy_static! {
-    /// Global data storage for key/value pairs. Thread safe.
-    static ref HASHMAP: Mutex<HashMap<String, String>> = {
-        let m = HashMap::new();
-        Mutex::new(m)
+    /// Global data storage for key/value pairs: the logical databases, as many as the `databases` config
+    /// says. Thread safe.
+    /// Keys and values are raw bytes since clients may store arbitrary binary data.
+    static ref DATABASES: Mutex<Vec<Database>> = {
+        let count = global_config_get("databases").and_then(|value| value.parse().ok()).unwrap_or(16);
+        Mutex::new((0..count).map(|_| Database::new()).collect())
     };
 
-    /// Global configuration storage. Thread safe.
-    static ref CONFIG: Mutex<HashMap<String, String>> = {
-        let mut m = HashMap::new();
-        // Defaults
-        populate_config_defaults(&mut m);
-        Mutex::new(m)
+    /// Channel to the thread that drops values removed with UNLINK and friends.
+    static ref LAZY_FREE: Mutex<Sender<Box<dyn Send>>> = {
+        let (sender, receiver) = channel::<Box<dyn Send>>();
+        thread::Builder::new()
+            .name("lazy-free".to_owned())
+            .spawn(move || receiver.into_iter().for_each(drop))
+            .expect("Could not start the lazy free thread");
+        Mutex::new(sender)
     };
 }
 
-pub fn global_store_set(key: String, value: String) {
-    let mut hashmap = HASHMAP.lock().unwrap();
-    hashmap.insert(key, value);
-}
-
-pub fn global_store_get(key: String) -> String {
-    let hashmap = HASHMAP.lock().unwrap();
-    hashmap.get(&key).unwrap_or(&NULL.to_owned()).to_string()
-}
-
-// Not implemented.
-// pub fn global_config_set(key: String, value: String) {
-//     let mut hashmap = CONFIG.lock().unwrap();
-//     hashmap.insert(key, value);
-// }
-
-pub fn global_config_get(key: String) -> String {
-    let hashmap = CONFIG.lock().unwrap();
-    hashmap.get(&key).unwrap_or(&NULL.to_owned()).to_string()
-}
-
-pub fn global_config_get_keys(match_text: String) -> Vec<String> {
-    let hashmap = CONFIG.lock().unwrap();
-    if match_text.contains("*") {
-        let regex_to_match = Regex::new(&match_text).unwrap();
-        return hashmap.keys().filter(|key| regex_to_match.is_match(key)).map(|s| s.to_string()).collect();
-    }
-    hashmap.keys().find(|key| key.to_string() == match_text).into_iter().map(|x| x.to_string()).collect::<Vec<String>>()
-}
-
-/// Insert a bunch of config defaults based on with what a vanilla Redis server would respond.
-/// I know this is ugly. Sorry.
-fn populate_config_defaults(m: &mut HashMap<String, String>) {
-    m.insert("replica-read-only".to_owned(), "yes".to_owned());
-    m.insert("stream-node-max-bytes".to_owned(), "4096".to_owned());
-    m.insert("auto-aof-rewrite-percentage".to_owned(), "100".to_owned());
-    m.insert("bind-source-addr".to_owned(), "".to_owned());
-    m.insert("tls-client-key-file".to_owned(), "".to_owned());
-    m.insert("notify-keyspace-events".to_owned(), "".to_owned());
-    m.insert("set-max-intset-entries".to_owned(), "512".to_owned());
-    m.insert("slowlog-log-slower-than".to_owned(), "10000".to_owned());
-    m.insert("cluster-slave-no-failover".to_owned(), "no".to_owned());
-    m.insert("syslog-facility".to_owned(), "local0".to_owned());
-    m.insert("tls-cert-file".to_owned(), "".to_owned());
-    m.insert("masterauth".to_owned(), "".to_owned());
-    m.insert("repl-diskless-load".to_owned(), "disabled".to_owned());
-    m.insert("loglevel".to_owned(), "notice".to_owned());
-    m.insert("repl-ping-replica-period".to_owned(), "10".to_owned());
-    m.insert("tls-ca-cert-dir".to_owned(), "".to_owned());
-    m.insert("cluster-migration-barrier".to_owned(), "1".to_owned());
-    m.insert("proc-title-template".to_owned(), "{title} {listen-addr} {server-mode}".to_owned());
-    m.insert("cluster-config-file".to_owned(), "nodes.conf".to_owned());
-    m.insert("tls-ciphers".to_owned(), "".to_owned());
-    m.insert("replica-announce-ip".to_owned(), "".to_owned());
-    m.insert("rdbcompression".to_owned(), "yes".to_owned());
-    m.insert("cluster-announce-tls-port".to_owned(), "0".to_owned());
-    m.insert("tls-dh-params-file".to_owned(), "".to_owned());
-    m.insert("maxclients".to_owned(), "10000".to_owned());
-    m.insert("aof-load-truncated".to_owned(), "yes".to_owned());
-    m.insert("hll-sparse-max-bytes".to_owned(), "3000".to_owned());
-    m.insert("cluster-link-sendbuf-limit".to_owned(), "0".to_owned());
-    m.insert("maxmemory".to_owned(), "0".to_owned());
-    m.insert("bind".to_owned(), "* -::*".to_owned());
-    m.insert("aof-timestamp-enabled".to_owned(), "no".to_owned());
-    m.insert("hash-max-ziplist-entries".to_owned(), "512".to_owned());
-    m.insert("supervised".to_owned(), "no".to_owned());
-    m.insert("active-defrag-cycle-max".to_owned(), "25".to_owned());
-    m.insert("tls-client-key-file-pass".to_owned(), "".to_owned());
-    m.insert("masteruser".to_owned(), "".to_owned());
-    m.insert("zset-max-ziplist-value".to_owned(), "64".to_owned());
-    m.insert("list-max-ziplist-size".to_owned(), "-2".to_owned());
-    m.insert("repl-ping-slave-period".to_owned(), "10".to_owned());
-    m.insert("min-slaves-max-lag".to_owned(), "10".to_owned());
-    m.insert("oom-score-adj-values".to_owned(), "0 200 800".to_owned());
-    m.insert("tls-session-caching".to_owned(), "yes".to_owned());
-    m.insert("lfu-decay-time".to_owned(), "1".to_owned());
-    m.insert("timeout".to_owned(), "0".to_owned());
-    m.insert("min-replicas-to-write".to_owned(), "0".to_owned());
-    m.insert("maxmemory-clients".to_owned(), "0".to_owned());
-    m.insert("cluster-announce-human-nodename".to_owned(), "".to_owned());
-    m.insert("cluster-preferred-endpoint-type".to_owned(), "ip".to_owned());
-    m.insert("active-defrag-ignore-bytes".to_owned(), "104857600".to_owned());
-    m.insert("pidfile".to_owned(), "".to_owned());
-    m.insert("port".to_owned(), "6379".to_owned());
-    m.insert("slave-read-only".to_owned(), "yes".to_owned());
-    m.insert("maxmemory-policy".to_owned(), "noeviction".to_owned());
-    m.insert("tls-port".to_owned(), "0".to_owned());
-    m.insert("set-max-listpack-value".to_owned(), "64".to_owned());
-    m.insert("propagation-error-behavior".to_owned(), "ignore".to_owned());
-    m.insert("client-query-buffer-limit".to_owned(), "1073741824".to_owned());
-    m.insert("active-defrag-max-scan-fields".to_owned(), "1000".to_owned());
-    m.insert("tls-protocols".to_owned(), "".to_owned());
-    m.insert("oom-score-adj".to_owned(), "no".to_owned());
-    m.insert("proto-max-bulk-len".to_owned(), "536870912".to_owned());
-    m.insert("aof-use-rdb-preamble".to_owned(), "yes".to_owned());
-    m.insert("aof_rewrite_cpulist".to_owned(), "".to_owned());
-    m.insert("save".to_owned(), "3600 1 300 100 60 10000".to_owned());
-    m.insert("list-compress-depth".to_owned(), "0".to_owned());
-    m.insert("databases".to_owned(), "16".to_owned());
-    m.insert("cluster-node-timeout".to_owned(), "15000".to_owned());
-    m.insert("busy-reply-threshold".to_owned(), "5000".to_owned());
-    m.insert("maxmemory-eviction-tenacity".to_owned(), "10".to_owned());
-    m.insert("rdbchecksum".to_owned(), "yes".to_owned());
-    m.insert("cluster-port".to_owned(), "0".to_owned());
-    m.insert("repl-disable-tcp-nodelay".to_owned(), "no".to_owned());
-    m.insert("cluster-replica-no-failover".to_owned(), "no".to_owned());
-    m.insert("ignore-warnings".to_owned(), "".to_owned());
-    m.insert("daemonize".to_owned(), "no".to_owned());
-    m.insert("appenddirname".to_owned(), "appendonlydir".to_owned());
-    m.insert("activerehashing".to_owned(), "yes".to_owned());
-    m.insert("lfu-log-factor".to_owned(), "10".to_owned());
-    m.insert("list-max-listpack-size".to_owned(), "-2".to_owned());
-    m.insert("cluster-slave-validity-factor".to_owned(), "10".to_owned());
-    m.insert("io-threads-do-reads".to_owned(), "no".to_owned());
-    m.insert("tls-key-file-pass".to_owned(), "".to_owned());
-    m.insert("auto-aof-rewrite-min-size".to_owned(), "67108864".to_owned());
-    m.insert("dynamic-hz".to_owned(), "yes".to_owned());
-    m.insert("set-proc-title".to_owned(), "yes".to_owned());
-    m.insert("unixsocketperm".to_owned(), "0".to_owned());
-    m.insert("dbfilename".to_owned(), "dump.rdb".to_owned());
-    m.insert("cluster-replica-validity-factor".to_owned(), "10".to_owned());
-    m.insert("cluster-allow-reads-when-down".to_owned(), "no".to_owned());
-    m.insert("active-expire-effort".to_owned(), "1".to_owned());
-    m.insert("cluster-require-full-coverage".to_owned(), "yes".to_owned());
-    m.insert("latency-monitor-threshold".to_owned(), "0".to_owned());
-    m.insert("tls-auth-clients".to_owned(), "yes".to_owned());
-    m.insert("tls-client-cert-file".to_owned(), "".to_owned());
-    m.insert("replica-lazy-flush".to_owned(), "no".to_owned());
-    m.insert("replica-priority".to_owned(), "100".to_owned());
-    m.insert("slave-announce-ip".to_owned(), "".to_owned());
-    m.insert("tls-replication".to_owned(), "no".to_owned());
-    m.insert("cluster-allow-replica-migration".to_owned(), "yes".to_owned());
-    m.insert("enable-debug-command".to_owned(), "no".to_owned());
-    m.insert("tls-key-file".to_owned(), "".to_owned());
-    m.insert("latency-tracking".to_owned(), "yes".to_owned());
-    m.insert("slave-ignore-maxmemory".to_owned(), "yes".to_owned());
-    m.insert("hash-max-listpack-value".to_owned(), "64".to_owned());
-    m.insert("rdb-save-incremental-fsync".to_owned(), "yes".to_owned());
-    m.insert("always-show-logo".to_owned(), "no".to_owned());
-    m.insert("bio_cpulist".to_owned(), "".to_owned());
-    m.insert("server_cpulist".to_owned(), "".to_owned());
-    m.insert("tcp-backlog".to_owned(), "511".to_owned());
-    m.insert("rdb-del-sync-files".to_owned(), "no".to_owned());
-    m.insert("lazyfree-lazy-expire".to_owned(), "no".to_owned());
-    m.insert("dir".to_owned(), "/data".to_owned());
-    m.insert("io-threads".to_owned(), "1".to_owned());
-    m.insert("active-defrag-threshold-lower".to_owned(), "10".to_owned());
-    m.insert("cluster-allow-pubsubshard-when-down".to_owned(), "yes".to_owned());
-    m.insert("logfile".to_owned(), "".to_owned());
-    m.insert("enable-protected-configs".to_owned(), "no".to_owned());
-    m.insert("zset-max-listpack-value".to_owned(), "64".to_owned());
-    m.insert("requirepass".to_owned(), "".to_owned());
-    m.insert("hz".to_owned(), "10".to_owned());
-    m.insert("appendfilename".to_owned(), "appendonly.aof".to_owned());
-    m.insert("tls-ciphersuites".to_owned(), "".to_owned());
-    m.insert("aof-rewrite-incremental-fsync".to_owned(), "yes".to_owned());
-    m.insert("lazyfree-lazy-server-del".to_owned(), "no".to_owned());
-    m.insert("cluster-announce-bus-port".to_owned(), "0".to_owned());
-    m.insert("active-defrag-threshold-upper".to_owned(), "100".to_owned());
-    m.insert("acl-pubsub-default".to_owned(), "resetchannels".to_owned());
-    m.insert("lazyfree-lazy-user-del".to_owned(), "no".to_owned());
-    m.insert("shutdown-timeout".to_owned(), "10".to_owned());
-    m.insert("unixsocket".to_owned(), "".to_owned());
-    m.insert("cluster-announce-ip".to_owned(), "".to_owned());
-    m.insert("slave-announce-port".to_owned(), "0".to_owned());
-    m.insert("cluster-enabled".to_owned(), "no".to_owned());
-    m.insert("tls-cluster".to_owned(), "no".to_owned());
-    m.insert("cluster-announce-hostname".to_owned(), "".to_owned());
-    m.insert("hash-max-ziplist-value".to_owned(), "64".to_owned());
-    m.insert("repl-backlog-size".to_owned(), "1048576".to_owned());
-    m.insert("protected-mode".to_owned(), "no".to_owned());
-    m.insert("activedefrag".to_owned(), "no".to_owned());
-    m.insert("slave-lazy-flush".to_owned(), "no".to_owned());
-    m.insert("latency-tracking-info-percentiles".to_owned(), "50 99 99.9".to_owned());
-    m.insert("sanitize-dump-payload".to_owned(), "no".to_owned());
-    m.insert("maxmemory-samples".to_owned(), "5".to_owned());
-    m.insert("socket-mark-id".to_owned(), "0".to_owned());
-    m.insert("crash-memcheck-enabled".to_owned(), "yes".to_owned());
-    m.insert("replica-announce-port".to_owned(), "0".to_owned());
-    m.insert("appendonly".to_owned(), "no".to_owned());
-    m.insert("active-defrag-cycle-min".to_owned(), "1".to_owned());
-    m.insert("slave-priority".to_owned(), "100".to_owned());
-    m.insert("bgsave_cpulist".to_owned(), "".to_owned());
-    m.insert("replica-announced".to_owned(), "yes".to_owned());
-    m.insert("aclfile".to_owned(), "".to_owned());
-    m.insert("lazyfree-lazy-user-flush".to_owned(), "no".to_owned());
-    m.insert("crash-log-enabled".to_owned(), "yes".to_owned());
-    m.insert("min-replicas-max-lag".to_owned(), "10".to_owned());
-    m.insert("slowlog-max-len".to_owned(), "128".to_owned());
-    m.insert("shutdown-on-sigterm".to_owned(), "default".to_owned());
-    m.insert("repl-diskless-sync-delay".to_owned(), "5".to_owned());
-    m.insert("tcp-keepalive".to_owned(), "300".to_owned());
-    m.insert("repl-timeout".to_owned(), "60".to_owned());
-    m.insert("tls-session-cache-size".to_owned(), "20480".to_owned());
-    m.insert("replica-ignore-disk-write-errors".to_owned(), "no".to_owned());
-    m.insert("tls-prefer-server-ciphers".to_owned(), "no".to_owned());
-    m.insert("zset-max-listpack-entries".to_owned(), "128".to_owned());
-    m.insert("tracking-table-max-keys".to_owned(), "1000000".to_owned());
-    m.insert("replica-serve-stale-data".to_owned(), "yes".to_owned());
-    m.insert("tls-ca-cert-file".to_owned(), "".to_owned());
-    m.insert("locale-collate".to_owned(), "".to_owned());
-    m.insert("client-output-buffer-limit".to_owned(), "normal 0 0 0 slave 268435456 67108864 60 pubsub 33554432 8388608 60".to_owned());
-    m.insert("jemalloc-bg-thread".to_owned(), "yes".to_owned());
-    m.insert("set-max-listpack-entries".to_owned(), "128".to_owned());
-    m.insert("replicaof".to_owned(), "".to_owned());
-    m.insert("enable-module-command".to_owned(), "no".to_owned());
-    m.insert("lua-time-limit".to_owned(), "5000".to_owned());
-    m.insert("syslog-enabled".to_owned(), "no".to_owned());
-    m.insert("replica-ignore-maxmemory".to_owned(), "yes".to_owned());
-    m.insert("tls-session-cache-timeout".to_owned(), "300".to_owned());
-    m.insert("lazyfree-lazy-eviction".to_owned(), "no".to_owned());
-    m.insert("repl-diskless-sync".to_owned(), "yes".to_owned());
-    m.insert("shutdown-on-sigint".to_owned(), "default".to_owned());
-    m.insert("disable-thp".to_owned(), "yes".to_owned());
-    m.insert("appendfsync".to_owned(), "everysec".to_owned());
-    m.insert("cluster-announce-port".to_owned(), "0".to_owned());
-    m.insert("no-appendfsync-on-rewrite".to_owned(), "no".to_owned());
-    m.insert("zset-max-ziplist-entries".to_owned(), "128".to_owned());
-    m.insert("acllog-max-len".to_owned(), "128".to_owned());
-    m.insert("hash-max-listpack-entries".to_owned(), "512".to_owned());
-    m.insert("syslog-ident".to_owned(), "redis".to_owned());
-    m.insert("repl-diskless-sync-max-replicas".to_owned(), "0".to_owned());
-    m.insert("stream-node-max-entries".to_owned(), "100".to_owned());
-    m.insert("repl-backlog-ttl".to_owned(), "3600".to_owned());
-    m.insert("stop-writes-on-bgsave-error".to_owned(), "yes".to_owned());
-    m.insert("slaveof".to_owned(), "".to_owned());
-    m.insert("slave-serve-stale-data".to_owned(), "yes".to_owned());
-    m.insert("min-slaves-to-write".to_owned(), "0".to_owned());
-}
\ No newline at end of file
+thread_local! {
+    /// The database selected by the client served by the current thread, 0 until it sends SELECT.
+    static SELECTED_DATABASE: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Current Unix time in milliseconds, the unit expiry times are stored in.
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis() as u64).unwrap_or(0)
+}
+
+/// Field/value pairs of a hash.
+pub type Hash = HashMap<Vec<u8>, Vec<u8>>;
+
+/// Elements of a list, with O(1) pushes and pops at both ends.
+pub type List = VecDeque<Vec<u8>>;
+
+/// Members of a set. Small sets of integers are kept as a sorted vector, like the Redis intset encoding,
+/// and switch to an `IndexedSet` for good once they get a member that is not an integer or grow past
+/// set-max-intset-entries.
+#[derive(Clone)]
+pub enum Set {
+    Integers(Vec<i64>),
+    Members(IndexedSet),
+}
+
+impl Default for Set {
+    fn default() -> Set {
+        Set::Integers(Vec::new())
+    }
+}
+
+/// Parse a member as an integer for the intset encoding. Only the canonical spelling qualifies, so
+/// that turning the integer back into a string gives the member unchanged.
+fn parse_intset_member(member: &[u8]) -> Option<i64> {
+    std::str::from_utf8(member)
+        .ok()
+        .and_then(|text| text.parse::<i64>().ok())
+        .filter(|value| value.to_string().as_bytes() == member)
+}
+
+fn max_intset_entries() -> usize {
+    global_config_get("set-max-intset-entries").and_then(|value| value.parse().ok()).unwrap_or(512)
+}
+
+impl Set {
+    pub fn len(&self) -> usize {
+        match self {
+            Set::Integers(integers) => integers.len(),
+            Set::Members(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            Set::Integers(integers) => {
+                parse_intset_member(member).is_some_and(|integer| integers.binary_search(&integer).is_ok())
+            }
+            Set::Members(members) => members.contains(member),
+        }
+    }
+
+    /// Add the member, returning false if it was already there.
+    pub fn insert(&mut self, member: &[u8]) -> bool {
+        if let (Set::Integers(integers), Some(integer)) = (&mut *self, parse_intset_member(member)) {
+            match integers.binary_search(&integer) {
+                Ok(_) => return false,
+                Err(position) if integers.len() < max_intset_entries() => {
+                    integers.insert(position, integer);
+                    return true;
+                }
+                Err(_) => {}
+            }
+        }
+        self.members_mut().insert(member)
+    }
+
+    /// Remove the member, returning false if it was not there.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            Set::Integers(integers) => {
+                let position = parse_intset_member(member).and_then(|integer| integers.binary_search(&integer).ok());
+                position.map(|position| integers.remove(position)).is_some()
+            }
+            Set::Members(members) => members.remove(member),
+        }
+    }
+
+    /// Every member, in ascending order for the integer encoding and in no particular order otherwise.
+    pub fn members(&self) -> Vec<Vec<u8>> {
+        match self {
+            Set::Integers(integers) => integers.iter().map(|integer| integer.to_string().into_bytes()).collect(),
+            Set::Members(members) => members.iter().cloned().collect(),
+        }
+    }
+
+    pub fn random(&self) -> Option<Vec<u8>> {
+        match self {
+            Set::Integers(integers) if integers.is_empty() => None,
+            Set::Integers(integers) => {
+                Some(integers[random_u64() as usize % integers.len()].to_string().into_bytes())
+            }
+            Set::Members(members) => members.random().cloned(),
+        }
+    }
+
+    /// Remove a random member and return it.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let member = self.random()?;
+        self.remove(&member);
+        Some(member)
+    }
+
+    /// The members as an `IndexedSet`, leaving the integer encoding if the set was using it.
+    fn members_mut(&mut self) -> &mut IndexedSet {
+        if let Set::Integers(integers) = self {
+            let mut members = IndexedSet::default();
+            for integer in integers.iter() {
+                members.insert(integer.to_string().as_bytes());
+            }
+            *self = Set::Members(members);
+        }
+        match self {
+            Set::Members(members) => members,
+            Set::Integers(_) => unreachable!("Converted above"),
+        }
+    }
+}
+
+/// One end of a score range, such as `(1.5` or `-inf`.
+#[derive(Clone, Copy)]
+pub struct ScoreBound {
+    pub score: f64,
+    pub exclusive: bool,
+}
+
+/// Scores between two bounds, as given to ZRANGE BYSCORE, ZCOUNT and friends.
+#[derive(Clone, Copy)]
+pub struct ScoreRange {
+    pub min: ScoreBound,
+    pub max: ScoreBound,
+}
+
+impl ScoreRange {
+    fn below_min(&self, score: f64) -> bool {
+        if self.min.exclusive { score <= self.min.score } else { score < self.min.score }
+    }
+
+    fn up_to_max(&self, score: f64) -> bool {
+        if self.max.exclusive { score < self.max.score } else { score <= self.max.score }
+    }
+}
+
+/// One end of a lexicographical range: `-` and `+` for the smallest and largest strings, otherwise a
+/// string prefixed with `[` or `(` for an inclusive or exclusive bound.
+#[derive(Clone)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(Vec<u8>),
+    Exclusive(Vec<u8>),
+}
+
+/// Members between two bounds, as given to ZRANGE BYLEX, ZLEXCOUNT and friends.
+/// Only meaningful when every member has the same score, in which case members are ordered bytewise.
+#[derive(Clone)]
+pub struct LexRange {
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+impl LexRange {
+    fn below_min(&self, member: &[u8]) -> bool {
+        match &self.min {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(min) => member < min.as_slice(),
+            LexBound::Exclusive(min) => member <= min.as_slice(),
+        }
+    }
+
+    fn up_to_max(&self, member: &[u8]) -> bool {
+        match &self.max {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(max) => member <= max.as_slice(),
+            LexBound::Exclusive(max) => member < max.as_slice(),
+        }
+    }
+}
+
+/// Members of a sorted set with their scores. The skiplist keeps them ordered for rank and range
+/// queries in O(log n), while the map finds the score of a member in O(1).
+#[derive(Clone, Default)]
+pub struct SortedSet {
+    scores: HashMap<Vec<u8>, f64>,
+    ordered: SkipList,
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.ordered.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Every member, in no particular order.
+    pub fn members(&self) -> std::collections::hash_map::Keys<'_, Vec<u8>, f64> {
+        self.scores.keys()
+    }
+
+    /// Every member with its score, ordered by score.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], f64)> + '_ {
+        self.pairs(self.ordered.walk(self.ordered.first(), false))
+    }
+
+    fn pairs<'a>(&'a self, nodes: impl Iterator<Item = NodeId> + 'a) -> impl Iterator<Item = (&'a [u8], f64)> + 'a {
+        nodes.map(|node| (self.ordered.member(node), self.ordered.score(node)))
+    }
+
+    /// Set the score of the member, adding it if needed. Returns true if it was added.
+    pub fn insert(&mut self, member: &[u8], score: f64) -> bool {
+        match self.scores.get_mut(member) {
+            Some(current) => {
+                if *current != score {
+                    self.ordered.update_score(*current, member, score);
+                    *current = score;
+                }
+                false
+            }
+            None => {
+                self.scores.insert(member.to_vec(), score);
+                self.ordered.insert(score, member.to_vec());
+                true
+            }
+        }
+    }
+
+    /// Remove the member, returning false if it was not there.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        let Some(score) = self.scores.remove(member) else {
+            return false;
+        };
+        self.ordered.remove(score, member);
+        true
+    }
+
+    /// Remove the member with the lowest score, or the highest one if `highest` is set.
+    pub fn pop(&mut self, highest: bool) -> Option<(Vec<u8>, f64)> {
+        let node = if highest { self.ordered.last() } else { self.ordered.first() }?;
+        let (member, score) = (self.ordered.member(node).to_vec(), self.ordered.score(node));
+        self.remove(&member);
+        Some((member, score))
+    }
+
+    /// Rank of the member counting from 0, from the lowest score or from the highest one if `reverse` is set.
+    pub fn rank(&self, member: &[u8], reverse: bool) -> Option<usize> {
+        let rank = self.ordered.rank(self.score(member)?, member)?;
+        Some(if reverse { self.len() - 1 - rank } else { rank })
+    }
+
+    /// The members from rank `start` to rank `stop` included, ranks counting from the highest score if
+    /// `reverse` is set.
+    pub fn range_by_rank(&self, start: usize, stop: usize, reverse: bool) -> impl Iterator<Item = (&[u8], f64)> + '_ {
+        let first = if reverse { self.len().checked_sub(start + 1) } else { Some(start) };
+        let first = first.and_then(|rank| self.ordered.get_by_rank(rank));
+        self.pairs(self.ordered.walk(first, reverse).take(stop.saturating_sub(start) + 1))
+    }
+
+    /// The members whose score is in the range, from the highest score if `reverse` is set.
+    pub fn range_by_score(&self, range: ScoreRange, reverse: bool) -> impl Iterator<Item = (&[u8], f64)> + '_ {
+        self.range_where(move |score, _| range.below_min(score), move |score, _| range.up_to_max(score), reverse)
+    }
+
+    /// The members in the lexicographical range, from the largest if `reverse` is set.
+    pub fn range_by_lex<'a>(&'a self, range: &'a LexRange, reverse: bool) -> impl Iterator<Item = (&'a [u8], f64)> + 'a {
+        self.range_where(|_, member| range.below_min(member), |_, member| range.up_to_max(member), reverse)
+    }
+
+    pub fn count_by_score(&self, range: ScoreRange) -> usize {
+        self.count_where(|score, _| range.below_min(score), |score, _| range.up_to_max(score))
+    }
+
+    pub fn count_by_lex(&self, range: &LexRange) -> usize {
+        self.count_where(|_, member| range.below_min(member), |_, member| range.up_to_max(member))
+    }
+
+    /// The members after those for which `below_min` holds and up to the last one for which `up_to_max` does.
+    fn range_where<'a>(
+        &'a self,
+        below_min: impl Fn(f64, &[u8]) -> bool + 'a,
+        up_to_max: impl Fn(f64, &[u8]) -> bool + 'a,
+        reverse: bool,
+    ) -> impl Iterator<Item = (&'a [u8], f64)> + 'a {
+        let first = if reverse { self.ordered.last_within(&up_to_max) } else { self.ordered.first_from(&below_min) };
+        self.pairs(self.ordered.walk(first.map(|(_, node)| node), reverse))
+            .take_while(move |(member, score)| !below_min(*score, member) && up_to_max(*score, member))
+    }
+
+    fn count_where(&self, below_min: impl Fn(f64, &[u8]) -> bool, up_to_max: impl Fn(f64, &[u8]) -> bool) -> usize {
+        match (self.ordered.first_from(below_min), self.ordered.last_within(up_to_max)) {
+            (Some((first, _)), Some((last, _))) if last >= first => last - first + 1,
+            _ => 0,
+        }
+    }
+}
+
+/// The value of a key, one variant per data type.
+#[derive(Clone)]
+pub enum Value {
+    String(Vec<u8>),
+    Hash(Hash),
+    List(List),
+    Set(Set),
+    SortedSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Value {
+    /// Name of the type as reported by the TYPE command.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Hash(_) => "hash",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// Whether the value is a collection with nothing left in it. Such keys are deleted, like in Redis.
+    /// Streams are kept even when empty, since they remember their last ID and consumer groups.
+    fn is_empty_collection(&self) -> bool {
+        match self {
+            Value::String(_) | Value::Stream(_) => false,
+            Value::Hash(hash) => hash.is_empty(),
+            Value::List(list) => list.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::SortedSet(sorted_set) => sorted_set.is_empty(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(string: Vec<u8>) -> Value {
+        Value::String(string)
+    }
+}
+
+/// A value stored under a key.
+#[derive(Clone)]
+pub struct Entry {
+    pub value: Value,
+    /// Unix time in milliseconds at which the key expires, or None if it lives forever.
+    /// Only changed through `Database` so that its index of volatile keys stays accurate.
+    expires_at: Option<u64>,
+    /// Unix time in milliseconds the key was last read or written, for the LRU eviction policies.
+    accessed: u64,
+    /// How often the key is read or written, for the LFU eviction policies.
+    frequency: Frequency,
+}
+
+impl Entry {
+    pub fn expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+
+    pub fn accessed(&self) -> u64 {
+        self.accessed
+    }
+
+    pub fn frequency(&self) -> u8 {
+        self.frequency.counter()
+    }
+
+    /// Name of the value's type as reported by the TYPE command.
+    pub fn type_name(&self) -> &'static str {
+        self.value.type_name()
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// How a write should treat the time to live of the key it replaces.
+pub enum Ttl {
+    /// Drop any existing time to live, the key lives forever.
+    Persist,
+    /// Expire the key at the given Unix time in milliseconds.
+    ExpireAt(u64),
+    /// Keep whatever time to live the previous value had.
+    Keep,
+}
+
+/// Condition under which a SET goes through.
+pub enum SetCondition {
+    Always,
+    /// NX: only set the key if it does not exist.
+    IfMissing,
+    /// XX: only set the key if it already exists.
+    IfExists,
+}
+
+/// The options of the SET command.
+pub struct SetOptions {
+    pub condition: SetCondition,
+    pub ttl: Ttl,
+    /// Whether the caller wants the previous value back.
+    pub get: bool,
+}
+
+/// Byte strings supporting O(1) insertion, removal, lookup and random sampling.
+/// Used for the keys that have a time to live and for sets too large for the integer encoding.
+#[derive(Clone, Default)]
+pub struct IndexedSet {
+    elements: Vec<Vec<u8>>,
+    positions: HashMap<Vec<u8>, usize>,
+}
+
+impl IndexedSet {
+    /// Add the element, returning false if it was already there.
+    pub fn insert(&mut self, element: &[u8]) -> bool {
+        if self.positions.contains_key(element) {
+            return false;
+        }
+        self.positions.insert(element.to_vec(), self.elements.len());
+        self.elements.push(element.to_vec());
+        true
+    }
+
+    /// Remove the element, returning false if it was not there.
+    pub fn remove(&mut self, element: &[u8]) -> bool {
+        let Some(position) = self.positions.remove(element) else {
+            return false;
+        };
+        self.elements.swap_remove(position);
+        if let Some(moved) = self.elements.get(position) {
+            self.positions.insert(moved.clone(), position);
+        }
+        true
+    }
+
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.positions.contains_key(element)
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Vec<u8>> {
+        self.elements.iter()
+    }
+
+    pub fn random(&self) -> Option<&Vec<u8>> {
+        if self.elements.is_empty() {
+            return None;
+        }
+        self.elements.get(random_u64() as usize % self.elements.len())
+    }
+}
+
+/// The keyspace.
+/// Expired keys are removed lazily: every lookup first drops the key if its time to live has passed,
+/// so callers never observe an expired value. Keys nobody reads are reclaimed by the active expire cycle.
+pub struct Database {
+    entries: HashMap<Vec<u8>, Entry>,
+    /// The keys that have a time to live.
+    volatile: IndexedSet,
+    /// Every key ordered by its scan hash, so SCAN can resume from a cursor in O(log n).
+    scan_index: BTreeSet<(u64, Vec<u8>)>,
+    blocked: BlockedClients,
+}
+
+impl Database {
+    pub fn new() -> Database {
+        Database {
+            entries: HashMap::new(),
+            volatile: IndexedSet::default(),
+            scan_index: BTreeSet::new(),
+            blocked: BlockedClients::default(),
+        }
+    }
+
+    /// Insert an entry, keeping the key indexes in sync.
+    fn insert_entry(&mut self, key: Vec<u8>, entry: Entry) {
+        match entry.expires_at {
+            Some(_) => self.volatile.insert(&key),
+            None => self.volatile.remove(&key),
+        };
+        if !self.entries.contains_key(&key) {
+            self.scan_index.insert((scan_hash(&key), key.clone()));
+        }
+        // Clients blocked on a key that does not exist may be served now that it does.
+        self.blocked.signal_key_ready(&key);
+        self.entries.insert(key, entry);
+    }
+
+    /// Remove an entry, keeping the key indexes in sync.
+    fn remove_entry(&mut self, key: &[u8]) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        if entry.expires_at.is_some() {
+            self.volatile.remove(key);
+        }
+        self.scan_index.remove(&(scan_hash(key), key.to_vec()));
+        Some(entry)
+    }
+
+    /// Remove the key if it has expired. Returns true if it was removed.
+    fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        let expired = self.entries.get(key).is_some_and(|entry| entry.is_expired(now_ms()));
+        if expired {
+            self.remove_entry(key);
+            stats::increment(&stats::EXPIRED_KEYS, 1);
+        }
+        expired
+    }
+
+    pub fn get(&mut self, key: &[u8]) -> Option<&Entry> {
+        self.get_mut(key).map(|entry| &*entry)
+    }
+
+    /// Mutable access to a value. Its time to live can only be changed through `set_expiry`.
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Entry> {
+        self.expire_if_needed(key);
+        let entry = self.entries.get_mut(key)?;
+        entry.accessed = now_ms();
+        entry.frequency.touch();
+        Some(entry)
+    }
+
+    /// Look at an entry without counting it as accessed, even if it expired.
+    pub fn peek(&self, key: &[u8]) -> Option<&Entry> {
+        self.entries.get(key)
+    }
+
+    /// A key picked at random, including expired ones not reclaimed yet.
+    pub fn random_key(&self) -> Option<Vec<u8>> {
+        let hash = random_u64();
+        let mut keys = self.scan_index.range((hash, Vec::new())..).chain(&self.scan_index);
+        keys.next().map(|(_, key)| key.clone())
+    }
+
+    /// A key with a time to live picked at random.
+    pub fn random_volatile_key(&self) -> Option<Vec<u8>> {
+        self.volatile.random().cloned()
+    }
+
+    /// The string stored under the key, or a WRONGTYPE error if the key holds another type.
+    pub fn get_string(&mut self, key: &[u8]) -> Result<Option<&Vec<u8>>, RedisError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::String(string), .. }) => Ok(Some(string)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn get_string_mut(&mut self, key: &[u8]) -> Result<Option<&mut Vec<u8>>, RedisError> {
+        match self.get_mut(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::String(string), .. }) => Ok(Some(string)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// The hash stored under the key, or a WRONGTYPE error if the key holds another type.
+    pub fn get_hash(&mut self, key: &[u8]) -> Result<Option<&Hash>, RedisError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Hash(hash), .. }) => Ok(Some(hash)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// Mutable access to the hash stored under the key. Call `remove_if_empty` after removing fields.
+    pub fn get_hash_mut(&mut self, key: &[u8]) -> Result<Option<&mut Hash>, RedisError> {
+        match self.get_mut(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Hash(hash), .. }) => Ok(Some(hash)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// The hash stored under the key, created empty if the key does not exist.
+    pub fn get_or_create_hash(&mut self, key: &[u8]) -> Result<&mut Hash, RedisError> {
+        if self.get(key).is_none() {
+            self.set(key.to_vec(), Value::Hash(Hash::new()), Ttl::Persist);
+        }
+        Ok(self.get_hash_mut(key)?.expect("Hash created above"))
+    }
+
+    /// The list stored under the key, or a WRONGTYPE error if the key holds another type.
+    pub fn get_list(&mut self, key: &[u8]) -> Result<Option<&List>, RedisError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::List(list), .. }) => Ok(Some(list)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// Mutable access to the list stored under the key. Call `remove_if_empty` after removing elements.
+    pub fn get_list_mut(&mut self, key: &[u8]) -> Result<Option<&mut List>, RedisError> {
+        match self.get_mut(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::List(list), .. }) => Ok(Some(list)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// The list stored under the key, created empty if the key does not exist.
+    pub fn get_or_create_list(&mut self, key: &[u8]) -> Result<&mut List, RedisError> {
+        if self.get(key).is_none() {
+            self.set(key.to_vec(), Value::List(List::new()), Ttl::Persist);
+        }
+        Ok(self.get_list_mut(key)?.expect("List created above"))
+    }
+
+    /// The set stored under the key, or a WRONGTYPE error if the key holds another type.
+    pub fn get_set(&mut self, key: &[u8]) -> Result<Option<&Set>, RedisError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Set(set), .. }) => Ok(Some(set)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// Mutable access to the set stored under the key. Call `remove_if_empty` after removing members.
+    pub fn get_set_mut(&mut self, key: &[u8]) -> Result<Option<&mut Set>, RedisError> {
+        match self.get_mut(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Set(set), .. }) => Ok(Some(set)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// The set stored under the key, created empty if the key does not exist.
+    pub fn get_or_create_set(&mut self, key: &[u8]) -> Result<&mut Set, RedisError> {
+        if self.get(key).is_none() {
+            self.set(key.to_vec(), Value::Set(Set::default()), Ttl::Persist);
+        }
+        Ok(self.get_set_mut(key)?.expect("Set created above"))
+    }
+
+    /// The values stored under each of the keys, for the commands combining several of them.
+    pub fn get_values(&mut self, keys: &[Vec<u8>]) -> Vec<Option<&Value>> {
+        for key in keys {
+            self.expire_if_needed(key);
+        }
+        keys.iter().map(|key| self.entries.get(key).map(|entry| &entry.value)).collect()
+    }
+
+    /// The sets stored under each of the keys. Fails with WRONGTYPE if any of the keys holds another type.
+    pub fn get_sets(&mut self, keys: &[Vec<u8>]) -> Result<Vec<Option<&Set>>, RedisError> {
+        self.get_values(keys)
+            .into_iter()
+            .map(|value| match value {
+                None => Ok(None),
+                Some(Value::Set(set)) => Ok(Some(set)),
+                Some(_) => Err(RedisError::WrongType),
+            })
+            .collect()
+    }
+
+    /// The sorted set stored under the key, or a WRONGTYPE error if the key holds another type.
+    pub fn get_sorted_set(&mut self, key: &[u8]) -> Result<Option<&SortedSet>, RedisError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::SortedSet(sorted_set), .. }) => Ok(Some(sorted_set)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// Mutable access to the sorted set stored under the key. Call `remove_if_empty` after removing members.
+    pub fn get_sorted_set_mut(&mut self, key: &[u8]) -> Result<Option<&mut SortedSet>, RedisError> {
+        match self.get_mut(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::SortedSet(sorted_set), .. }) => Ok(Some(sorted_set)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// The sorted set stored under the key, created empty if the key does not exist.
+    pub fn get_or_create_sorted_set(&mut self, key: &[u8]) -> Result<&mut SortedSet, RedisError> {
+        if self.get(key).is_none() {
+            self.set(key.to_vec(), Value::SortedSet(SortedSet::default()), Ttl::Persist);
+        }
+        Ok(self.get_sorted_set_mut(key)?.expect("Sorted set created above"))
+    }
+
+    /// The stream stored under the key, or a WRONGTYPE error if the key holds another type.
+    pub fn get_stream(&mut self, key: &[u8]) -> Result<Option<&Stream>, RedisError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Stream(stream), .. }) => Ok(Some(stream)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    pub fn get_stream_mut(&mut self, key: &[u8]) -> Result<Option<&mut Stream>, RedisError> {
+        match self.get_mut(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Stream(stream), .. }) => Ok(Some(stream)),
+            Some(_) => Err(RedisError::WrongType),
+        }
+    }
+
+    /// The stream stored under the key, created empty if the key does not exist.
+    pub fn get_or_create_stream(&mut self, key: &[u8]) -> Result<&mut Stream, RedisError> {
+        if self.get(key).is_none() {
+            self.set(key.to_vec(), Value::Stream(Stream::default()), Ttl::Persist);
+        }
+        Ok(self.get_stream_mut(key)?.expect("Stream created above"))
+    }
+
+    /// Delete the key if it holds a collection that has become empty.
+    pub fn remove_if_empty(&mut self, key: &[u8]) {
+        if self.entries.get(key).is_some_and(|entry| entry.value.is_empty_collection()) {
+            self.remove_entry(key);
+        }
+    }
+
+    /// Store a value under the key, replacing any previous one whatever its type.
+    pub fn set(&mut self, key: Vec<u8>, value: impl Into<Value>, ttl: Ttl) {
+        let value = value.into();
+        let expires_at = match ttl {
+            Ttl::Persist => None,
+            Ttl::ExpireAt(expires_at) => Some(expires_at),
+            Ttl::Keep => self.get(&key).and_then(|entry| entry.expires_at),
+        };
+        self.insert_entry(key, Entry { value, expires_at, accessed: now_ms(), frequency: Frequency::default() });
+    }
+
+    /// Set or clear the expiry time of an existing key. Returns false if the key does not exist.
+    /// An expiry time that already passed deletes the key.
+    pub fn set_expiry(&mut self, key: &[u8], expires_at: Option<u64>) -> bool {
+        let Some(mut entry) = self.remove(key) else {
+            return false;
+        };
+        entry.expires_at = expires_at;
+        self.insert_entry(key.to_vec(), entry);
+        self.expire_if_needed(key);
+        true
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Entry> {
+        self.expire_if_needed(key);
+        self.remove_entry(key)
+    }
+
+    /// Store an entry as is, including its time to live, replacing any previous value.
+    pub fn insert(&mut self, key: Vec<u8>, entry: Entry) {
+        self.insert_entry(key, entry);
+    }
+
+    /// Every key that has not expired, in no particular order.
+    pub fn keys(&self) -> Vec<Vec<u8>> {
+        let now = now_ms();
+        self.entries.iter().filter(|(_, entry)| !entry.is_expired(now)).map(|(key, _)| key.clone()).collect()
+    }
+
+    /// One step of a SCAN over the keyspace, see `scan_step`.
+    /// Expired keys met along the way are deleted and left out.
+    pub fn scan(&mut self, cursor: u64, count: usize) -> (u64, Vec<Vec<u8>>) {
+        let (cursor, keys) = scan_step(
+            self.scan_index.range((cursor, Vec::new())..).map(|(hash, key)| (*hash, key)),
+            count,
+        );
+        let keys = keys.into_iter().cloned().collect::<Vec<_>>();
+        (cursor, keys.into_iter().filter(|key| self.get(key).is_some()).collect())
+    }
+
+    /// Remove every key, returning the entries so the caller can choose to free them in the background.
+    /// Clients blocked on keys stay blocked.
+    pub fn flush(&mut self) -> HashMap<Vec<u8>, Entry> {
+        self.volatile = IndexedSet::default();
+        self.scan_index = BTreeSet::new();
+        std::mem::take(&mut self.entries)
+    }
+
+    /// Exchange the keys of the two databases. Clients blocked on keys stay with their database, and are
+    /// served if the keys they wait for now exist.
+    pub fn swap_keys(&mut self, other: &mut Database) {
+        std::mem::swap(&mut self.entries, &mut other.entries);
+        std::mem::swap(&mut self.volatile, &mut other.volatile);
+        std::mem::swap(&mut self.scan_index, &mut other.scan_index);
+        self.signal_existing_keys();
+        other.signal_existing_keys();
+    }
+
+    /// Signal every key clients wait for that exists.
+    fn signal_existing_keys(&mut self) {
+        for key in self.blocked.waited_keys() {
+            if self.entries.contains_key(&key) {
+                self.blocked.signal_key_ready(&key);
+            }
+        }
+    }
+
+    /// Clients blocked on keys of this keyspace.
+    pub fn blocked_clients(&mut self) -> &mut BlockedClients {
+        &mut self.blocked
+    }
+
+    /// Number of keys, including expired ones not reclaimed yet.
+    pub fn key_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Number of keys that have a time to live, including expired ones not reclaimed yet.
+    pub fn volatile_count(&self) -> usize {
+        self.volatile.len()
+    }
+
+    /// Check up to `count` randomly picked keys with a time to live and delete the expired ones.
+    /// Returns how many keys were sampled and how many of them were deleted.
+    pub fn expire_random_keys(&mut self, count: usize) -> (usize, usize) {
+        let now = now_ms();
+        let mut sampled = 0;
+        let mut expired = 0;
+        while sampled < count {
+            let Some(key) = self.volatile.random().cloned() else {
+                break;
+            };
+            sampled += 1;
+            if self.entries.get(&key).is_some_and(|entry| entry.is_expired(now)) {
+                self.remove_entry(&key);
+                expired += 1;
+            }
+        }
+        stats::increment(&stats::EXPIRED_KEYS, expired as u64);
+        (sampled, expired)
+    }
+}
+
+/// Hash deciding the order in which SCAN style commands visit elements.
+/// It only depends on the element itself, so the order is the same no matter how the collection changes.
+pub fn scan_hash(element: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(element);
+    hasher.finish()
+}
+
+/// Walk elements sorted by scan hash, starting at the cursor, and take about `count` of them.
+/// Elements sharing a hash are never split across calls. Returns the cursor to continue from,
+/// which is 0 once everything was visited.
+///
+/// The cursor is the next hash to visit, so elements present for the whole iteration are returned
+/// exactly once, however the collection grows or shrinks in between calls.
+pub fn scan_step<'a, T>(sorted: impl Iterator<Item = (u64, &'a T)>, count: usize) -> (u64, Vec<&'a T>)
+where
+    T: ?Sized,
+{
+    let mut elements = Vec::new();
+    let mut last_hash = None;
+    for (hash, element) in sorted {
+        if elements.len() >= count && last_hash != Some(hash) {
+            // More elements remain: resume from this one's hash.
+            return (hash, elements);
+        }
+        last_hash = Some(hash);
+        elements.push(element);
+    }
+    (0, elements)
+}
+
+/// One SCAN step over a collection that keeps no scan index: the elements past the cursor are sorted
+/// by scan hash on every call, see `scan_step`.
+pub fn scan_collection<'a, T>(elements: impl Iterator<Item = &'a T>, cursor: u64, count: usize) -> (u64, Vec<&'a T>)
+where
+    T: AsRef<[u8]> + ?Sized + 'a,
+{
+    let mut sorted = elements
+        .map(|element| (scan_hash(element.as_ref()), element))
+        .filter(|(hash, _)| *hash >= cursor)
+        .collect::<Vec<_>>();
+    sorted.sort_unstable_by_key(|(hash, _)| *hash);
+    scan_step(sorted.into_iter(), count)
+}
+
+/// Hand a value over to a background thread to be dropped, so that freeing large values
+/// does not hold up the client that removed them.
+pub fn free_in_background(value: impl Send + 'static) {
+    let sender = LAZY_FREE.lock().unwrap();
+    if let Err(error) = sender.send(Box::new(value)) {
+        // The lazy free thread is gone, free the value here instead.
+        drop(error.0);
+    }
+}
+
+/// Run a closure with exclusive access to the keyspace of the database the client selected.
+/// Everything done inside the closure is atomic with respect to other clients. Clients blocked on keys
+/// the closure wrote to are served before the keyspace is unlocked.
+pub fn with_store<R>(f: impl FnOnce(&mut Database) -> R) -> R {
+    with_databases(|databases, selected| {
+        let result = f(&mut databases[selected]);
+        serve_ready_keys(&mut databases[selected]);
+        result
+    })
+}
+
+/// Like `with_store`, but with access to every database, for the commands that span several of them.
+/// The closure is also given the index of the database the client selected. Clients blocked on keys are
+/// not served here, since only the closure knows which databases it wrote to: it must call
+/// `serve_ready_keys` on each of them.
+pub fn with_databases<R>(f: impl FnOnce(&mut [Database], usize) -> R) -> R {
+    let mut databases = DATABASES.lock().unwrap();
+    f(&mut databases, selected_database())
+}
+
+/// The index of the database the client served by the current thread selected.
+pub fn selected_database() -> usize {
+    SELECTED_DATABASE.with(Cell::get)
+}
+
+/// Select the database the client served by the current thread works on, which must exist.
+pub fn select_database(index: usize) {
+    SELECTED_DATABASE.with(|selected| selected.set(index));
+}
+
+/// Set a key according to the SET options.
+/// Returns whether the value was written, and the previous value if `options.get` asked for it.
+/// SET replaces values of any type, but fails with WRONGTYPE without writing anything when asked
+/// for a previous value that is not a string.
+pub fn global_store_set(
+    key: Vec<u8>,
+    value: Vec<u8>,
+    options: SetOptions,
+) -> Result<(bool, Option<Vec<u8>>), RedisError> {
+    with_store(|database| {
+        let exists = database.get(&key).is_some();
+        let previous = match options.get {
+            true => database.get_string(&key)?.cloned(),
+            false => None,
+        };
+        let allowed = match options.condition {
+            SetCondition::Always => true,
+            SetCondition::IfMissing => !exists,
+            SetCondition::IfExists => exists,
+        };
+        if allowed {
+            database.set(key, value, options.ttl);
+        }
+        Ok((allowed, previous))
+    })
+}
+
+/// Atomically replace the string of a key with one computed from its current value, or from None if
+/// the key does not exist. The closure returns the new value along with whatever the caller needs back.
+/// The key keeps its time to live, and nothing is written if the closure fails or the key is not a string.
+pub fn global_store_update<R>(
+    key: &[u8],
+    update: impl FnOnce(Option<&[u8]>) -> Result<(Vec<u8>, R), RedisError>,
+) -> Result<R, RedisError> {
+    with_store(|database| {
+        if let Some(string) = database.get_string_mut(key)? {
+            let (value, result) = update(Some(string))?;
+            *string = value;
+            return Ok(result);
+        }
+        let (value, result) = update(None)?;
+        database.set(key.to_vec(), value, Ttl::Persist);
+        Ok(result)
+    })
+}
+
+pub fn global_store_get(key: &[u8]) -> Result<Option<Vec<u8>>, RedisError> {
+    with_store(|database| Ok(database.get_string(key)?.cloned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire_random_keys() {
+        let mut database = Database::new();
+        for index in 0..10 {
+            database.set(format!("expired{}", index).into_bytes(), b"v".to_vec(), Ttl::ExpireAt(1));
+        }
+        database.set(b"volatile".to_vec(), b"v".to_vec(), Ttl::ExpireAt(u64::MAX));
+        database.set(b"persistent".to_vec(), b"v".to_vec(), Ttl::Persist);
+        assert_eq!(database.volatile_count(), 11);
+
+        while database.volatile_count() > 1 {
+            database.expire_random_keys(20);
+        }
+        assert!(database.get(b"volatile").is_some());
+        assert!(database.get(b"persistent").is_some());
+        assert_eq!(database.entries.len(), 2);
+
+        database.set_expiry(b"volatile", None);
+        assert_eq!(database.volatile_count(), 0);
+        assert_eq!(database.expire_random_keys(20), (0, 0));
+    }
+
+    #[test]
+    fn test_scan_step_never_splits_equal_hashes() {
+        let elements = [(1, "a"), (2, "b"), (2, "c"), (5, "d")];
+        let sorted = || elements.iter().map(|(hash, element)| (*hash, element));
+        assert_eq!(scan_step(sorted(), 2), (5, vec![&"a", &"b", &"c"]));
+        assert_eq!(scan_step(sorted().skip(3), 2), (0, vec![&"d"]));
+    }
+
+    #[test]
+    fn test_scan_visits_every_key_once() {
+        let mut database = Database::new();
+        for index in 0..100 {
+            database.set(format!("key{}", index).into_bytes(), b"v".to_vec(), Ttl::Persist);
+        }
+        let mut seen = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, keys) = database.scan(cursor, 7);
+            seen.extend(keys);
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 100);
+    }
+
+    #[test]
+    fn test_set_leaves_integer_encoding() {
+        let mut set = Set::default();
+        for member in ["3", "1", "2", "1"] {
+            set.insert(member.as_bytes());
+        }
+        assert!(matches!(&set, Set::Integers(integers) if integers == &[1, 2, 3]));
+        // Not the canonical spelling of an integer, so it cannot be stored as one.
+        assert!(!set.contains(b"01"));
+        set.insert(b"01");
+        assert!(matches!(set, Set::Members(_)));
+        assert_eq!(set.len(), 4);
+        assert!(set.contains(b"2") && set.contains(b"01"));
+        assert!(set.remove(b"2"));
+        assert!(!set.contains(b"2"));
+    }
+}
@@ -0,0 +1,638 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::config::global_config_get;
+
+/// ID of a stream entry: the Unix time in milliseconds the entry was added at, and a sequence number
+/// telling apart the entries added within the same millisecond.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId { ms: u64::MAX, seq: u64::MAX };
+
+    /// Parse `ms-seq`, or a lone `ms` in which case the sequence number is `missing_seq`.
+    pub fn parse(arg: &[u8], missing_seq: u64) -> Option<StreamId> {
+        let text = std::str::from_utf8(arg).ok()?;
+        let number = |part: &str| Some(part).filter(|part| part.bytes().all(|byte| byte.is_ascii_digit()))?.parse().ok();
+        match text.split_once('-') {
+            Some((ms, seq)) => Some(StreamId { ms: number(ms)?, seq: number(seq)? }),
+            None => Some(StreamId { ms: number(text)?, seq: missing_seq }),
+        }
+    }
+
+    /// The smallest ID greater than this one.
+    pub fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId { ms: self.ms.checked_add(1)?, seq: 0 }),
+        }
+    }
+
+    /// The largest ID smaller than this one.
+    pub fn previous(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId { ms: self.ms.checked_sub(1)?, seq: u64::MAX }),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// The fields and values of an entry, alternating.
+pub type Fields = Vec<Vec<u8>>;
+
+/// Consecutive entries packed together, like the listpacks hanging off the Redis stream radix tree.
+#[derive(Clone)]
+struct Node {
+    entries: Vec<(StreamId, Fields)>,
+    /// Approximate memory used by the entries, compared against stream-node-max-bytes.
+    bytes: usize,
+}
+
+/// Approximate size of an entry in a node: its ID plus each field and value with a little overhead.
+fn entry_size(fields: &Fields) -> usize {
+    16 + fields.iter().map(|field| field.len() + 2).sum::<usize>()
+}
+
+/// How large a node may grow before entries go to a new one, from stream-node-max-bytes and
+/// stream-node-max-entries. 0 means no limit.
+#[derive(Clone, Copy)]
+pub struct NodeLimits {
+    pub max_bytes: usize,
+    pub max_entries: usize,
+}
+
+impl NodeLimits {
+    pub fn from_config() -> NodeLimits {
+        let read = |name: &str, default| global_config_get(name).and_then(|value| value.parse().ok()).unwrap_or(default);
+        NodeLimits {
+            max_bytes: read("stream-node-max-bytes", 4096),
+            max_entries: read("stream-node-max-entries", 100),
+        }
+    }
+
+    fn fit(&self, node: &Node, size: usize) -> bool {
+        (self.max_entries == 0 || node.entries.len() < self.max_entries)
+            && (self.max_bytes == 0 || node.bytes + size <= self.max_bytes)
+    }
+}
+
+/// What XADD and XTRIM trim a stream down to.
+#[derive(Clone, Copy)]
+pub enum Trim {
+    /// Keep at most this many entries.
+    MaxLen(usize),
+    /// Drop the entries with a smaller ID.
+    MinId(StreamId),
+}
+
+/// An entry delivered to a consumer of a group and not acknowledged yet.
+#[derive(Clone)]
+pub struct PendingEntry {
+    pub consumer: Vec<u8>,
+    /// Unix time in milliseconds of the last delivery.
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+/// A consumer of a group, created the first time it reads or claims entries.
+#[derive(Clone)]
+pub struct Consumer {
+    /// Unix time in milliseconds the consumer last tried to read or claim entries.
+    pub seen_time: u64,
+    /// Unix time in milliseconds the consumer last got entries, if ever.
+    pub active_time: Option<u64>,
+    /// IDs of the entries delivered to the consumer and not acknowledged yet.
+    pub pending: BTreeSet<StreamId>,
+}
+
+/// A consumer group, which delivers each new entry of the stream to one of its consumers and remembers
+/// it until acknowledged.
+#[derive(Clone)]
+pub struct ConsumerGroup {
+    pub last_delivered_id: StreamId,
+    /// How many entries of the stream the group read, to tell its lag. None when unknown, for example
+    /// after entries were deleted in the part of the stream the group still has to read.
+    pub entries_read: Option<u64>,
+    /// The pending entries list, shared by all the consumers.
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<Vec<u8>, Consumer>,
+}
+
+/// How XCLAIM and XAUTOCLAIM update the pending entries they claim.
+#[derive(Clone, Copy)]
+pub struct Claim {
+    /// Only entries delivered at least this many milliseconds ago are claimed.
+    pub min_idle: u64,
+    /// Unix time in milliseconds to record as the last delivery.
+    pub delivery_time: u64,
+    /// Set the delivery count, rather than counting one more delivery.
+    pub delivery_count: Option<u64>,
+    /// Whether only the IDs are returned, in which case the entries are not counted as delivered again.
+    pub just_id: bool,
+}
+
+impl ConsumerGroup {
+    fn new(last_delivered_id: StreamId, entries_read: Option<u64>) -> ConsumerGroup {
+        ConsumerGroup { last_delivered_id, entries_read, pending: BTreeMap::new(), consumers: BTreeMap::new() }
+    }
+
+    /// Create the consumer, returning false if it exists already.
+    pub fn create_consumer(&mut self, name: &[u8], now: u64) -> bool {
+        if self.consumers.contains_key(name) {
+            return false;
+        }
+        self.consumers.insert(name.to_vec(), Consumer { seen_time: now, active_time: None, pending: BTreeSet::new() });
+        true
+    }
+
+    /// Note that the consumer tried to read or claim entries, creating it if needed.
+    fn see_consumer(&mut self, name: &[u8], now: u64) -> &mut Consumer {
+        self.create_consumer(name, now);
+        let consumer = self.consumers.get_mut(name).expect("Consumer created above");
+        consumer.seen_time = now;
+        consumer
+    }
+
+    /// Delete the consumer and its pending entries, returning how many it had or None if there is no such
+    /// consumer.
+    pub fn delete_consumer(&mut self, name: &[u8]) -> Option<usize> {
+        let consumer = self.consumers.remove(name)?;
+        for id in &consumer.pending {
+            self.pending.remove(id);
+        }
+        Some(consumer.pending.len())
+    }
+
+    /// Acknowledge the entry, returning false if it was not pending.
+    pub fn acknowledge(&mut self, id: StreamId) -> bool {
+        let Some(entry) = self.pending.remove(&id) else {
+            return false;
+        };
+        if let Some(consumer) = self.consumers.get_mut(&entry.consumer) {
+            consumer.pending.remove(&id);
+        }
+        true
+    }
+
+    /// Record that the entry was delivered to the consumer, moving it from its previous consumer if
+    /// it was pending already.
+    fn deliver(&mut self, id: StreamId, consumer: &[u8], now: u64, count: impl FnOnce(u64) -> u64) {
+        let entry = self.pending.entry(id).or_insert_with(|| PendingEntry {
+            consumer: consumer.to_vec(),
+            delivery_time: now,
+            delivery_count: 0,
+        });
+        entry.delivery_time = now;
+        entry.delivery_count = count(entry.delivery_count);
+        if entry.consumer != consumer {
+            let previous = std::mem::replace(&mut entry.consumer, consumer.to_vec());
+            if let Some(previous) = self.consumers.get_mut(&previous) {
+                previous.pending.remove(&id);
+            }
+        }
+        let consumer = self.consumers.get_mut(consumer).expect("Consumers are seen before being delivered entries");
+        consumer.pending.insert(id);
+        consumer.active_time = Some(now);
+    }
+}
+
+/// An append-only log of entries ordered by ID.
+#[derive(Clone, Default)]
+pub struct Stream {
+    /// Nodes keyed by the ID of the first entry they were created with, which stays a lower bound of
+    /// their IDs when entries are deleted.
+    nodes: BTreeMap<StreamId, Node>,
+    length: usize,
+    /// The ID of the last entry ever added, which new IDs must be greater than even if it was deleted.
+    last_id: StreamId,
+    /// The largest ID deleted with XDEL.
+    max_deleted_id: StreamId,
+    /// How many entries were ever added.
+    entries_added: u64,
+    groups: BTreeMap<Vec<u8>, ConsumerGroup>,
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    pub fn max_deleted_id(&self) -> StreamId {
+        self.max_deleted_id
+    }
+
+    pub fn entries_added(&self) -> u64 {
+        self.entries_added
+    }
+
+    /// Number of nodes the entries are packed in.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The ID for a new entry when XADD is given `*`: the current time, or right after the last ID if
+    /// the clock is behind it. None if the stream has used up every ID.
+    pub fn next_id(&self, now: u64) -> Option<StreamId> {
+        if now > self.last_id.ms {
+            Some(StreamId { ms: now, seq: 0 })
+        } else {
+            self.last_id.next()
+        }
+    }
+
+    /// The ID for a new entry when XADD is given `ms-*`. None if that millisecond is behind the last ID
+    /// or has used up its sequence numbers.
+    pub fn next_id_at(&self, ms: u64) -> Option<StreamId> {
+        match ms.cmp(&self.last_id.ms) {
+            std::cmp::Ordering::Greater => Some(StreamId { ms, seq: 0 }),
+            std::cmp::Ordering::Equal => Some(StreamId { ms, seq: self.last_id.seq.checked_add(1)? }),
+            std::cmp::Ordering::Less => None,
+        }
+    }
+
+    /// Append an entry, whose ID must be greater than the last one.
+    pub fn add(&mut self, id: StreamId, fields: Fields, limits: NodeLimits) {
+        let size = entry_size(&fields);
+        match self.nodes.last_entry() {
+            Some(mut last) if limits.fit(last.get(), size) => {
+                let node = last.get_mut();
+                node.entries.push((id, fields));
+                node.bytes += size;
+            }
+            _ => {
+                self.nodes.insert(id, Node { entries: vec![(id, fields)], bytes: size });
+            }
+        }
+        self.length += 1;
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    /// The entries with an ID between `start` and `end` included, from the last one if `reverse` is set.
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        reverse: bool,
+    ) -> Box<dyn Iterator<Item = (StreamId, &Fields)> + '_> {
+        if start > end {
+            return Box::new(std::iter::empty());
+        }
+        if reverse {
+            let entries = self.nodes.range(..=end).rev().flat_map(|(_, node)| node.entries.iter().rev());
+            let entries = entries.skip_while(move |(id, _)| *id > end).take_while(move |(id, _)| *id >= start);
+            Box::new(entries.map(|(id, fields)| (*id, fields)))
+        } else {
+            // The node holding `start`, if any, is the last one keyed at or before it.
+            let first = self.nodes.range(..=start).next_back().map_or(start, |(key, _)| *key);
+            let entries = self.nodes.range(first..).flat_map(|(_, node)| node.entries.iter());
+            let entries = entries.skip_while(move |(id, _)| *id < start).take_while(move |(id, _)| *id <= end);
+            Box::new(entries.map(|(id, fields)| (*id, fields)))
+        }
+    }
+
+    pub fn first_entry(&self) -> Option<(StreamId, &Fields)> {
+        self.range(StreamId::MIN, StreamId::MAX, false).next()
+    }
+
+    pub fn last_entry(&self) -> Option<(StreamId, &Fields)> {
+        self.range(StreamId::MIN, StreamId::MAX, true).next()
+    }
+
+    pub fn get(&self, id: StreamId) -> Option<&Fields> {
+        self.range(id, id, false).next().map(|(_, fields)| fields)
+    }
+
+    /// Delete the entry, returning false if there is none with that ID.
+    pub fn delete(&mut self, id: StreamId) -> bool {
+        let Some((&key, node)) = self.nodes.range_mut(..=id).next_back() else {
+            return false;
+        };
+        let Ok(position) = node.entries.binary_search_by_key(&id, |(id, _)| *id) else {
+            return false;
+        };
+        let (_, fields) = node.entries.remove(position);
+        node.bytes -= entry_size(&fields);
+        if node.entries.is_empty() {
+            self.nodes.remove(&key);
+        }
+        self.length -= 1;
+        self.max_deleted_id = self.max_deleted_id.max(id);
+        true
+    }
+
+    /// Trim the stream from its oldest entries, returning how many were removed.
+    /// An approximate trim only removes whole nodes, which is much cheaper, so it may leave a few more entries
+    /// than asked for. It then stops before removing more than `limit` entries, if given.
+    pub fn trim(&mut self, trim: Trim, approximate: bool, limit: Option<usize>) -> usize {
+        let mut removed = 0;
+        while let Some(mut first) = self.nodes.first_entry() {
+            let node = first.get_mut();
+            // How many of the node's entries have to go.
+            let excess = match trim {
+                Trim::MaxLen(max_length) => self.length.saturating_sub(max_length).min(node.entries.len()),
+                Trim::MinId(min_id) => node.entries.partition_point(|(id, _)| *id < min_id),
+            };
+            if excess == node.entries.len() {
+                if limit.is_some_and(|limit| removed + excess > limit) {
+                    break;
+                }
+                first.remove();
+            } else if approximate || excess == 0 {
+                break;
+            } else {
+                for (_, fields) in node.entries.drain(..excess) {
+                    node.bytes -= entry_size(&fields);
+                }
+            }
+            removed += excess;
+            self.length -= excess;
+        }
+        removed
+    }
+
+    /// Whether entries were deleted between `start` and the end of the stream, making the position of
+    /// later entries unknown.
+    fn has_tombstones(&self, start: StreamId) -> bool {
+        !self.is_empty() && self.max_deleted_id != StreamId::MIN && self.max_deleted_id >= start
+    }
+
+    /// How many entries were added up to the one with the ID, if it can be told without counting them.
+    /// Like in Redis, that is only possible at either end of the stream when nothing was deleted in between.
+    pub fn estimate_entries_read(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 || (self.is_empty() && id <= self.last_id) || id == self.last_id {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+        let first_id = self.first_entry().map_or(StreamId::MIN, |(id, _)| id);
+        if self.max_deleted_id != StreamId::MIN && self.max_deleted_id >= first_id {
+            return None;
+        }
+        let before_first = self.entries_added - self.length as u64;
+        match id.cmp(&first_id) {
+            std::cmp::Ordering::Less => Some(before_first),
+            std::cmp::Ordering::Equal => Some(before_first + 1),
+            std::cmp::Ordering::Greater => None,
+        }
+    }
+
+    /// How many entries the group still has to read, if that can be told.
+    pub fn lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        let entries_read = match group.entries_read {
+            Some(entries_read) if !self.has_tombstones(group.last_delivered_id) => Some(entries_read),
+            _ => self.estimate_entries_read(group.last_delivered_id),
+        };
+        entries_read.map(|entries_read| self.entries_added.saturating_sub(entries_read))
+    }
+
+    pub fn group(&self, name: &[u8]) -> Option<&ConsumerGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn group_mut(&mut self, name: &[u8]) -> Option<&mut ConsumerGroup> {
+        self.groups.get_mut(name)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&Vec<u8>, &ConsumerGroup)> {
+        self.groups.iter()
+    }
+
+    /// Create a group that will deliver the entries after `last_delivered_id`, returning false if there
+    /// is a group with that name already.
+    pub fn create_group(&mut self, name: &[u8], last_delivered_id: StreamId, entries_read: Option<u64>) -> bool {
+        if self.groups.contains_key(name) {
+            return false;
+        }
+        self.groups.insert(name.to_vec(), ConsumerGroup::new(last_delivered_id, entries_read));
+        true
+    }
+
+    pub fn destroy_group(&mut self, name: &[u8]) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    /// Deliver up to `count` of the entries the group has not delivered yet to the consumer, adding them
+    /// to the pending entries list unless `no_ack` is set. None if there is no such group.
+    pub fn read_group(
+        &mut self,
+        group: &[u8],
+        consumer: &[u8],
+        count: usize,
+        no_ack: bool,
+        now: u64,
+    ) -> Option<Vec<(StreamId, Fields)>> {
+        self.groups.get_mut(group)?.see_consumer(consumer, now);
+        let start = self.groups[group].last_delivered_id.next();
+        let entries = match start {
+            Some(start) => self.range(start, StreamId::MAX, false).take(count).map(|(id, fields)| (id, fields.clone())).collect(),
+            None => Vec::new(),
+        };
+        for (id, _) in &entries {
+            let counted = !self.has_tombstones(*id);
+            let estimate = self.estimate_entries_read(*id);
+            let group = self.groups.get_mut(group).expect("Group checked above");
+            group.entries_read = match group.entries_read {
+                Some(entries_read) if counted => Some(entries_read + 1),
+                _ => estimate,
+            };
+            group.last_delivered_id = *id;
+            if no_ack {
+                group.consumers.get_mut(consumer).expect("Consumer seen above").active_time = Some(now);
+            } else {
+                group.deliver(*id, consumer, now, |_| 1);
+            }
+        }
+        Some(entries)
+    }
+
+    /// Deliver again up to `count` of the entries pending for the consumer with an ID greater than `after`.
+    /// Entries deleted from the stream since have no fields. None if there is no such group.
+    pub fn read_pending(
+        &mut self,
+        group: &[u8],
+        consumer: &[u8],
+        after: StreamId,
+        count: usize,
+        now: u64,
+    ) -> Option<Vec<(StreamId, Option<Fields>)>> {
+        let group = self.groups.get_mut(group)?;
+        let pending = &group.see_consumer(consumer, now).pending;
+        let ids = pending.range((std::ops::Bound::Excluded(after), std::ops::Bound::Unbounded)).take(count).copied().collect::<Vec<_>>();
+        for id in &ids {
+            group.deliver(*id, consumer, now, |count| count + 1);
+        }
+        Some(ids.into_iter().map(|id| (id, self.get(id).cloned())).collect())
+    }
+
+    /// Transfer the given pending entries idle for long enough to the consumer,
+    /// returning the IDs of those claimed. With `force`, entries of the stream that are not pending are
+    /// claimed too. Entries deleted from the stream are dropped from the pending entries list instead.
+    /// None if there is no such group.
+    pub fn claim(
+        &mut self,
+        group: &[u8],
+        consumer: &[u8],
+        ids: &[StreamId],
+        claim: Claim,
+        force: bool,
+        now: u64,
+    ) -> Option<Vec<StreamId>> {
+        self.groups.get_mut(group)?.see_consumer(consumer, now);
+        let mut claimed = Vec::new();
+        for id in ids {
+            let exists = self.get(*id).is_some();
+            let group = self.groups.get_mut(group).expect("Group checked above");
+            match group.pending.get(id) {
+                None if force && exists => {}
+                None => continue,
+                Some(_) if !exists => {
+                    group.acknowledge(*id);
+                    continue;
+                }
+                Some(entry) if now.saturating_sub(entry.delivery_time) < claim.min_idle => continue,
+                Some(_) => {}
+            }
+            group.deliver(*id, consumer, claim.delivery_time, |count| {
+                claim.delivery_count.unwrap_or(if claim.just_id { count } else { count + 1 })
+            });
+            claimed.push(*id);
+        }
+        Some(claimed)
+    }
+
+    /// Scan the pending entries list from `start` for up to `count` entries idle for long enough,
+    /// transferring them to the consumer. At most ten times `count` entries are looked at.
+    /// Returns the ID to continue the scan from, 0-0 once done, the IDs of the entries claimed, and the IDs
+    /// of the entries that were dropped from the list because they were deleted from the stream.
+    /// None if there is no such group.
+    pub fn auto_claim(
+        &mut self,
+        group: &[u8],
+        consumer: &[u8],
+        start: StreamId,
+        count: usize,
+        claim: Claim,
+        now: u64,
+    ) -> Option<(StreamId, Vec<StreamId>, Vec<StreamId>)> {
+        self.groups.get_mut(group)?.see_consumer(consumer, now);
+        let candidates = self.groups[group].pending.range(start..).take(count.saturating_mul(10)).map(|(id, _)| *id);
+        let candidates = candidates.collect::<Vec<_>>();
+        let (mut claimed, mut deleted) = (Vec::new(), Vec::new());
+        let mut last_examined = None;
+        for id in candidates {
+            if claimed.len() == count {
+                break;
+            }
+            last_examined = Some(id);
+            let exists = self.get(id).is_some();
+            let group = self.groups.get_mut(group).expect("Group checked above");
+            if !exists {
+                group.acknowledge(id);
+                deleted.push(id);
+            } else if now.saturating_sub(group.pending[&id].delivery_time) >= claim.min_idle {
+                group.deliver(id, consumer, claim.delivery_time, |count| if claim.just_id { count } else { count + 1 });
+                claimed.push(id);
+            }
+        }
+        let next = match last_examined.and_then(StreamId::next) {
+            Some(after) => self.groups[group].pending.range(after..).next().map_or(StreamId::MIN, |(id, _)| *id),
+            None => StreamId::MIN,
+        };
+        Some((next, claimed, deleted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const LIMITS: NodeLimits = NodeLimits { max_bytes: 0, max_entries: 10 };
+
+    fn stream_of(length: u64) -> Stream {
+        let mut stream = Stream::default();
+        for ms in 1..=length {
+            stream.add(StreamId { ms, seq: 0 }, vec![b"field".to_vec(), b"value".to_vec()], LIMITS);
+        }
+        stream
+    }
+
+    fn ids(stream: &Stream, start: u64, end: u64, reverse: bool) -> Vec<u64> {
+        let range = stream.range(StreamId { ms: start, seq: 0 }, StreamId { ms: end, seq: 0 }, reverse);
+        range.map(|(id, _)| id.ms).collect()
+    }
+
+    #[rstest]
+    #[case("5-3", 0, Some(StreamId { ms: 5, seq: 3 }))]
+    #[case("5", 7, Some(StreamId { ms: 5, seq: 7 }))]
+    #[case("5-", 0, None)]
+    #[case("-3", 0, None)]
+    #[case("+5-3", 0, None)]
+    #[case("18446744073709551616", 0, None)]
+    fn test_parse_id(#[case] input: &str, #[case] missing_seq: u64, #[case] expected: Option<StreamId>) {
+        assert_eq!(StreamId::parse(input.as_bytes(), missing_seq), expected);
+    }
+
+    #[test]
+    fn test_range_across_nodes() {
+        let mut stream = stream_of(35);
+        assert_eq!(stream.nodes.len(), 4);
+        assert_eq!(ids(&stream, 8, 12, false), vec![8, 9, 10, 11, 12]);
+        assert_eq!(ids(&stream, 8, 12, true), vec![12, 11, 10, 9, 8]);
+        assert!(stream.delete(StreamId { ms: 11, seq: 0 }));
+        assert!(!stream.delete(StreamId { ms: 11, seq: 0 }));
+        assert_eq!(ids(&stream, 10, 13, false), vec![10, 12, 13]);
+        assert_eq!(stream.len(), 34);
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut stream = stream_of(35);
+        // Only whole nodes go with an approximate trim, so 25 entries are kept rather than 22.
+        assert_eq!(stream.trim(Trim::MaxLen(22), true, None), 10);
+        assert_eq!(stream.len(), 25);
+        assert_eq!(stream.trim(Trim::MaxLen(0), true, Some(15)), 10);
+        assert_eq!(stream.trim(Trim::MinId(StreamId { ms: 33, seq: 0 }), false, None), 12);
+        assert_eq!(ids(&stream, 0, 100, false), vec![33, 34, 35]);
+        assert_eq!(stream.trim(Trim::MaxLen(5), false, None), 0);
+    }
+
+    #[test]
+    fn test_group_lag() {
+        let mut stream = stream_of(5);
+        stream.create_group(b"group", StreamId::MIN, None);
+        assert_eq!(stream.lag(stream.group(b"group").unwrap()), Some(5));
+        stream.read_group(b"group", b"consumer", 2, false, 0);
+        assert_eq!(stream.lag(stream.group(b"group").unwrap()), Some(3));
+        // Once an entry the group has still to read is deleted, its lag is unknown until it catches up.
+        stream.delete(StreamId { ms: 4, seq: 0 });
+        assert_eq!(stream.lag(stream.group(b"group").unwrap()), None);
+        stream.read_group(b"group", b"consumer", 10, false, 0);
+        assert_eq!(stream.lag(stream.group(b"group").unwrap()), Some(0));
+        assert_eq!(stream.group(b"group").unwrap().pending.len(), 4);
+    }
+}